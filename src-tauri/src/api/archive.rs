@@ -0,0 +1,20 @@
+use crate::core::ArchiveState;
+use crate::services::archive::{ArchiveQuery, ArchivedEvent};
+use tauri::State;
+
+/// 查询消息归档
+///
+/// 可按会话、房间、观众 `open_id`、消息类型、时间范围组合查询，
+/// 设置 `text` 时对弹幕做全文检索
+#[tauri::command]
+pub async fn query_archive(
+    archive_state: State<'_, ArchiveState>,
+    query: ArchiveQuery,
+) -> Result<Vec<ArchivedEvent>, String> {
+    let archive = archive_state
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| "消息归档不可用".to_string())?;
+    archive.query(query).await
+}
@@ -1,35 +1,23 @@
-use crate::core::ClientState;
-use crate::services::bilibili::{BilibiliClient, BilibiliConfig};
-use serde::{Deserialize, Serialize};
-use tauri::{Emitter, State};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct OpenAIConfig {
-    pub api_url: String,
-    pub api_key: String,
-    pub model: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TtsConfig {
-    pub api_url: String,
-    pub model: String,
-    pub voice: String,
-    pub response_format: String,
-    pub speed: String,
-    pub authorization: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AppConfig {
-    pub id_code: String,
-    pub app_id: u64,
-    pub access_key: String,
-    pub access_secret: String,
-    pub host: String,
-    pub openai: Option<OpenAIConfig>,
-    pub indextts: Option<TtsConfig>,
-}
+use crate::core::{
+    ArchiveState, BilibiliMessage, ClientState, ConnectionEvent, DEFAULT_SESSION, ParseDiagnostics,
+    ParseDiagnosticsSnapshot, SessionEvent, StatsState, SuperChatState, ViewerState,
+};
+use crate::services::bilibili::{BilibiliClient, BilibiliError};
+use crate::services::combo::ComboFinished;
+use crate::services::config::AppConfig;
+use crate::services::heartbeat::HeartbeatStatsSnapshot;
+use crate::services::pipeline::{MessageBatch, PipelineStatsSnapshot};
+use crate::services::session::{SessionContext, SessionOutput, run_session};
+use crate::services::source::{LiveEvent, LiveEventSource};
+use crate::services::stats::{LiveStats, SessionStatsSnapshot};
+use crate::services::viewer::ViewerProfile;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+
+/// `bilibili-stats` 事件的推送间隔
+const STATS_REPORT_INTERVAL: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Serialize)]
 pub struct BilibiliResponse {
@@ -37,67 +25,263 @@ pub struct BilibiliResponse {
     pub message: String,
 }
 
+/// 会话结束时的汇总，随 `bilibili-session-ended` 事件发送
+#[derive(Debug, Serialize)]
+pub struct SessionSummary {
+    /// 结束原因
+    pub reason: String,
+    pub room_id: Option<i64>,
+    /// 最终统计，不包含分钟序列
+    pub stats: Option<SessionStatsSnapshot>,
+    /// 录制文件路径，未录制时为 `None`
+    pub record_path: Option<String>,
+}
+
+/// 一个会话的连接状态
+#[derive(Debug, Serialize)]
+pub struct SessionStatus {
+    pub session: String,
+    /// 主播直播间号，开启游戏会话后才能获取
+    pub room_id: Option<i64>,
+    pub status: ConnectionEvent,
+    pub pipeline: PipelineStatsSnapshot,
+    pub heartbeat: HeartbeatStatsSnapshot,
+}
+
+fn session_name(session: Option<String>) -> String {
+    session
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| DEFAULT_SESSION.to_string())
+}
+
+/// 把会话输出转成发给前端的事件
+struct TauriOutput {
+    app_handle: AppHandle,
+    session: String,
+}
+
+impl TauriOutput {
+    fn emit<T: Serialize>(&self, event: &str, payload: &T, what: &str) {
+        let payload = SessionEvent::new(&self.session, payload);
+        if let Err(e) = self.app_handle.emit(event, &payload) {
+            log::error!("发送{}到前端失败: {}", what, e);
+        }
+    }
+}
+
+impl SessionOutput for TauriOutput {
+    fn connection(&self, event: &ConnectionEvent) {
+        self.emit("bilibili-connection", event, "连接状态");
+    }
+
+    fn messages(&self, messages: &[BilibiliMessage]) {
+        self.emit("bilibili-messages", &MessageBatch { messages }, "消息");
+    }
+
+    fn viewer_returned(&self, profile: &ViewerProfile) {
+        // 隔了一段时间再次出现的老观众，前端可据此打招呼
+        self.emit("bilibili-viewer-returned", profile, "观众回访事件");
+    }
+
+    fn combo_finished(&self, summary: &ComboFinished) {
+        self.emit("bilibili-combo", summary, "连击汇总");
+    }
+}
+
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn connect_bilibili(
     config: AppConfig,
+    session: Option<String>,
     client_state: State<'_, ClientState>,
-    app_handle: tauri::AppHandle,
-) -> Result<BilibiliResponse, String> {
-    let bili_config = BilibiliConfig {
-        id_code: config.id_code,
-        app_id: config.app_id,
-        access_key: config.access_key,
-        access_secret: config.access_secret,
-        host: config.host,
-    };
+    superchat_state: State<'_, SuperChatState>,
+    stats_state: State<'_, StatsState>,
+    archive_state: State<'_, ArchiveState>,
+    viewer_state: State<'_, ViewerState>,
+    app_handle: AppHandle,
+) -> Result<BilibiliResponse, BilibiliError> {
+    let session = session_name(session);
+
+    // 先开始新一代统计，旧会话的收尾任务据此得知自己已被替换，不再发送会话汇总
+    let stats = stats_state.inner().clone();
+    let generation = stats.start(&session);
 
+    // 同名会话已存在时先关闭旧连接
+    let previous = client_state.lock().await.remove(&session);
+    if let Some(previous) = previous {
+        log::info!("会话 {} 已存在，关闭旧连接", session);
+        if let Err(e) = previous.close().await {
+            log::warn!("关闭旧连接失败: {}", e);
+        }
+    }
+
+    let bili_config = config.bilibili_config();
+    let pipeline = bili_config.pipeline.clone();
+    let output = Arc::new(TauriOutput {
+        app_handle: app_handle.clone(),
+        session: session.clone(),
+    });
     let mut client = BilibiliClient::new(bili_config);
+    if let Err(e) = LiveEventSource::connect(&mut client).await {
+        log::error!("会话 {} 连接失败: {}", session, e);
+        stats.finish(&session, generation);
+        // 连接失败时不会再读取事件，直接把最终状态告诉前端
+        output.connection(&client.handle().connection_state());
+        return Err(e);
+    }
+    let handle = client.handle();
 
-    match client.connect().await {
-        Ok(receiver) => {
-            // 启动消息处理任务
-            let app_handle_clone = app_handle.clone();
-            tokio::spawn(async move {
-                let mut receiver: tokio::sync::mpsc::UnboundedReceiver<
-                    crate::core::proto::BilibiliMessage,
-                > = receiver;
-                while let Some(message) = receiver.recv().await {
-                    // 发送消息到前端
-                    if let Err(e) = app_handle_clone.emit("bilibili-message", &message) {
-                        log::error!("发送消息到前端失败: {}", e);
-                    }
-                }
-            });
+    let context = SessionContext {
+        session: session.clone(),
+        stats: stats.clone(),
+        archive: archive_state.lock().await.clone(),
+        viewers: viewer_state.inner().clone(),
+        superchats: superchat_state.inner().clone(),
+        pipeline,
+        pipeline_stats: handle.pipeline_stats(),
+        combo: config.combo.unwrap_or_default(),
+    };
+    let mut summary = SessionSummary {
+        reason: String::new(),
+        room_id: handle.room_id().await,
+        stats: None,
+        record_path: config.record_path,
+    };
+    spawn_stats_reporter(&app_handle, &session, stats.clone(), generation);
+
+    // 先保存客户端，会话立即结束时收尾任务也能找到它；
+    // 连接期间另一个同名连接可能已经完成，被替换的客户端需要关闭
+    let replaced = client_state.lock().await.insert(session.clone(), handle);
+    if let Some(replaced) = replaced {
+        log::info!("会话 {} 被新的连接替换，关闭旧连接", session);
+        if let Err(e) = replaced.close().await {
+            log::warn!("关闭被替换的连接失败: {}", e);
+        }
+    }
 
-            // 保存客户端状态
-            *client_state.lock().await = Some(client);
+    let clients = client_state.inner().clone();
+    let message_session = session.clone();
+    tokio::spawn(async move {
+        let end_reason = run_session(&mut client, context, output.clone()).await;
 
-            Ok(BilibiliResponse {
-                success: true,
-                message: "连接成功".to_string(),
-            })
+        // 直播结束、互动结束、重连失败或主动断开后统一收尾
+        let finished =
+            close_finished_client(&clients, &stats, &message_session, generation, end_reason).await;
+        // 收尾时关闭连接产生的最终状态也要送到前端
+        while let Some(event) = client.next_event().await {
+            if let LiveEvent::Connection(event) = event {
+                output.connection(&event);
+            }
         }
-        Err(e) => {
-            log::error!("连接失败: {}", e);
-            Err(format!("连接失败: {}", e))
+        summary.reason = match finished {
+            FinishedClient::Superseded => {
+                log::info!("会话 {} 已被新的连接替换", message_session);
+                return;
+            }
+            FinishedClient::Disconnected => "主动断开连接".to_string(),
+            FinishedClient::Closed(reason) => reason,
+        };
+
+        stats.finish(&message_session, generation);
+        summary.stats = stats.snapshot(&message_session, false);
+        if let Some(snapshot) = &summary.stats {
+            output.emit("bilibili-stats", snapshot, "直播统计");
+        }
+        log::info!("会话 {} 已结束: {}", message_session, summary.reason);
+        output.emit("bilibili-session-ended", &summary, "会话汇总");
+    });
+
+    Ok(BilibiliResponse {
+        success: true,
+        message: format!("会话 {} 连接成功", session),
+    })
+}
+
+/// 会话收尾时客户端的去向
+enum FinishedClient {
+    /// 同名会话已开始新的连接，旧会话不再发送汇总
+    Superseded,
+    /// 客户端已被主动断开
+    Disconnected,
+    /// 客户端由收尾任务关闭，附带最终的关闭原因
+    Closed(String),
+}
+
+/// 消息通道结束或收到结束消息后，关闭仍留在 `ClientState` 中的客户端
+///
+/// 被同名会话替换或已主动断开时客户端已被移除，这里只报告去向，不做任何关闭操作
+async fn close_finished_client(
+    clients: &ClientState,
+    stats: &LiveStats,
+    session: &str,
+    generation: u64,
+    end_reason: Option<&str>,
+) -> FinishedClient {
+    // 持有锁时检查统计代数，避免误关同名的新会话
+    let client = {
+        let mut clients = clients.lock().await;
+        if !stats.is_running(session, generation) {
+            return FinishedClient::Superseded;
         }
+        clients.remove(session)
+    };
+    let Some(client) = client else {
+        return FinishedClient::Disconnected;
+    };
+
+    let reason = match (end_reason, client.connection_state()) {
+        (Some(reason), _) => reason.to_string(),
+        (None, ConnectionEvent::Closed { reason, .. }) => reason,
+        (None, _) => "连接已关闭".to_string(),
+    };
+    if let Err(e) = client.close_with_reason(&reason).await {
+        log::warn!("关闭会话 {} 失败: {}", session, e);
     }
+    FinishedClient::Closed(reason)
+}
+
+/// 定时推送 `bilibili-stats` 事件，会话结束或被同名会话替换后停止
+fn spawn_stats_reporter(
+    app_handle: &AppHandle,
+    session: &str,
+    stats: Arc<LiveStats>,
+    generation: u64,
+) {
+    let app_handle = app_handle.clone();
+    let session = session.to_string();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(STATS_REPORT_INTERVAL);
+        loop {
+            interval.tick().await;
+            if !stats.is_running(&session, generation) {
+                break;
+            }
+            if let Some(snapshot) = stats.snapshot(&session, false) {
+                let payload = SessionEvent::new(&session, &snapshot);
+                if let Err(e) = app_handle.emit("bilibili-stats", &payload) {
+                    log::error!("发送直播统计到前端失败: {}", e);
+                }
+            }
+        }
+    });
 }
 
 #[tauri::command]
 pub async fn disconnect_bilibili(
+    session: Option<String>,
     client_state: State<'_, ClientState>,
-) -> Result<BilibiliResponse, String> {
-    let mut client_guard = client_state.lock().await;
-    if let Some(mut client) = client_guard.take() {
+) -> Result<BilibiliResponse, BilibiliError> {
+    let session = session_name(session);
+    let client = client_state.lock().await.remove(&session);
+    if let Some(client) = client {
         match client.close().await {
             Ok(_) => Ok(BilibiliResponse {
                 success: true,
-                message: "断开连接成功".to_string(),
+                message: format!("会话 {} 断开连接成功", session),
             }),
             Err(e) => {
-                log::error!("断开连接失败: {}", e);
-                Err(format!("断开连接失败: {}", e))
+                log::error!("会话 {} 断开连接失败: {}", session, e);
+                Err(e)
             }
         }
     } else {
@@ -109,7 +293,53 @@ pub async fn disconnect_bilibili(
 }
 
 #[tauri::command]
-pub async fn get_connection_status(client_state: State<'_, ClientState>) -> Result<bool, String> {
+pub async fn get_connection_status(
+    session: Option<String>,
+    client_state: State<'_, ClientState>,
+) -> Result<ConnectionEvent, String> {
+    let session = session_name(session);
+    let client_guard = client_state.lock().await;
+    Ok(match client_guard.get(&session) {
+        Some(client) => client.connection_state(),
+        None => ConnectionEvent::closed("未连接"),
+    })
+}
+
+/// 获取会话的心跳健康状态和延迟，会话不存在时返回 `None`
+#[tauri::command]
+pub async fn get_heartbeat_stats(
+    session: Option<String>,
+    client_state: State<'_, ClientState>,
+) -> Result<Option<HeartbeatStatsSnapshot>, String> {
+    let session = session_name(session);
+    let client_guard = client_state.lock().await;
+    Ok(client_guard
+        .get(&session)
+        .map(|client| client.heartbeat_stats()))
+}
+
+/// 列出所有会话及其连接状态
+#[tauri::command]
+pub async fn list_bilibili_sessions(
+    client_state: State<'_, ClientState>,
+) -> Result<Vec<SessionStatus>, String> {
     let client_guard = client_state.lock().await;
-    Ok(client_guard.is_some())
+    let mut sessions = Vec::with_capacity(client_guard.len());
+    for (session, client) in client_guard.iter() {
+        sessions.push(SessionStatus {
+            session: session.clone(),
+            room_id: client.room_id().await,
+            status: client.connection_state(),
+            pipeline: client.pipeline_stats().snapshot(),
+            heartbeat: client.heartbeat_stats(),
+        });
+    }
+    sessions.sort_by(|a, b| a.session.cmp(&b.session));
+    Ok(sessions)
+}
+
+/// 获取消息解析诊断计数
+#[tauri::command]
+pub async fn get_parse_diagnostics() -> Result<ParseDiagnosticsSnapshot, String> {
+    Ok(ParseDiagnostics::global().snapshot())
 }
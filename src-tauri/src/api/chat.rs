@@ -0,0 +1,304 @@
+//! 对话
+//!
+//! `chat_with_openai` 一次返回完整回复。进行中的流式请求按 `stream_id` 登记，增量内容通过 `openai-stream-delta` 事件推送给前端，
+//! 调用 `cancel_chat_stream` 可以随时中止。需要语音时回复会按句切分，每句生成完就送去合成，
+//! 音频按序号通过 `tts-audio-chunk` 事件推送。没有生成完就放弃的回复通过 `openai-stream-cancelled` 事件通知前端。
+
+use crate::api::config::{load_openai_config, load_persona_config};
+use crate::api::memory::MemoryScope;
+use crate::core::{ChatStreamState, DEFAULT_SESSION, MemoryState, StatsState, ViewerState};
+use crate::services::config::{OpenAIConfig, TtsConfig};
+use crate::services::openai::{
+    OpenAIMessage, SamplingParams, chat_completion, chat_completion_stream,
+};
+use crate::services::persona::{PromptBuilder, PromptContext};
+use crate::services::sentence::SentenceSplitter;
+use crate::services::stats::LiveStats;
+use crate::services::tts::{SpeechChunk, synthesize_sentences};
+use crate::services::viewer::ViewerRegistry;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Serialize)]
+pub struct ChatResponse {
+    pub success: bool,
+    pub message: String,
+    pub content: Option<String>,
+}
+
+/// 推送给前端的增量内容
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatStreamDelta<'a> {
+    pub stream_id: &'a str,
+    pub delta: &'a str,
+}
+
+/// 推送给前端的一句语音，`SpeechChunk` 的字段平铺在同一层
+#[derive(Debug, Clone, Serialize)]
+pub struct SpeechChunkEvent<'a> {
+    pub stream_id: &'a str,
+    #[serde(flatten)]
+    pub chunk: &'a SpeechChunk,
+}
+
+/// 推送给前端的取消通知，已经推送的内容和语音不再有后续
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatStreamCancelled<'a> {
+    pub stream_id: &'a str,
+    pub reason: &'a str,
+}
+
+/// 一次可取消的流式回复
+#[derive(Clone, Copy)]
+pub(crate) struct ChatStream<'a> {
+    pub app_handle: &'a AppHandle,
+    pub streams: &'a ChatStreamState,
+    pub stream_id: &'a str,
+}
+
+impl ChatStream<'_> {
+    /// 流式生成回复并逐段推送，被取消时返回 `Ok(None)`
+    pub async fn reply(
+        &self,
+        client: &reqwest::Client,
+        config: &OpenAIConfig,
+        messages: Vec<OpenAIMessage>,
+    ) -> Result<Option<String>, String> {
+        self.reply_with(client, config, messages, |_| {}).await
+    }
+
+    /// 流式生成回复并逐句合成语音，返回完整回复和语音段数，被取消时返回 `Ok(None)`
+    ///
+    /// 合成与生成同时进行，第一句生成完就开始合成；取消或出错时尚未合成的句子直接放弃。
+    pub async fn reply_and_speak(
+        &self,
+        client: &reqwest::Client,
+        openai_config: &OpenAIConfig,
+        tts_config: &TtsConfig,
+        messages: Vec<OpenAIMessage>,
+    ) -> Result<Option<(String, u32)>, String> {
+        let (sentence_tx, sentence_rx) = mpsc::unbounded_channel();
+        let speaker = synthesize_sentences(client, tts_config, sentence_rx, |chunk| {
+            log::info!("第 {} 句语音合成完成: {}", chunk.sequence, chunk.text);
+            let payload = SpeechChunkEvent {
+                stream_id: self.stream_id,
+                chunk: &chunk,
+            };
+            if let Err(e) = self.app_handle.emit("tts-audio-chunk", &payload) {
+                log::error!("发送语音到前端失败: {}", e);
+            }
+        });
+        let chat = async move {
+            let mut splitter = SentenceSplitter::default();
+            let result = self
+                .reply_with(client, openai_config, messages, |delta| {
+                    for sentence in splitter.push(delta) {
+                        let _ = sentence_tx.send(sentence);
+                    }
+                })
+                .await;
+            if let Ok(Some(_)) = &result
+                && let Some(sentence) = splitter.finish()
+            {
+                let _ = sentence_tx.send(sentence);
+            }
+            // 离开这里时发送端关闭，合成完剩余句子后 speaker 结束
+            result
+        };
+        tokio::pin!(speaker, chat);
+
+        let mut chunks = None;
+        let result = loop {
+            tokio::select! {
+                result = &mut chat => break result,
+                count = &mut speaker, if chunks.is_none() => chunks = Some(count),
+            }
+        };
+        match result {
+            Ok(Some(content)) => {
+                let chunks = match chunks {
+                    Some(chunks) => chunks,
+                    None => speaker.await,
+                };
+                Ok(Some((content, chunks)))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 通知前端本次回复已被放弃
+    pub fn cancelled(&self, reason: &str) {
+        let payload = ChatStreamCancelled {
+            stream_id: self.stream_id,
+            reason,
+        };
+        if let Err(e) = self.app_handle.emit("openai-stream-cancelled", &payload) {
+            log::error!("发送取消通知到前端失败: {}", e);
+        }
+    }
+
+    /// 流式生成回复，每收到一段增量内容先推送给前端，再交给 `on_text`
+    async fn reply_with(
+        &self,
+        client: &reqwest::Client,
+        config: &OpenAIConfig,
+        messages: Vec<OpenAIMessage>,
+        mut on_text: impl FnMut(&str) + Send,
+    ) -> Result<Option<String>, String> {
+        let on_delta = |delta: &str| {
+            let payload = ChatStreamDelta {
+                stream_id: self.stream_id,
+                delta,
+            };
+            if let Err(e) = self.app_handle.emit("openai-stream-delta", &payload) {
+                log::error!("发送流式内容到前端失败: {}", e);
+            }
+            on_text(delta);
+        };
+        let result = self
+            .streams
+            .run(
+                self.stream_id,
+                chat_completion_stream(client, config, messages, on_delta),
+            )
+            .await;
+        if result.is_none() {
+            self.cancelled("对话已取消");
+        }
+        result.transpose()
+    }
+}
+
+/// 根据会话统计中的直播信息和观众档案生成提示词上下文
+pub(crate) fn prompt_context(
+    session: Option<&str>,
+    open_id: Option<&str>,
+    stats: &LiveStats,
+    viewers: &ViewerRegistry,
+) -> PromptContext {
+    let mut context = PromptContext::default();
+    if let Some(snapshot) = stats.snapshot(session.unwrap_or(DEFAULT_SESSION), false) {
+        context.stream_title = snapshot.title;
+        context.area_name = snapshot.area_name;
+    }
+    match open_id.and_then(|open_id| viewers.get(open_id)) {
+        Some(profile) => context.with_viewer(&profile),
+        None => context,
+    }
+}
+
+/// 对话，`session` 决定使用哪段对话记忆和哪场直播的标题、分区，为空时使用默认会话
+///
+/// `sampling` 中设置了的项覆盖配置文件中的采样参数，只对本次调用生效。
+#[tauri::command]
+pub async fn chat_with_openai(
+    message: String,
+    session: Option<String>,
+    sampling: Option<SamplingParams>,
+    stats_state: State<'_, StatsState>,
+    viewer_state: State<'_, ViewerState>,
+    memory_state: State<'_, MemoryState>,
+) -> Result<ChatResponse, String> {
+    // 从配置文件读取OpenAI配置
+    let openai_config = match load_openai_config().await {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("加载OpenAI配置失败: {}", e);
+            return Err(format!("加载OpenAI配置失败: {}", e));
+        }
+    };
+
+    let persona = load_persona_config().await?;
+    let memory = MemoryScope::load(&memory_state, session.as_deref(), None).await?;
+    let history = memory
+        .as_ref()
+        .map(MemoryScope::history)
+        .unwrap_or_default();
+    let context = prompt_context(session.as_deref(), None, &stats_state, &viewer_state);
+    let messages =
+        PromptBuilder::new(persona).build_with_history(&context, history, message.clone());
+
+    let request_config = openai_config.with_sampling(sampling.as_ref());
+    let content = chat_completion(&reqwest::Client::new(), &request_config, messages).await?;
+    if let Some(memory) = &memory {
+        memory.remember(message, content.clone(), &openai_config);
+    }
+    Ok(ChatResponse {
+        success: true,
+        message: "对话成功".to_string(),
+        content: Some(content),
+    })
+}
+
+/// 流式对话，增量内容通过 `openai-stream-delta` 事件推送，返回完整回复
+///
+/// `session` 决定使用哪段对话记忆和哪场直播的标题、分区，为空时使用默认会话；`sampling` 覆盖本次调用的采样参数。
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub async fn chat_with_openai_stream(
+    message: String,
+    stream_id: String,
+    session: Option<String>,
+    sampling: Option<SamplingParams>,
+    app_handle: AppHandle,
+    stream_state: State<'_, ChatStreamState>,
+    stats_state: State<'_, StatsState>,
+    viewer_state: State<'_, ViewerState>,
+    memory_state: State<'_, MemoryState>,
+) -> Result<ChatResponse, String> {
+    let openai_config = load_openai_config().await.map_err(|e| {
+        log::error!("加载OpenAI配置失败: {}", e);
+        format!("加载OpenAI配置失败: {}", e)
+    })?;
+
+    let persona = load_persona_config().await?;
+    let memory = MemoryScope::load(&memory_state, session.as_deref(), None).await?;
+    let history = memory
+        .as_ref()
+        .map(MemoryScope::history)
+        .unwrap_or_default();
+    let context = prompt_context(session.as_deref(), None, &stats_state, &viewer_state);
+    let messages =
+        PromptBuilder::new(persona).build_with_history(&context, history, message.clone());
+    let stream = ChatStream {
+        app_handle: &app_handle,
+        streams: stream_state.inner(),
+        stream_id: &stream_id,
+    };
+    let content = stream
+        .reply(
+            &reqwest::Client::new(),
+            &openai_config.with_sampling(sampling.as_ref()),
+            messages,
+        )
+        .await?;
+
+    // 取消的对话不计入记忆
+    if let (Some(memory), Some(content)) = (&memory, &content) {
+        memory.remember(message, content.clone(), &openai_config);
+    }
+
+    Ok(match content {
+        Some(content) => ChatResponse {
+            success: true,
+            message: "对话成功".to_string(),
+            content: Some(content),
+        },
+        None => ChatResponse {
+            success: false,
+            message: "对话已取消".to_string(),
+            content: None,
+        },
+    })
+}
+
+/// 取消进行中的流式对话，返回是否找到了对应的请求
+#[tauri::command]
+pub async fn cancel_chat_stream(
+    stream_id: String,
+    stream_state: State<'_, ChatStreamState>,
+) -> Result<bool, String> {
+    Ok(stream_state.cancel(&stream_id))
+}
@@ -1,41 +1,12 @@
-use crate::api::bilibili::{AppConfig, OpenAIConfig, TtsConfig};
+use crate::services::config::{self, AppConfig, OpenAIConfig, TtsConfig};
+use crate::services::memory::MemoryConfig;
+use crate::services::persona::PersonaConfig;
 use std::fs;
 use std::path::PathBuf;
 
 /// 统一的配置加载器 - 从config.json加载完整配置
 async fn load_config_internal() -> Result<AppConfig, String> {
-    // 尝试从多个位置读取配置文件
-    let possible_paths = vec![
-        PathBuf::from("config.json"),           // 项目根目录
-        PathBuf::from("src-tauri/config.json"), // Tauri 目录
-        PathBuf::from("config/config.json"),    // 配置目录
-        PathBuf::from("../config.json"),        // 上级目录
-    ];
-
-    for config_path in possible_paths {
-        if config_path.exists() {
-            log::info!("找到配置文件: {:?}", config_path);
-
-            match fs::read_to_string(&config_path) {
-                Ok(content) => match serde_json::from_str::<AppConfig>(&content) {
-                    Ok(config) => {
-                        log::info!("成功加载配置文件");
-                        return Ok(config);
-                    }
-                    Err(e) => {
-                        log::error!("解析配置文件失败: {}", e);
-                        return Err(format!("解析配置文件失败: {}", e));
-                    }
-                },
-                Err(e) => {
-                    log::error!("读取配置文件失败: {}", e);
-                    return Err(format!("读取配置文件失败: {}", e));
-                }
-            }
-        }
-    }
-
-    Err("未找到配置文件".to_string())
+    config::load_app_config()
 }
 
 /// 加载完整配置 - Tauri命令
@@ -70,6 +41,18 @@ pub async fn load_tts_config() -> Result<TtsConfig, String> {
         .ok_or_else(|| "配置文件中未找到IndexTTS配置".to_string())
 }
 
+/// 加载人设配置 - 内部使用，未配置时使用默认人设
+pub async fn load_persona_config() -> Result<PersonaConfig, String> {
+    let config = load_config_internal().await?;
+    Ok(config.persona.unwrap_or_default())
+}
+
+/// 加载对话记忆配置 - 内部使用，未配置时使用默认配置
+pub async fn load_memory_config() -> Result<MemoryConfig, String> {
+    let config = load_config_internal().await?;
+    Ok(config.memory.unwrap_or_default())
+}
+
 #[tauri::command]
 pub async fn save_config_to_file(config: AppConfig) -> Result<String, String> {
     let config_path = PathBuf::from("config.json");
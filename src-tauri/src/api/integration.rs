@@ -1,7 +1,13 @@
-use crate::api::config::{load_openai_config, load_tts_config};
-use crate::services::openai::{OpenAIMessage, OpenAIRequest, OpenAIResponse};
-use crate::services::tts::TtsRequest;
+use crate::api::chat::{ChatStream, prompt_context};
+use crate::api::config::{load_openai_config, load_persona_config, load_tts_config};
+use crate::api::memory::MemoryScope;
+use crate::core::{ChatStreamState, MemoryState, StatsState, SuperChatState, ViewerState};
+use crate::services::config::OpenAIConfig;
+use crate::services::openai::{SamplingParams, chat_completion};
+use crate::services::persona::{PromptBuilder, PromptContext};
+use crate::services::tts::synthesize;
 use serde::Serialize;
+use tauri::{AppHandle, State};
 
 // 整合对话和TTS的响应结构
 #[derive(Debug, Serialize)]
@@ -10,10 +16,112 @@ pub struct ChatAndSpeakResponse {
     pub message: String,
     pub chat_content: Option<String>,
     pub audio_data: Option<Vec<u8>>, // 直接返回音频字节数组
+    /// 流式生成时语音按句通过 `tts-audio-chunk` 事件推送，这里是推送的段数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_chunks: Option<u32>,
 }
 
+/// 已经生成、尚未写入对话记忆的一轮对话
+struct Turn {
+    user: String,
+    assistant: String,
+    openai_config: OpenAIConfig,
+}
+
+/// 确认回复有效后把这一轮对话写入记忆
+fn remember(
+    memory: Option<&MemoryScope>,
+    result: Result<(ChatAndSpeakResponse, Option<Turn>), String>,
+) -> Result<ChatAndSpeakResponse, String> {
+    let (response, turn) = result?;
+    if let (Some(memory), Some(turn)) = (memory, turn) {
+        memory.remember(turn.user, turn.assistant, &turn.openai_config);
+    }
+    Ok(response)
+}
+
+/// 对话并生成语音
+///
+/// 回复的是醒目留言时传入 `super_chat_id`，留言被撤回后立即放弃生成，不返回任何内容。
+/// 传入 `stream_id` 时以流式方式生成回复，增量内容通过 `openai-stream-delta` 事件推送，
+/// 每生成完一句就开始合成，音频按序号通过 `tts-audio-chunk` 事件推送，不再随结果返回；
+/// 可以用 `cancel_chat_stream` 取消；流式生成中途留言被撤回时发送 `openai-stream-cancelled` 事件。
+///
+/// `session` 和 `open_id` 用于在系统提示词中加入直播标题和发言观众的档案，并选择对话记忆；
+/// `sampling` 覆盖本次调用的采样参数。
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
-pub async fn chat_and_speak(message: String) -> Result<ChatAndSpeakResponse, String> {
+pub async fn chat_and_speak(
+    message: String,
+    super_chat_id: Option<i64>,
+    stream_id: Option<String>,
+    session: Option<String>,
+    open_id: Option<String>,
+    sampling: Option<SamplingParams>,
+    app_handle: AppHandle,
+    superchat_state: State<'_, SuperChatState>,
+    stream_state: State<'_, ChatStreamState>,
+    stats_state: State<'_, StatsState>,
+    viewer_state: State<'_, ViewerState>,
+    memory_state: State<'_, MemoryState>,
+) -> Result<ChatAndSpeakResponse, String> {
+    let memory = MemoryScope::load(&memory_state, session.as_deref(), open_id.as_deref()).await?;
+    let context = prompt_context(
+        session.as_deref(),
+        open_id.as_deref(),
+        &stats_state,
+        &viewer_state,
+    );
+    let stream = stream_id.as_deref().map(|stream_id| ChatStream {
+        app_handle: &app_handle,
+        streams: stream_state.inner(),
+        stream_id,
+    });
+    let Some(message_id) = super_chat_id else {
+        let result = run_chat_and_speak(message, context, memory.as_ref(), sampling, stream).await;
+        return remember(memory.as_ref(), result);
+    };
+
+    let retracted = || {
+        log::info!("醒目留言 {} 已撤回，放弃回复", message_id);
+        // 已经推送的内容和语音作废，流式登记项随被丢弃的请求一起移除
+        if let Some(stream) = &stream {
+            stream.cancelled("醒目留言已撤回");
+        }
+        Ok(ChatAndSpeakResponse {
+            success: false,
+            message: "醒目留言已撤回".to_string(),
+            chat_content: None,
+            audio_data: None,
+            audio_chunks: None,
+        })
+    };
+
+    if superchat_state.is_retracted(message_id) {
+        return retracted();
+    }
+
+    tokio::select! {
+        result = run_chat_and_speak(message, context, memory.as_ref(), sampling, stream) => {
+            // 生成完成的瞬间也可能刚好被撤回，撤回的留言不写入对话记忆
+            if superchat_state.is_retracted(message_id) {
+                retracted()
+            } else {
+                remember(memory.as_ref(), result)
+            }
+        }
+        _ = superchat_state.wait_retracted(message_id) => retracted(),
+    }
+}
+
+/// 生成回复和语音，返回的对话轮次由调用方决定是否写入记忆
+async fn run_chat_and_speak(
+    message: String,
+    context: PromptContext,
+    memory: Option<&MemoryScope>,
+    sampling: Option<SamplingParams>,
+    stream: Option<ChatStream<'_>>,
+) -> Result<(ChatAndSpeakResponse, Option<Turn>), String> {
     // 第一步：从配置文件读取OpenAI配置
     log::info!("开始整合对话和TTS流程，用户消息: {}", message);
 
@@ -34,114 +142,72 @@ pub async fn chat_and_speak(message: String) -> Result<ChatAndSpeakResponse, Str
         }
     };
 
-    let openai_request = OpenAIRequest {
-        model: openai_config.model.clone(),
-        messages: vec![OpenAIMessage {
-            role: "user".to_string(),
-            content: message,
-        }],
-    };
-
     // 创建HTTP客户端
     let client = reqwest::Client::new();
 
     log::info!("发送OpenAI API请求，模型: {}", openai_config.model);
+    // 单次调用的采样参数只用于本次请求，对话摘要仍使用配置中的参数
+    let request_config = openai_config.with_sampling(sampling.as_ref());
+
+    // 加入人设、直播间上下文和对话记忆
+    let persona = load_persona_config().await?;
+    let history = memory.map(MemoryScope::history).unwrap_or_default();
+    let messages =
+        PromptBuilder::new(persona).build_with_history(&context, history, message.clone());
+    // 多位观众共用记忆时需要知道每句话是谁说的
+    let remembered = match &context.viewer_name {
+        Some(name) => format!("{}：{}", name, message),
+        None => message,
+    };
 
     // 调用 OpenAI API
-    let chat_content = match client
-        .post(&openai_config.api_url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", openai_config.api_key))
-        .json(&openai_request)
-        .send()
-        .await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.json::<OpenAIResponse>().await {
-                    Ok(openai_response) => {
-                        log::info!("OpenAI API请求成功");
-
-                        if let Some(choice) = openai_response.choices.first() {
-                            log::info!("AI回复: {}", choice.message.content);
-                            choice.message.content.clone()
-                        } else {
-                            return Err("OpenAI API返回空响应".to_string());
-                        }
-                    }
-                    Err(e) => {
-                        log::error!("解析OpenAI API响应失败: {}", e);
-                        return Err(format!("解析OpenAI API响应失败: {}", e));
-                    }
-                }
-            } else {
-                let status = response.status();
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "未知错误".to_string());
-                log::error!("OpenAI API请求失败: {} - {}", status, error_text);
-                return Err(format!("OpenAI API请求失败: {} - {}", status, error_text));
-            }
-        }
-        Err(e) => {
-            log::error!("发送OpenAI API请求失败: {}", e);
-            return Err(format!("发送OpenAI API请求失败: {}", e));
-        }
+    // 流式生成时边生成边按句合成
+    if let Some(stream) = stream {
+        let reply = stream
+            .reply_and_speak(&client, &request_config, &tts_config, messages)
+            .await?;
+        let Some((chat_content, audio_chunks)) = reply else {
+            let response = ChatAndSpeakResponse {
+                success: false,
+                message: "对话已取消".to_string(),
+                chat_content: None,
+                audio_data: None,
+                audio_chunks: None,
+            };
+            return Ok((response, None));
+        };
+        log::info!("AI回复: {}", chat_content);
+        log::info!("整合流程完成，语音共 {} 段", audio_chunks);
+        let turn = Turn {
+            user: remembered,
+            assistant: chat_content.clone(),
+            openai_config,
+        };
+        let response = ChatAndSpeakResponse {
+            success: true,
+            message: format!("对话成功，语音已分 {} 段推送", audio_chunks),
+            chat_content: Some(chat_content),
+            audio_data: None,
+            audio_chunks: Some(audio_chunks),
+        };
+        return Ok((response, Some(turn)));
+    }
+
+    let chat_content = chat_completion(&client, &request_config, messages).await?;
+    log::info!("AI回复: {}", chat_content);
+    let turn = Turn {
+        user: remembered,
+        assistant: chat_content.clone(),
+        openai_config,
     };
 
     // 第二步：将 AI 回复转换为语音
     log::info!("开始将AI回复转换为语音: {}", chat_content);
 
-    let tts_request = TtsRequest {
-        model: tts_config.model.clone(),
-        input: chat_content.clone(),
-        voice: tts_config.voice.clone(),
-        response_format: tts_config.response_format.clone(),
-        speed: tts_config.speed.clone(),
-    };
-
-    // 调用 TTS API
-    let audio_data = match client
-        .post(&tts_config.api_url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", &tts_config.authorization)
-        .json(&tts_request)
-        .send()
+    // TTS失败不影响对话结果，继续返回文本
+    let audio_data = synthesize(&client, &tts_config, chat_content.clone())
         .await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.bytes().await {
-                    Ok(audio_bytes) => {
-                        log::info!("TTS请求成功，接收到 {} 字节的音频数据", audio_bytes.len());
-
-                        // 直接返回字节数组
-                        Some(audio_bytes.to_vec())
-                    }
-                    Err(e) => {
-                        log::error!("读取TTS音频数据失败: {}", e);
-                        // TTS失败不影响对话结果，继续返回文本
-                        None
-                    }
-                }
-            } else {
-                let status = response.status();
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "未知错误".to_string());
-                log::error!("TTS请求失败: {} - {}", status, error_text);
-                // TTS失败不影响对话结果，继续返回文本
-                None
-            }
-        }
-        Err(e) => {
-            log::error!("发送TTS请求失败: {}", e);
-            // TTS失败不影响对话结果，继续返回文本
-            None
-        }
-    };
+        .ok();
 
     // 返回整合结果
     let success_message = if audio_data.is_some() {
@@ -152,10 +218,12 @@ pub async fn chat_and_speak(message: String) -> Result<ChatAndSpeakResponse, Str
 
     log::info!("整合流程完成: {}", success_message);
 
-    Ok(ChatAndSpeakResponse {
+    let response = ChatAndSpeakResponse {
         success: true,
         message: success_message,
         chat_content: Some(chat_content),
         audio_data,
-    })
+        audio_chunks: None,
+    };
+    Ok((response, Some(turn)))
 }
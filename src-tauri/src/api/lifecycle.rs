@@ -0,0 +1,113 @@
+//! 应用退出处理
+//!
+//! 退出前关闭所有直播间连接（调用 `/v2/app/end` 结束游戏会话），停止代理和模拟服务，
+//! 并等待归档和观众档案写入完成。整个过程有时间上限，超时后直接退出。
+
+use crate::core::{ArchiveState, ClientState, MockServerState, ProxyState, ViewerState};
+use futures_util::future::join_all;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, RunEvent};
+
+/// 退出清理的时间上限
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+static SHUTDOWN_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// 关闭所有连接和服务，多次调用时只有第一次生效
+pub async fn shutdown(app_handle: &AppHandle) {
+    if SHUTDOWN_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    log::info!("=== 应用退出，开始清理 ===");
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, run_shutdown(app_handle))
+        .await
+        .is_err()
+    {
+        log::warn!("退出清理超过 {:?}，放弃剩余步骤", SHUTDOWN_TIMEOUT);
+    } else {
+        log::info!("退出清理完成");
+    }
+}
+
+async fn run_shutdown(app_handle: &AppHandle) {
+    // 取出所有客户端后再关闭，避免关闭期间一直持有锁
+    let clients: Vec<_> = match app_handle.try_state::<ClientState>() {
+        Some(state) => state.lock().await.drain().collect(),
+        None => Vec::new(),
+    };
+    join_all(clients.into_iter().map(|(session, client)| async move {
+        if let Err(e) = client.close().await {
+            log::warn!("关闭会话 {} 失败: {}", session, e);
+        }
+    }))
+    .await;
+
+    if let Some(state) = app_handle.try_state::<ProxyState>()
+        && let Some(mut proxy_server) = state.lock().await.take()
+    {
+        proxy_server.stop();
+    }
+    if let Some(state) = app_handle.try_state::<MockServerState>()
+        && let Some(mut mock_server) = state.lock().await.take()
+    {
+        mock_server.stop();
+    }
+
+    if let Some(state) = app_handle.try_state::<ArchiveState>() {
+        let archive = state.lock().await.clone();
+        if let Some(archive) = archive {
+            archive.flush().await;
+        }
+    }
+    if let Some(state) = app_handle.try_state::<ViewerState>() {
+        state.flush().await;
+    }
+}
+
+/// 处理应用运行事件：拦截第一次退出请求，清理完成后再退出
+pub fn handle_run_event(app_handle: &AppHandle, event: RunEvent) {
+    match event {
+        RunEvent::ExitRequested { code, api, .. } => {
+            if SHUTDOWN_STARTED.load(Ordering::SeqCst) {
+                return;
+            }
+            api.prevent_exit();
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                shutdown(&app_handle).await;
+                app_handle.exit(code.unwrap_or(0));
+            });
+        }
+        // 没有经过退出请求（例如直接调用 exit）时在这里同步清理
+        RunEvent::Exit if !SHUTDOWN_STARTED.load(Ordering::SeqCst) => {
+            tauri::async_runtime::block_on(shutdown(app_handle));
+        }
+        _ => {}
+    }
+}
+
+/// 安装 panic 钩子，在 `panic = "abort"` 下进程终止前尽量结束游戏会话
+///
+/// 会展开的 panic 只影响出错的任务，不做清理。
+pub fn install_panic_hook(app_handle: &AppHandle) {
+    if !cfg!(panic = "abort") {
+        return;
+    }
+
+    let app_handle = app_handle.clone();
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        // 清理在异步运行时中进行，当前线程只负责等待
+        let (done, wait) = std::sync::mpsc::channel();
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            shutdown(&app_handle).await;
+            let _ = done.send(());
+        });
+        let _ = wait.recv_timeout(SHUTDOWN_TIMEOUT);
+    }));
+}
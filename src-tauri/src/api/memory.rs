@@ -0,0 +1,100 @@
+use crate::api::config::load_memory_config;
+use crate::core::{DEFAULT_SESSION, MemoryState};
+use crate::services::config::OpenAIConfig;
+use crate::services::memory::{ConversationKey, ConversationSnapshot, MemoryConfig, summarize};
+use crate::services::openai::OpenAIMessage;
+use tauri::State;
+
+/// 一次对话用到的记忆
+pub(crate) struct MemoryScope {
+    store: MemoryState,
+    key: ConversationKey,
+    config: MemoryConfig,
+}
+
+impl MemoryScope {
+    /// 按配置确定对话所属的记忆，未启用对话记忆时返回 `None`
+    pub async fn load(
+        store: &MemoryState,
+        session: Option<&str>,
+        open_id: Option<&str>,
+    ) -> Result<Option<Self>, String> {
+        let config = load_memory_config().await?;
+        if !config.enabled {
+            return Ok(None);
+        }
+        let key = ConversationKey {
+            session: session.unwrap_or(DEFAULT_SESSION).to_string(),
+            open_id: open_id
+                .filter(|_| config.per_viewer)
+                .map(|open_id| open_id.to_string()),
+        };
+        Ok(Some(Self {
+            store: store.clone(),
+            key,
+            config,
+        }))
+    }
+
+    pub fn history(&self) -> Vec<OpenAIMessage> {
+        self.store.history(&self.key)
+    }
+
+    /// 记录一轮对话，有对话移出窗口时在后台合并进摘要
+    pub fn remember(&self, user: String, assistant: String, openai_config: &OpenAIConfig) {
+        if !self.store.append(&self.key, user, assistant, &self.config) {
+            return;
+        }
+        let Some(job) = self.store.take_for_summary(&self.key) else {
+            return;
+        };
+
+        let store = self.store.clone();
+        let openai_config = openai_config.clone();
+        let max_chars = self.config.summary_max_chars;
+        tokio::spawn(async move {
+            let result = summarize(&reqwest::Client::new(), &openai_config, &job, max_chars).await;
+            match &result {
+                Ok(summary) => log::info!("对话摘要已更新 {:?}: {}", job.key, summary),
+                Err(e) => log::warn!("生成对话摘要失败，下次再试: {}", e),
+            }
+            store.finish_summary(job, result);
+        });
+    }
+}
+
+/// 查看一段对话记忆，`open_id` 为空时为整个会话共用的对话
+#[tauri::command]
+pub async fn get_conversation(
+    memory_state: State<'_, MemoryState>,
+    session: Option<String>,
+    open_id: Option<String>,
+) -> Result<Option<ConversationSnapshot>, String> {
+    let key = ConversationKey {
+        session: session.unwrap_or_else(|| DEFAULT_SESSION.to_string()),
+        open_id,
+    };
+    Ok(memory_state.get(&key))
+}
+
+/// 列出对话记忆，最近更新的在前
+#[tauri::command]
+pub async fn list_conversations(
+    memory_state: State<'_, MemoryState>,
+    session: Option<String>,
+) -> Result<Vec<ConversationSnapshot>, String> {
+    Ok(memory_state.list(session.as_deref()))
+}
+
+/// 清空对话记忆，`open_id` 为空时清空整个会话的记忆，返回清空的对话数
+#[tauri::command]
+pub async fn reset_conversation(
+    memory_state: State<'_, MemoryState>,
+    session: Option<String>,
+    open_id: Option<String>,
+) -> Result<usize, String> {
+    let session = session.as_deref().unwrap_or(DEFAULT_SESSION);
+    let removed = memory_state.reset(session, open_id.as_deref());
+    log::info!("已清空会话 {} 的 {} 段对话记忆", session, removed);
+    Ok(removed)
+}
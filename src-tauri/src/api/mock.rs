@@ -0,0 +1,64 @@
+use crate::api::bilibili::BilibiliResponse;
+use crate::core::MockServerState;
+use crate::services::mock::{MockConfig, MockServer};
+use tauri::State;
+
+/// 启动本地模拟开放平台，成功时 `message` 为可填入 `host` 的地址
+#[tauri::command]
+pub async fn start_mock_server(
+    mock_state: State<'_, MockServerState>,
+    port: Option<u16>,
+    config: Option<MockConfig>,
+) -> Result<BilibiliResponse, String> {
+    let mut mock_guard = mock_state.lock().await;
+
+    if mock_guard.is_some() {
+        return Ok(BilibiliResponse {
+            success: false,
+            message: "模拟开放平台已经在运行".to_string(),
+        });
+    }
+
+    let mut mock_server = MockServer::new();
+    mock_server
+        .start(port.unwrap_or(0), config.unwrap_or_default())
+        .await
+        .map_err(|e| format!("模拟开放平台启动失败: {}", e))?;
+
+    let host = mock_server.host().unwrap_or_default();
+    *mock_guard = Some(mock_server);
+
+    Ok(BilibiliResponse {
+        success: true,
+        message: host,
+    })
+}
+
+#[tauri::command]
+pub async fn stop_mock_server(
+    mock_state: State<'_, MockServerState>,
+) -> Result<BilibiliResponse, String> {
+    let mut mock_guard = mock_state.lock().await;
+
+    if let Some(mut mock_server) = mock_guard.take() {
+        mock_server.stop();
+        Ok(BilibiliResponse {
+            success: true,
+            message: "模拟开放平台已停止".to_string(),
+        })
+    } else {
+        Ok(BilibiliResponse {
+            success: false,
+            message: "模拟开放平台未运行".to_string(),
+        })
+    }
+}
+
+/// 返回模拟开放平台的地址，未运行时为 `None`
+#[tauri::command]
+pub async fn get_mock_server_status(
+    mock_state: State<'_, MockServerState>,
+) -> Result<Option<String>, String> {
+    let mock_guard = mock_state.lock().await;
+    Ok(mock_guard.as_ref().and_then(MockServer::host))
+}
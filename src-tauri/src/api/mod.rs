@@ -2,13 +2,30 @@
 //!
 //! 包含Tauri命令处理器和接口定义
 
+pub mod archive;
 pub mod bilibili;
+pub mod chat;
 pub mod config;
 pub mod integration;
+pub mod lifecycle;
+pub mod memory;
+pub mod mock;
 pub mod proxy;
+pub mod stats;
+pub mod superchat;
+pub mod tts;
+pub mod viewer;
 
 // 重新导出API处理器
+pub use archive::*;
 pub use bilibili::*;
+pub use chat::*;
 pub use config::*;
 pub use integration::*;
+pub use memory::*;
+pub use mock::*;
 pub use proxy::*;
+pub use stats::*;
+pub use superchat::*;
+pub use tts::*;
+pub use viewer::*;
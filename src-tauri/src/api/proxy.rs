@@ -19,16 +19,14 @@ pub async fn start_proxy_server(
         });
     }
 
+    // 绑定端口后服务在后台运行，保存的实例用于停止服务
     let mut proxy_server = ProxyServer::new();
+    proxy_server
+        .start(port)
+        .await
+        .map_err(|e| format!("代理服务启动失败: {}", e))?;
 
-    // 在后台启动代理服务
-    tokio::spawn(async move {
-        if let Err(e) = proxy_server.start(port).await {
-            log::error!("代理服务启动失败: {}", e);
-        }
-    });
-
-    *proxy_guard = Some(ProxyServer::new());
+    *proxy_guard = Some(proxy_server);
 
     Ok(BilibiliResponse {
         success: true,
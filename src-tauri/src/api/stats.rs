@@ -0,0 +1,22 @@
+use crate::core::{DEFAULT_SESSION, StatsState};
+use crate::services::stats::SessionStatsSnapshot;
+use tauri::State;
+
+/// 获取一个会话的直播统计，默认包含按分钟的时间序列
+#[tauri::command]
+pub async fn get_live_stats(
+    stats_state: State<'_, StatsState>,
+    session: Option<String>,
+    with_minutes: Option<bool>,
+) -> Result<Option<SessionStatsSnapshot>, String> {
+    let session = session.unwrap_or_else(|| DEFAULT_SESSION.to_string());
+    Ok(stats_state.snapshot(&session, with_minutes.unwrap_or(true)))
+}
+
+/// 获取所有会话的直播统计，不包含分钟序列
+#[tauri::command]
+pub async fn list_live_stats(
+    stats_state: State<'_, StatsState>,
+) -> Result<Vec<SessionStatsSnapshot>, String> {
+    Ok(stats_state.list(false))
+}
@@ -0,0 +1,21 @@
+use crate::core::SuperChatState;
+use crate::services::superchat::{SuperChatEntry, SuperChatStatus};
+use tauri::State;
+
+/// 列出醒目留言，默认只返回展示期内且未撤回的留言
+#[tauri::command]
+pub async fn list_super_chats(
+    superchat_state: State<'_, SuperChatState>,
+    include_inactive: Option<bool>,
+) -> Result<Vec<SuperChatEntry>, String> {
+    Ok(superchat_state.list(include_inactive.unwrap_or(false)))
+}
+
+/// 查询一条醒目留言的状态，朗读前应确认不是 `retracted`
+#[tauri::command]
+pub async fn get_super_chat_status(
+    superchat_state: State<'_, SuperChatState>,
+    message_id: i64,
+) -> Result<Option<SuperChatStatus>, String> {
+    Ok(superchat_state.status(message_id))
+}
@@ -0,0 +1,31 @@
+use crate::api::config::load_tts_config;
+use crate::services::tts::synthesize;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct TtsResponse {
+    pub success: bool,
+    pub message: String,
+    pub audio_data: Option<Vec<u8>>, // 直接返回字节数组
+}
+
+#[tauri::command]
+pub async fn text_to_speech(text: String) -> Result<TtsResponse, String> {
+    // 从配置文件读取TTS配置
+    let tts_config = match load_tts_config().await {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("加载IndexTTS配置失败: {}", e);
+            return Err(format!("加载IndexTTS配置失败: {}", e));
+        }
+    };
+
+    let audio_data = synthesize(&reqwest::Client::new(), &tts_config, text).await?;
+
+    // 直接返回字节数组
+    Ok(TtsResponse {
+        success: true,
+        message: "文本转语音成功".to_string(),
+        audio_data: Some(audio_data),
+    })
+}
@@ -0,0 +1,28 @@
+use crate::core::ViewerState;
+use crate::services::viewer::{ViewerOrder, ViewerProfile};
+use tauri::State;
+
+/// 列表默认返回的档案数量
+const DEFAULT_VIEWER_LIMIT: usize = 50;
+
+/// 按 `open_id` 获取观众档案
+#[tauri::command]
+pub async fn get_viewer_profile(
+    viewer_state: State<'_, ViewerState>,
+    open_id: String,
+) -> Result<Option<ViewerProfile>, String> {
+    Ok(viewer_state.get(&open_id))
+}
+
+/// 列出观众档案，默认按最近出现时间排序
+#[tauri::command]
+pub async fn list_viewers(
+    viewer_state: State<'_, ViewerState>,
+    order: Option<ViewerOrder>,
+    limit: Option<usize>,
+) -> Result<Vec<ViewerProfile>, String> {
+    Ok(viewer_state.list(
+        order.unwrap_or_default(),
+        limit.unwrap_or(DEFAULT_VIEWER_LIMIT),
+    ))
+}
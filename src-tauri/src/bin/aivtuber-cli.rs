@@ -0,0 +1,355 @@
+//! 无界面的命令行程序
+//!
+//! 读取与图形界面相同的 `config.json`，连接直播间，把弹幕和醒目留言交给 AI 回复并合成语音，
+//! 音频写入输出目录或推送到本地地址，日志输出到标准输出。
+//!
+//! ```text
+//! aivtuber-cli [--config <路径>] [--output-dir <目录>] [--post-url <地址>] [--replay <录制文件>]
+//! ```
+
+use aivtuber_lib::core::BilibiliMessage;
+use aivtuber_lib::services::bilibili::BilibiliClient;
+use aivtuber_lib::services::config::{self, AppConfig, OpenAIConfig, TtsConfig};
+use aivtuber_lib::services::openai::{OpenAIMessage, chat_completion};
+use aivtuber_lib::services::persona::{PromptBuilder, PromptContext};
+use aivtuber_lib::services::recorder::ReplayConfig;
+use aivtuber_lib::services::source::{LiveEvent, LiveEventSource, ReplaySource};
+use aivtuber_lib::services::superchat::SuperChatRegistry;
+use aivtuber_lib::services::tts::synthesize;
+use aivtuber_lib::services::viewer::ViewerRegistry;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// 等待回复的弹幕数量上限，超出后丢弃新弹幕，醒目留言不受限制
+const QUEUE_CAPACITY: usize = 16;
+
+/// 命令行程序中醒目留言登记使用的会话名
+const SESSION: &str = "cli";
+
+const USAGE: &str = "用法: aivtuber-cli [--config <路径>] [--output-dir <目录>] [--post-url <地址>] [--replay <录制文件>]";
+
+/// 输出到标准输出的日志
+struct StdoutLogger;
+
+impl log::Log for StdoutLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Info
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            println!(
+                "[{}] [{}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StdoutLogger = StdoutLogger;
+
+#[derive(Default)]
+struct Args {
+    config: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    post_url: Option<String>,
+    replay: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| format!("参数 {} 缺少取值\n{}", arg, USAGE))
+        };
+        match arg.as_str() {
+            "--config" => args.config = Some(value()?.into()),
+            "--output-dir" => args.output_dir = Some(value()?.into()),
+            "--post-url" => args.post_url = Some(value()?),
+            "--replay" => args.replay = Some(value()?.into()),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ => return Err(format!("未知参数: {}\n{}", arg, USAGE)),
+        }
+    }
+    // 两种输出都没指定时默认写入 output 目录
+    if args.output_dir.is_none() && args.post_url.is_none() {
+        args.output_dir = Some(PathBuf::from("output"));
+    }
+    Ok(args)
+}
+
+/// 一条等待回复的消息
+struct Prompt {
+    /// 日志中显示的内容
+    text: String,
+    super_chat_id: Option<i64>,
+    messages: Vec<OpenAIMessage>,
+}
+
+impl Prompt {
+    /// 为弹幕和醒目留言生成提示词，`live` 为当前的直播间上下文
+    fn from_message(
+        message: &BilibiliMessage,
+        prompts: &PromptBuilder,
+        live: &PromptContext,
+        viewers: &ViewerRegistry,
+    ) -> Option<Self> {
+        let (open_id, uname, content, super_chat_id) = match message {
+            BilibiliMessage::Danmaku { data } => {
+                (&data.open_id, &data.uname, data.msg.clone(), None)
+            }
+            BilibiliMessage::SuperChat { data } => (
+                &data.open_id,
+                &data.uname,
+                format!("（{}元醒目留言）{}", data.rmb, data.message),
+                Some(data.message_id),
+            ),
+            _ => return None,
+        };
+        let context = match viewers.get(open_id) {
+            Some(profile) => live.clone().with_viewer(&profile),
+            None => PromptContext {
+                viewer_name: Some(uname.clone()),
+                ..live.clone()
+            },
+        };
+        Some(Self {
+            text: format!("{}: {}", uname, content),
+            super_chat_id,
+            messages: prompts.build(&context, content),
+        })
+    }
+}
+
+/// 回复生成所需的配置和输出位置
+struct Responder {
+    client: reqwest::Client,
+    openai: OpenAIConfig,
+    tts: TtsConfig,
+    output_dir: Option<PathBuf>,
+    post_url: Option<String>,
+    superchats: Arc<SuperChatRegistry>,
+}
+
+impl Responder {
+    async fn run(self, mut prompts: mpsc::Receiver<Prompt>) {
+        let mut sequence = 0u64;
+        while let Some(prompt) = prompts.recv().await {
+            let retracted = |id: Option<i64>| id.is_some_and(|id| self.superchats.is_retracted(id));
+            if retracted(prompt.super_chat_id) {
+                log::info!("醒目留言已撤回，跳过: {}", prompt.text);
+                continue;
+            }
+
+            let Some(audio) = self.respond(&prompt).await else {
+                continue;
+            };
+            // 生成期间留言也可能被撤回
+            if retracted(prompt.super_chat_id) {
+                log::info!("醒目留言已撤回，丢弃生成的语音: {}", prompt.text);
+                continue;
+            }
+
+            sequence += 1;
+            self.deliver(sequence, audio).await;
+        }
+    }
+
+    async fn respond(&self, prompt: &Prompt) -> Option<Vec<u8>> {
+        log::info!("开始回复: {}", prompt.text);
+        let reply = match chat_completion(&self.client, &self.openai, prompt.messages.clone()).await
+        {
+            Ok(reply) => reply,
+            Err(e) => {
+                log::error!("生成回复失败: {}", e);
+                return None;
+            }
+        };
+        log::info!("AI回复: {}", reply);
+
+        match synthesize(&self.client, &self.tts, reply).await {
+            Ok(audio) => Some(audio),
+            Err(e) => {
+                log::error!("语音合成失败: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn deliver(&self, sequence: u64, audio: Vec<u8>) {
+        if let Some(output_dir) = &self.output_dir {
+            let path = output_dir.join(format!("{:06}.{}", sequence, self.tts.response_format));
+            match tokio::fs::write(&path, &audio).await {
+                Ok(()) => log::info!("音频已写入: {}", path.display()),
+                Err(e) => log::error!("写入音频 {} 失败: {}", path.display(), e),
+            }
+        }
+
+        if let Some(post_url) = &self.post_url {
+            let result = self
+                .client
+                .post(post_url)
+                .header(
+                    "Content-Type",
+                    format!("audio/{}", self.tts.response_format),
+                )
+                .body(audio)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => log::info!("音频已推送到: {}", post_url),
+                Err(e) => log::error!("推送音频失败: {}", e),
+            }
+        }
+    }
+}
+
+fn load_config(path: Option<&Path>) -> Result<AppConfig, String> {
+    match path {
+        Some(path) => config::load_app_config_from(path),
+        None => config::load_app_config(),
+    }
+}
+
+async fn run(args: Args) -> Result<(), String> {
+    let app_config = load_config(args.config.as_deref())?;
+    let openai = app_config
+        .openai
+        .clone()
+        .ok_or("配置文件中未找到OpenAI配置")?;
+    let tts = app_config
+        .indextts
+        .clone()
+        .ok_or("配置文件中未找到IndexTTS配置")?;
+    if let Some(output_dir) = &args.output_dir {
+        std::fs::create_dir_all(output_dir)
+            .map_err(|e| format!("创建输出目录 {} 失败: {}", output_dir.display(), e))?;
+    }
+
+    let prompts = PromptBuilder::new(app_config.persona.clone().unwrap_or_default());
+    let superchats = Arc::new(SuperChatRegistry::default());
+    let (prompt_tx, prompt_rx) = mpsc::channel(QUEUE_CAPACITY);
+    let responder = tokio::spawn(
+        Responder {
+            client: reqwest::Client::new(),
+            openai,
+            tts,
+            output_dir: args.output_dir,
+            post_url: args.post_url,
+            superchats: superchats.clone(),
+        }
+        .run(prompt_rx),
+    );
+
+    let closing = match args.replay {
+        Some(path) => {
+            let source = ReplaySource::new(ReplayConfig { path, speed: 1.0 });
+            listen(source, &prompts, &superchats, &prompt_tx).await?
+        }
+        None => {
+            let source = BilibiliClient::new(app_config.bilibili_config());
+            listen(source, &prompts, &superchats, &prompt_tx).await?
+        }
+    };
+
+    // 等待已排队的回复处理完，中断时直接退出
+    drop(prompt_tx);
+    if closing {
+        responder.abort();
+    } else {
+        let _ = responder.await;
+    }
+    Ok(())
+}
+
+/// 连接事件源并把弹幕和醒目留言放进回复队列，直到事件源结束，返回是否因中断而结束
+async fn listen<S: LiveEventSource>(
+    mut source: S,
+    prompts: &PromptBuilder,
+    superchats: &SuperChatRegistry,
+    prompt_tx: &mpsc::Sender<Prompt>,
+) -> Result<bool, String> {
+    source
+        .connect()
+        .await
+        .map_err(|e| format!("连接失败: {}", e))?;
+
+    let viewers = ViewerRegistry::in_memory();
+    let mut live = PromptContext::default();
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut closing = false;
+    loop {
+        let event = tokio::select! {
+            _ = &mut ctrl_c, if !closing => {
+                log::info!("收到中断信号，正在断开连接");
+                closing = true;
+                if let Err(e) = source.close().await {
+                    log::warn!("断开连接失败: {}", e);
+                }
+                continue;
+            }
+            event = source.next_event() => event,
+        };
+
+        match event {
+            Some(LiveEvent::Message(message)) => {
+                viewers.record(std::slice::from_ref(&message));
+                match &message {
+                    BilibiliMessage::LiveStart { data } => {
+                        log::info!("开播: {}（{}）", data.title, data.area_name);
+                        live.stream_title = Some(data.title.clone());
+                        live.area_name = Some(data.area_name.clone());
+                    }
+                    BilibiliMessage::SuperChat { data } => superchats.insert(SESSION, data.clone()),
+                    BilibiliMessage::SuperChatDel { data } => {
+                        superchats.retract(SESSION, &data.message_ids)
+                    }
+                    _ => {}
+                }
+                let Some(prompt) = Prompt::from_message(&message, prompts, &live, &viewers) else {
+                    continue;
+                };
+                if prompt.super_chat_id.is_some() {
+                    // 醒目留言不丢弃，队列满时等待
+                    let _ = prompt_tx.send(prompt).await;
+                } else if let Err(mpsc::error::TrySendError::Full(prompt)) =
+                    prompt_tx.try_send(prompt)
+                {
+                    log::warn!("回复队列已满，丢弃弹幕: {}", prompt.text);
+                }
+            }
+            Some(LiveEvent::Connection(event)) => log::info!("连接状态: {:?}", event),
+            None => break,
+        }
+    }
+    Ok(closing)
+}
+
+#[tokio::main]
+async fn main() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            println!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(args).await {
+        log::error!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,75 @@
+//! 连接生命周期事件
+//!
+//! 描述Bilibili客户端连接状态的变化，通过独立的Tauri事件发送到前端。
+//! 同时连接多个直播间时，发送到前端的事件都带有会话名称。
+
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 连接生命周期事件，`timestamp` 为毫秒级时间戳
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ConnectionEvent {
+    /// 正在获取游戏会话并建立WebSocket连接
+    Connecting { timestamp: i64 },
+    /// WebSocket认证成功（op 8 返回 code 0）
+    Authenticated { timestamp: i64 },
+    /// WebSocket认证失败
+    AuthFailed { reason: String, timestamp: i64 },
+    /// WebSocket心跳未回复或应用心跳失败，连接降级但仍在使用
+    HeartbeatLost { reason: String, timestamp: i64 },
+    /// 心跳恢复正常
+    HeartbeatRecovered { timestamp: i64 },
+    /// 连接断开，等待后进行第 `attempt` 次重连
+    Reconnecting {
+        attempt: u32,
+        delay_ms: u64,
+        reason: String,
+        timestamp: i64,
+    },
+    /// 连接已关闭，不会再自动重连
+    Closed { reason: String, timestamp: i64 },
+    /// 开放平台结束了本次互动玩法
+    InteractionEnd { game_id: String, timestamp: i64 },
+}
+
+impl ConnectionEvent {
+    /// 当前的毫秒级时间戳
+    pub fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default()
+    }
+
+    pub fn closed(reason: impl Into<String>) -> Self {
+        ConnectionEvent::Closed {
+            reason: reason.into(),
+            timestamp: Self::now(),
+        }
+    }
+
+    /// 连接当前是否可用
+    pub fn is_connected(&self) -> bool {
+        matches!(
+            self,
+            ConnectionEvent::Authenticated { .. }
+                | ConnectionEvent::HeartbeatLost { .. }
+                | ConnectionEvent::HeartbeatRecovered { .. }
+        )
+    }
+}
+
+/// 带会话名称的事件，原事件的字段平铺在同一层，前端只需额外读取 `session`
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionEvent<'a, T> {
+    pub session: &'a str,
+    #[serde(flatten)]
+    pub event: &'a T,
+}
+
+impl<'a, T> SessionEvent<'a, T> {
+    pub fn new(session: &'a str, event: &'a T) -> Self {
+        Self { session, event }
+    }
+}
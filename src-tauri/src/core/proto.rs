@@ -1,10 +1,84 @@
 use flate2::read::ZlibDecoder;
-use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
+use std::collections::VecDeque;
+use std::fmt;
 use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 const HEADER_LENGTH: usize = 16;
 const MAX_BODY_SIZE: usize = 1024 * 1024; // 增加到1MB以支持更大的消息
+/// 压缩包解压后的大小上限，防止压缩炸弹耗尽内存
+const MAX_DECOMPRESSED_SIZE: usize = 8 * MAX_BODY_SIZE;
+
+/// 协议版本：未压缩的JSON
+pub const VER_NORMAL: u16 = 0;
+/// 协议版本：心跳回复等整数内容
+pub const VER_HEARTBEAT: u16 = 1;
+/// 协议版本：zlib压缩的嵌套包
+pub const VER_ZLIB: u16 = 2;
+/// 协议版本：brotli压缩的嵌套包
+pub const VER_BROTLI: u16 = 3;
+
+/// 协议解析错误
+#[derive(Debug)]
+pub enum ProtoError {
+    /// 数据不足一个包头
+    IncompleteHeader { len: usize },
+    /// 包头长度字段不合法
+    InvalidHeaderLength(u16),
+    /// 包长度字段不合法
+    InvalidPacketLength(u32),
+    /// 数据不足一个完整的包
+    IncompletePacket { expected: usize, actual: usize },
+    /// 不支持的协议版本
+    UnsupportedVersion(u16),
+    /// 解压缩失败
+    Decompress(std::io::Error),
+    /// 解压后的数据超过上限
+    DecompressedTooLarge { limit: usize },
+    /// 压缩包解压后又包含压缩包，开放平台只嵌套一层
+    NestedCompression { ver: u16 },
+    /// 包体不是合法的UTF-8
+    InvalidUtf8(std::string::FromUtf8Error),
+}
+
+impl fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtoError::IncompleteHeader { len } => write!(f, "包头不够: {}", len),
+            ProtoError::InvalidHeaderLength(len) => write!(f, "包头长度不对: {}", len),
+            ProtoError::InvalidPacketLength(len) => write!(f, "包体长度不对: {}", len),
+            ProtoError::IncompletePacket { expected, actual } => {
+                write!(
+                    f,
+                    "数据不完整: 需要 {} 字节，实际 {} 字节",
+                    expected, actual
+                )
+            }
+            ProtoError::UnsupportedVersion(ver) => write!(f, "不支持的版本: {}", ver),
+            ProtoError::Decompress(e) => write!(f, "解压缩失败: {}", e),
+            ProtoError::DecompressedTooLarge { limit } => {
+                write!(f, "解压后的数据超过上限: {} 字节", limit)
+            }
+            ProtoError::NestedCompression { ver } => {
+                write!(f, "压缩包中嵌套了压缩包: 版本 {}", ver)
+            }
+            ProtoError::InvalidUtf8(e) => write!(f, "解码UTF-8失败: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProtoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProtoError::Decompress(e) => Some(e),
+            ProtoError::InvalidUtf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Proto {
@@ -42,72 +116,100 @@ impl Proto {
         buf
     }
 
-    pub fn unpack(&mut self, buf: &[u8]) -> Result<(), String> {
-        if buf.len() < HEADER_LENGTH {
-            return Err("包头不够".to_string());
+    /// 从 `buf` 开头解析一个包，返回该包占用的字节数
+    pub fn unpack(&mut self, buf: &[u8]) -> Result<usize, ProtoError> {
+        let packet_len = Self::peek_packet_len(buf)?;
+        if buf.len() < packet_len {
+            return Err(ProtoError::IncompletePacket {
+                expected: packet_len,
+                actual: buf.len(),
+            });
         }
 
-        self.packet_len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        self.packet_len = packet_len as u32;
         self.header_len = u16::from_be_bytes([buf[4], buf[5]]);
         self.ver = u16::from_be_bytes([buf[6], buf[7]]);
         self.op = u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]);
         self.seq = u32::from_be_bytes([buf[12], buf[13], buf[14], buf[15]]);
+        self.body = buf[self.header_len as usize..packet_len].to_vec();
+
+        Ok(packet_len)
+    }
 
-        if self.packet_len as usize > MAX_BODY_SIZE {
-            return Err(format!("包体长度不对: {}", self.packet_len));
+    /// 校验 `buf` 开头的包头，返回整个包的长度
+    fn peek_packet_len(buf: &[u8]) -> Result<usize, ProtoError> {
+        if buf.len() < HEADER_LENGTH {
+            return Err(ProtoError::IncompleteHeader { len: buf.len() });
         }
 
-        if self.header_len as usize != HEADER_LENGTH {
-            return Err("包头长度不对".to_string());
+        let packet_len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        let header_len = u16::from_be_bytes([buf[4], buf[5]]);
+
+        if header_len as usize != HEADER_LENGTH {
+            return Err(ProtoError::InvalidHeaderLength(header_len));
         }
 
-        let body_len = self.packet_len as usize - HEADER_LENGTH;
-        if body_len > 0 {
-            if buf.len() < self.packet_len as usize {
-                return Err("数据不完整".to_string());
-            }
-            self.body = buf[HEADER_LENGTH..self.packet_len as usize].to_vec();
+        if (packet_len as usize) < HEADER_LENGTH || packet_len as usize > MAX_BODY_SIZE {
+            return Err(ProtoError::InvalidPacketLength(packet_len));
         }
 
-        Ok(())
+        Ok(packet_len as usize)
+    }
+
+    /// 是否为需要解压后再展开的压缩包
+    pub fn is_compressed(&self) -> bool {
+        matches!(self.ver, VER_ZLIB | VER_BROTLI)
     }
 
-    pub fn get_body_string(&self) -> Result<String, String> {
+    /// 解压压缩包的包体，得到若干拼接在一起的子包
+    ///
+    /// 解压后超过 `MAX_DECOMPRESSED_SIZE` 时返回 `DecompressedTooLarge`。
+    pub fn decompress_body(&self) -> Result<Vec<u8>, ProtoError> {
+        // 多读一个字节，用来区分刚好达到上限和超出上限
+        let limit = MAX_DECOMPRESSED_SIZE as u64 + 1;
+        let mut decompressed = Vec::new();
         match self.ver {
-            0 => {
-                // 未压缩的数据
-                String::from_utf8(self.body.clone()).map_err(|e| format!("解码UTF-8失败: {}", e))
+            VER_ZLIB => {
+                ZlibDecoder::new(&self.body[..])
+                    .take(limit)
+                    .read_to_end(&mut decompressed)
+                    .map_err(ProtoError::Decompress)?;
             }
-            2 => {
-                // zlib压缩的数据
-                let mut decoder = ZlibDecoder::new(&self.body[..]);
-                let mut decompressed = Vec::new();
-                decoder
+            VER_BROTLI => {
+                brotli::Decompressor::new(&self.body[..], 4096)
+                    .take(limit)
                     .read_to_end(&mut decompressed)
-                    .map_err(|e| format!("解压缩失败: {}", e))?;
-
-                // 解压缩后的数据可能包含多个消息包，需要递归处理
-                if decompressed.len() >= HEADER_LENGTH {
-                    // 检查是否是嵌套的协议包
-                    let nested_packet_len = u32::from_be_bytes([
-                        decompressed[0],
-                        decompressed[1],
-                        decompressed[2],
-                        decompressed[3],
-                    ]);
+                    .map_err(ProtoError::Decompress)?;
+            }
+            ver => return Err(ProtoError::UnsupportedVersion(ver)),
+        }
+        if decompressed.len() > MAX_DECOMPRESSED_SIZE {
+            return Err(ProtoError::DecompressedTooLarge {
+                limit: MAX_DECOMPRESSED_SIZE,
+            });
+        }
+        Ok(decompressed)
+    }
 
-                    if nested_packet_len as usize == decompressed.len() {
-                        // 这是一个嵌套的协议包，递归解析
-                        let mut nested_proto = Proto::new();
-                        nested_proto.unpack(&decompressed)?;
-                        return nested_proto.get_body_string();
-                    }
+    /// 读取未压缩包的包体文本；压缩包需要先经过 [`ProtoDecoder`] 展开
+    pub fn get_body_string(&self) -> Result<String, ProtoError> {
+        match self.ver {
+            VER_NORMAL => String::from_utf8(self.body.clone()).map_err(ProtoError::InvalidUtf8),
+            VER_HEARTBEAT => {
+                // 心跳回复的包体是一个大端整数（人气值）
+                if self.body.len() == 4 {
+                    let value = u32::from_be_bytes([
+                        self.body[0],
+                        self.body[1],
+                        self.body[2],
+                        self.body[3],
+                    ]);
+                    Ok(value.to_string())
+                } else {
+                    String::from_utf8(self.body.clone()).map_err(ProtoError::InvalidUtf8)
                 }
-
-                // 直接解码为字符串
-                String::from_utf8(decompressed).map_err(|e| format!("解码UTF-8失败: {}", e))
             }
-            _ => Err(format!("不支持的版本: {}", self.ver)),
+            ver => Err(ProtoError::UnsupportedVersion(ver)),
         }
     }
 }
@@ -118,6 +220,313 @@ impl Default for Proto {
     }
 }
 
+/// 流式解包器
+///
+/// 可以分多次喂入数据，逐个取出其中的完整子包。
+/// zlib/brotli 压缩包会被自动解压并展开，取出的包都是未压缩的。
+/// 开放平台的压缩包只嵌套一层，解压后再遇到压缩包时返回 `NestedCompression`，
+/// 这样一帧解压出的数据总量不会超过 `MAX_DECOMPRESSED_SIZE` 乘以帧中压缩包的个数。
+#[derive(Debug, Default)]
+pub struct ProtoDecoder {
+    buf: Vec<u8>,
+    pending: VecDeque<Proto>,
+    // 正在展开压缩包的包体，不再接受压缩包
+    nested: bool,
+}
+
+impl ProtoDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 一次性解出 `data` 中的所有子包，末尾有残缺数据时报错
+    pub fn decode_all(data: &[u8]) -> Result<Vec<Proto>, ProtoError> {
+        Self::decode_with(Self::new(), data)
+    }
+
+    fn decode_with(mut decoder: Self, data: &[u8]) -> Result<Vec<Proto>, ProtoError> {
+        decoder.feed(data);
+
+        let mut packets = Vec::new();
+        while let Some(packet) = decoder.next_packet()? {
+            packets.push(packet);
+        }
+
+        if !decoder.buf.is_empty() {
+            return Err(match Proto::peek_packet_len(&decoder.buf) {
+                Ok(expected) => ProtoError::IncompletePacket {
+                    expected,
+                    actual: decoder.buf.len(),
+                },
+                Err(e) => e,
+            });
+        }
+
+        Ok(packets)
+    }
+
+    /// 追加收到的数据
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// 缓冲区中尚未组成完整包的字节数
+    pub fn buffered_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// 取出下一个完整的子包，数据不足时返回 `Ok(None)`
+    ///
+    /// 包头不合法时缓冲区会被清空，以免后续数据一直错位。
+    pub fn next_packet(&mut self) -> Result<Option<Proto>, ProtoError> {
+        loop {
+            if let Some(packet) = self.pending.pop_front() {
+                return Ok(Some(packet));
+            }
+
+            let packet_len = match Proto::peek_packet_len(&self.buf) {
+                Ok(len) if self.buf.len() >= len => len,
+                Ok(_) | Err(ProtoError::IncompleteHeader { .. }) => return Ok(None),
+                Err(e) => {
+                    self.buf.clear();
+                    return Err(e);
+                }
+            };
+
+            let mut packet = Proto::new();
+            packet.unpack(&self.buf[..packet_len])?;
+            self.buf.drain(..packet_len);
+
+            match packet.ver {
+                VER_NORMAL | VER_HEARTBEAT => return Ok(Some(packet)),
+                VER_ZLIB | VER_BROTLI if self.nested => {
+                    return Err(ProtoError::NestedCompression { ver: packet.ver });
+                }
+                VER_ZLIB | VER_BROTLI => {
+                    let body = packet.decompress_body()?;
+                    let nested = Self {
+                        nested: true,
+                        ..Self::default()
+                    };
+                    self.pending.extend(Self::decode_with(nested, &body)?);
+                }
+                ver => return Err(ProtoError::UnsupportedVersion(ver)),
+            }
+        }
+    }
+}
+
+impl Iterator for ProtoDecoder {
+    type Item = Result<Proto, ProtoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().transpose()
+    }
+}
+
+/// 载荷解析诊断计数
+///
+/// 统计宽松反序列化中被修正或回退为默认值的字段，以及解析失败的消息，
+/// 用于发现平台字段调整。
+#[derive(Debug, Default)]
+pub struct ParseDiagnostics {
+    messages_parsed: AtomicU64,
+    messages_unknown: AtomicU64,
+    messages_failed: AtomicU64,
+    fields_coerced: AtomicU64,
+    fields_defaulted: AtomicU64,
+}
+
+/// [`ParseDiagnostics`] 的快照，可直接发送到前端
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseDiagnosticsSnapshot {
+    /// 按已知结构成功解析的消息数
+    pub messages_parsed: u64,
+    /// cmd 未收录、以 `Unknown` 转发的消息数
+    pub messages_unknown: u64,
+    /// cmd 已知但结构不匹配、以 `Unknown` 转发的消息数
+    pub messages_failed: u64,
+    /// 类型不符但经过转换后成功解析的字段数
+    pub fields_coerced: u64,
+    /// 缺失、为 null 或无法解析、回退为默认值的字段数，`Option` 字段缺失不计入
+    pub fields_defaulted: u64,
+}
+
+static PARSE_DIAGNOSTICS: ParseDiagnostics = ParseDiagnostics {
+    messages_parsed: AtomicU64::new(0),
+    messages_unknown: AtomicU64::new(0),
+    messages_failed: AtomicU64::new(0),
+    fields_coerced: AtomicU64::new(0),
+    fields_defaulted: AtomicU64::new(0),
+};
+
+impl ParseDiagnostics {
+    /// 全局诊断计数
+    pub fn global() -> &'static ParseDiagnostics {
+        &PARSE_DIAGNOSTICS
+    }
+
+    /// 记录一条解析完成的业务消息
+    pub fn record_message(&self, message: &BilibiliMessage) {
+        let counter = match message {
+            BilibiliMessage::Unknown { cmd, .. } if KNOWN_CMDS.contains(&cmd.as_str()) => {
+                &self.messages_failed
+            }
+            BilibiliMessage::Unknown { .. } => &self.messages_unknown,
+            _ => &self.messages_parsed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ParseDiagnosticsSnapshot {
+        ParseDiagnosticsSnapshot {
+            messages_parsed: self.messages_parsed.load(Ordering::Relaxed),
+            messages_unknown: self.messages_unknown.load(Ordering::Relaxed),
+            messages_failed: self.messages_failed.load(Ordering::Relaxed),
+            fields_coerced: self.fields_coerced.load(Ordering::Relaxed),
+            fields_defaulted: self.fields_defaulted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 宽松地反序列化单个字段
+///
+/// 类型不符时尝试在字符串、数字和布尔值之间转换，仍然失败则回退为默认值，
+/// 避免一个字段的变化导致整条消息丢失。
+fn lenient<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned + Default,
+{
+    let value = Value::deserialize(deserializer)?;
+    let err = match T::deserialize(&value) {
+        Ok(parsed) => return Ok(parsed),
+        Err(e) => e,
+    };
+
+    for candidate in coerce_candidates(&value) {
+        if let Ok(parsed) = T::deserialize(&candidate) {
+            PARSE_DIAGNOSTICS
+                .fields_coerced
+                .fetch_add(1, Ordering::Relaxed);
+            return Ok(parsed);
+        }
+    }
+
+    if !value.is_null() {
+        log::warn!("字段解析失败，使用默认值: {}, 原始值: {}", err, value);
+    }
+    PARSE_DIAGNOSTICS
+        .fields_defaulted
+        .fetch_add(1, Ordering::Relaxed);
+    Ok(T::default())
+}
+
+/// 载荷字段缺失时的取值
+///
+/// `Option` 字段本来就可能缺失，取 `None` 且不计入诊断；其余字段回退为默认值，
+/// 计入 `fields_defaulted`。
+trait MissingField {
+    fn missing() -> Self;
+}
+
+/// 缺失时回退为默认值并计数
+fn defaulted<T: Default>() -> T {
+    PARSE_DIAGNOSTICS
+        .fields_defaulted
+        .fetch_add(1, Ordering::Relaxed);
+    T::default()
+}
+
+impl<T> MissingField for Option<T> {
+    fn missing() -> Self {
+        None
+    }
+}
+
+impl<T> MissingField for Vec<T> {
+    fn missing() -> Self {
+        defaulted()
+    }
+}
+
+macro_rules! missing_field_defaulted {
+    ($($ty:ty),*) => {$(
+        impl MissingField for $ty {
+            fn missing() -> Self {
+                defaulted()
+            }
+        }
+    )*};
+}
+
+missing_field_defaulted!(i64, bool, String);
+
+/// 供 `#[serde(default = ...)]` 使用
+fn missing_field<T: MissingField>() -> T {
+    T::missing()
+}
+
+/// 类型不符时可以尝试的替代值
+fn coerce_candidates(value: &Value) -> Vec<Value> {
+    match value {
+        Value::String(s) => {
+            let s = s.trim();
+            let mut candidates = Vec::new();
+            if let Ok(n) = s.parse::<i64>() {
+                candidates.push(Value::from(n));
+            } else if let Ok(n) = s.parse::<f64>() {
+                candidates.push(Value::from(n));
+            }
+            if let Ok(b) = s.parse::<bool>() {
+                candidates.push(Value::Bool(b));
+            }
+            candidates
+        }
+        Value::Number(n) => {
+            let mut candidates = vec![Value::String(n.to_string())];
+            match n.as_i64() {
+                Some(0) => candidates.push(Value::Bool(false)),
+                Some(1) => candidates.push(Value::Bool(true)),
+                _ => {}
+            }
+            // 浮点数形式的整数，如 1.0
+            if let Some(f) = n.as_f64().filter(|f| f.fract() == 0.0) {
+                candidates.push(Value::from(f as i64));
+            }
+            candidates
+        }
+        Value::Bool(b) => vec![Value::from(*b as i64)],
+        _ => Vec::new(),
+    }
+}
+
+/// 定义载荷结构体：缺失的字段取默认值并计入诊断，每个字段都宽松反序列化
+macro_rules! lenient_payload {
+    ($(
+        $(#[$meta:meta])*
+        pub struct $name:ident {
+            $($(#[$field_meta:meta])* pub $field:ident: $ty:ty,)*
+        }
+    )*) => {$(
+        $(#[$meta])*
+        #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+        pub struct $name {
+            $(
+                $(#[$field_meta])*
+                #[serde(default = "missing_field", deserialize_with = "lenient")]
+                pub $field: $ty,
+            )*
+        }
+
+        impl MissingField for $name {
+            fn missing() -> Self {
+                defaulted()
+            }
+        }
+    )*};
+}
+
 // 各种消息类型的结构体
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DanmakuMessage {
@@ -125,27 +534,28 @@ pub struct DanmakuMessage {
     pub data: DanmakuData,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DanmakuData {
-    pub room_id: i64,
-    pub uid: i64,
-    pub open_id: String,
-    pub union_id: Option<String>,
-    pub uname: String,
-    pub uface: String,
-    pub timestamp: i64,
-    pub msg: String,
-    pub msg_id: String,
-    pub guard_level: i64,
-    pub fans_medal_wearing_status: bool,
-    pub fans_medal_name: String,
-    pub fans_medal_level: i64,
-    pub emoji_img_url: Option<String>,
-    pub dm_type: i64,
-    pub glory_level: Option<i32>,
-    pub reply_open_id: Option<String>,
-    pub reply_uname: Option<String>,
-    pub is_admin: Option<i32>,
+lenient_payload! {
+    pub struct DanmakuData {
+        pub room_id: i64,
+        pub uid: i64,
+        pub open_id: String,
+        pub union_id: Option<String>,
+        pub uname: String,
+        pub uface: String,
+        pub timestamp: i64,
+        pub msg: String,
+        pub msg_id: String,
+        pub guard_level: i64,
+        pub fans_medal_wearing_status: bool,
+        pub fans_medal_name: String,
+        pub fans_medal_level: i64,
+        pub emoji_img_url: Option<String>,
+        pub dm_type: i64,
+        pub glory_level: Option<i32>,
+        pub reply_open_id: Option<String>,
+        pub reply_uname: Option<String>,
+        pub is_admin: Option<i32>,
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -154,54 +564,58 @@ pub struct GiftMessage {
     pub data: GiftData,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GiftData {
-    pub room_id: i64,
-    pub uid: i64,
-    pub open_id: String,
-    pub union_id: Option<String>,
-    pub uname: String,
-    pub uface: String,
-    pub gift_id: i64,
-    pub gift_name: String,
-    pub gift_num: i64,
-    pub price: i64,
-    pub r_price: i64,
-    pub paid: bool,
-    pub fans_medal_level: i64,
-    pub fans_medal_name: String,
-    pub fans_medal_wearing_status: bool,
-    pub guard_level: i64,
-    pub timestamp: i64,
-    pub anchor_info: AnchorInfo,
-    pub msg_id: String,
-    pub gift_icon: Option<String>,
-    pub combo_gift: Option<bool>,
-    pub combo_info: Option<ComboInfo>,
-    pub blind_gift: Option<BlindGift>,
+lenient_payload! {
+    pub struct GiftData {
+        pub room_id: i64,
+        pub uid: i64,
+        pub open_id: String,
+        pub union_id: Option<String>,
+        pub uname: String,
+        pub uface: String,
+        pub gift_id: i64,
+        pub gift_name: String,
+        pub gift_num: i64,
+        pub price: i64,
+        pub r_price: i64,
+        pub paid: bool,
+        pub fans_medal_level: i64,
+        pub fans_medal_name: String,
+        pub fans_medal_wearing_status: bool,
+        pub guard_level: i64,
+        pub timestamp: i64,
+        pub anchor_info: AnchorInfo,
+        pub msg_id: String,
+        pub gift_icon: Option<String>,
+        pub combo_gift: Option<bool>,
+        pub combo_info: Option<ComboInfo>,
+        pub blind_gift: Option<BlindGift>,
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AnchorInfo {
-    pub uid: i64,
-    pub open_id: String,
-    pub union_id: Option<String>,
-    pub uname: String,
-    pub uface: String,
+lenient_payload! {
+    pub struct AnchorInfo {
+        pub uid: i64,
+        pub open_id: String,
+        pub union_id: Option<String>,
+        pub uname: String,
+        pub uface: String,
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ComboInfo {
-    pub combo_base_num: i64,
-    pub combo_count: i64,
-    pub combo_id: String,
-    pub combo_timeout: i64,
+lenient_payload! {
+    pub struct ComboInfo {
+        pub combo_base_num: i64,
+        pub combo_count: i64,
+        pub combo_id: String,
+        pub combo_timeout: i64,
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BlindGift {
-    pub blind_gift_id: i64,
-    pub status: bool,
+lenient_payload! {
+    pub struct BlindGift {
+        pub blind_gift_id: i64,
+        pub status: bool,
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -210,25 +624,26 @@ pub struct SuperChatMessage {
     pub data: SuperChatData,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SuperChatData {
-    pub room_id: i64,
-    pub uid: i64,
-    pub open_id: String,
-    pub union_id: Option<String>,
-    pub uname: String,
-    pub uface: String,
-    pub message_id: i64,
-    pub message: String,
-    pub rmb: i64,
-    pub timestamp: i64,
-    pub start_time: i64,
-    pub end_time: i64,
-    pub guard_level: i64,
-    pub fans_medal_level: i64,
-    pub fans_medal_name: String,
-    pub fans_medal_wearing_status: bool,
-    pub msg_id: String,
+lenient_payload! {
+    pub struct SuperChatData {
+        pub room_id: i64,
+        pub uid: i64,
+        pub open_id: String,
+        pub union_id: Option<String>,
+        pub uname: String,
+        pub uface: String,
+        pub message_id: i64,
+        pub message: String,
+        pub rmb: i64,
+        pub timestamp: i64,
+        pub start_time: i64,
+        pub end_time: i64,
+        pub guard_level: i64,
+        pub fans_medal_level: i64,
+        pub fans_medal_name: String,
+        pub fans_medal_wearing_status: bool,
+        pub msg_id: String,
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -237,28 +652,30 @@ pub struct GuardMessage {
     pub data: GuardData,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GuardData {
-    pub user_info: UserInfo,
-    pub guard_level: i64,
-    pub guard_num: i64,
-    pub guard_unit: String,
-    pub price: i64,
-    pub fans_medal_level: i64,
-    pub fans_medal_name: String,
-    pub fans_medal_wearing_status: bool,
-    pub room_id: i64,
-    pub msg_id: String,
-    pub timestamp: i64,
+lenient_payload! {
+    pub struct GuardData {
+        pub user_info: UserInfo,
+        pub guard_level: i64,
+        pub guard_num: i64,
+        pub guard_unit: String,
+        pub price: i64,
+        pub fans_medal_level: i64,
+        pub fans_medal_name: String,
+        pub fans_medal_wearing_status: bool,
+        pub room_id: i64,
+        pub msg_id: String,
+        pub timestamp: i64,
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UserInfo {
-    pub uid: i64,
-    pub open_id: String,
-    pub union_id: Option<String>,
-    pub uname: String,
-    pub uface: String,
+lenient_payload! {
+    pub struct UserInfo {
+        pub uid: i64,
+        pub open_id: String,
+        pub union_id: Option<String>,
+        pub uname: String,
+        pub uface: String,
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -267,29 +684,23 @@ pub struct LikeMessage {
     pub data: LikeData,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LikeData {
-    pub room_id: i64,
-    pub uid: i64,
-    pub open_id: String,
-    pub union_id: Option<String>,
-    pub uname: String,
-    pub uface: String,
-    pub timestamp: i64,
-    pub like_text: String,
-    pub like_count: i64,
-    pub fans_medal_level: i64,
-    pub fans_medal_name: String,
-    pub fans_medal_wearing_status: bool,
-    pub guard_level: Option<i64>,
-    pub msg_id: String,
-}
-
-// 通用消息结构，用于处理未知或新的消息类型
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UnknownMessage {
-    pub cmd: String,
-    pub data: Value,
+lenient_payload! {
+    pub struct LikeData {
+        pub room_id: i64,
+        pub uid: i64,
+        pub open_id: String,
+        pub union_id: Option<String>,
+        pub uname: String,
+        pub uface: String,
+        pub timestamp: i64,
+        pub like_text: String,
+        pub like_count: i64,
+        pub fans_medal_level: i64,
+        pub fans_medal_name: String,
+        pub fans_medal_wearing_status: bool,
+        pub guard_level: Option<i64>,
+        pub msg_id: String,
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -315,47 +726,92 @@ pub enum BilibiliMessage {
     LiveEnd { data: LiveEndData },
     #[serde(rename = "LIVE_OPEN_PLATFORM_INTERACTION_END")]
     InteractionEnd { data: InteractionEndData },
+    /// 兜底：未收录或无法按已知结构解析的消息，原样保留 cmd 和 data
+    #[serde(untagged)]
+    Unknown {
+        cmd: String,
+        #[serde(default)]
+        data: Value,
+    },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SuperChatDelData {
-    pub room_id: i64,
-    pub message_ids: Vec<i64>,
-    pub msg_id: String,
+/// 已收录的消息类型
+const KNOWN_CMDS: [&str; 10] = [
+    "LIVE_OPEN_PLATFORM_DM",
+    "LIVE_OPEN_PLATFORM_SEND_GIFT",
+    "LIVE_OPEN_PLATFORM_SUPER_CHAT",
+    "LIVE_OPEN_PLATFORM_GUARD",
+    "LIVE_OPEN_PLATFORM_LIKE",
+    "LIVE_OPEN_PLATFORM_SUPER_CHAT_DEL",
+    "LIVE_OPEN_PLATFORM_LIVE_ROOM_ENTER",
+    "LIVE_OPEN_PLATFORM_LIVE_START",
+    "LIVE_OPEN_PLATFORM_LIVE_END",
+    "LIVE_OPEN_PLATFORM_INTERACTION_END",
+];
+
+impl BilibiliMessage {
+    /// 消息的 cmd 字段
+    pub fn cmd(&self) -> &str {
+        match self {
+            BilibiliMessage::Danmaku { .. } => "LIVE_OPEN_PLATFORM_DM",
+            BilibiliMessage::Gift { .. } => "LIVE_OPEN_PLATFORM_SEND_GIFT",
+            BilibiliMessage::SuperChat { .. } => "LIVE_OPEN_PLATFORM_SUPER_CHAT",
+            BilibiliMessage::Guard { .. } => "LIVE_OPEN_PLATFORM_GUARD",
+            BilibiliMessage::Like { .. } => "LIVE_OPEN_PLATFORM_LIKE",
+            BilibiliMessage::SuperChatDel { .. } => "LIVE_OPEN_PLATFORM_SUPER_CHAT_DEL",
+            BilibiliMessage::LiveRoomEnter { .. } => "LIVE_OPEN_PLATFORM_LIVE_ROOM_ENTER",
+            BilibiliMessage::LiveStart { .. } => "LIVE_OPEN_PLATFORM_LIVE_START",
+            BilibiliMessage::LiveEnd { .. } => "LIVE_OPEN_PLATFORM_LIVE_END",
+            BilibiliMessage::InteractionEnd { .. } => "LIVE_OPEN_PLATFORM_INTERACTION_END",
+            BilibiliMessage::Unknown { cmd, .. } => cmd,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LiveRoomEnterData {
-    pub room_id: i64,
-    pub uface: String,
-    pub uname: String,
-    pub open_id: String,
-    pub union_id: Option<String>,
-    pub timestamp: i64,
+lenient_payload! {
+    pub struct SuperChatDelData {
+        pub room_id: i64,
+        pub message_ids: Vec<i64>,
+        pub msg_id: String,
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LiveStartData {
-    pub room_id: i64,
-    pub open_id: String,
-    pub union_id: Option<String>,
-    pub timestamp: i64,
-    pub area_name: String,
-    pub title: String,
+lenient_payload! {
+    pub struct LiveRoomEnterData {
+        pub room_id: i64,
+        pub uface: String,
+        pub uname: String,
+        pub open_id: String,
+        pub union_id: Option<String>,
+        pub timestamp: i64,
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LiveEndData {
-    pub room_id: i64,
-    pub open_id: String,
-    pub union_id: Option<String>,
-    pub timestamp: i64,
-    pub area_name: String,
-    pub title: String,
+lenient_payload! {
+    pub struct LiveStartData {
+        pub room_id: i64,
+        pub open_id: String,
+        pub union_id: Option<String>,
+        pub timestamp: i64,
+        pub area_name: String,
+        pub title: String,
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct InteractionEndData {
-    pub game_id: String,
-    pub timestamp: i64,
+lenient_payload! {
+    pub struct LiveEndData {
+        pub room_id: i64,
+        pub open_id: String,
+        pub union_id: Option<String>,
+        pub timestamp: i64,
+        pub area_name: String,
+        pub title: String,
+    }
+}
+
+lenient_payload! {
+    pub struct InteractionEndData {
+        pub game_id: String,
+        pub timestamp: i64,
+    }
 }
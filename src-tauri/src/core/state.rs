@@ -2,13 +2,45 @@
 //!
 //! 定义应用程序的全局状态类型
 
-use crate::services::bilibili::BilibiliClient;
+use crate::services::archive::EventArchive;
+use crate::services::bilibili::ClientHandle;
+use crate::services::chat_stream::ChatStreamRegistry;
+use crate::services::memory::ConversationStore;
+use crate::services::mock::MockServer;
 use crate::services::proxy::ProxyServer;
+use crate::services::stats::LiveStats;
+use crate::services::superchat::SuperChatRegistry;
+use crate::services::viewer::ViewerRegistry;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-/// Bilibili客户端状态
-pub type ClientState = Arc<Mutex<Option<BilibiliClient>>>;
+/// 未指定会话名称时使用的默认会话
+pub const DEFAULT_SESSION: &str = "default";
+
+/// Bilibili客户端状态，按会话名称管理多个直播间连接
+pub type ClientState = Arc<Mutex<HashMap<String, ClientHandle>>>;
 
 /// 代理服务器状态
 pub type ProxyState = Arc<Mutex<Option<ProxyServer>>>;
+
+/// 模拟开放平台状态
+pub type MockServerState = Arc<Mutex<Option<MockServer>>>;
+
+/// 醒目留言登记表，所有会话共用
+pub type SuperChatState = Arc<SuperChatRegistry>;
+
+/// 直播统计，所有会话共用
+pub type StatsState = Arc<LiveStats>;
+
+/// 消息归档，应用启动时打开，打开失败时为 `None`
+pub type ArchiveState = Arc<Mutex<Option<EventArchive>>>;
+
+/// 观众档案，应用启动时载入，打开数据库失败时只保存在内存中
+pub type ViewerState = Arc<ViewerRegistry>;
+
+/// 进行中的流式对话，按 stream_id 保存取消信号
+pub type ChatStreamState = Arc<ChatStreamRegistry>;
+
+/// 对话记忆，按会话（和观众）保存近期对话与摘要
+pub type MemoryState = Arc<ConversationStore>;
@@ -1,9 +1,19 @@
+#[cfg(feature = "desktop")]
 mod api;
-mod core;
-mod services;
+// 核心类型和服务层不依赖 Tauri，关闭默认的 desktop 特性后可以作为库单独使用
+pub mod core;
+pub mod services;
+pub mod utils;
 
-use core::{ClientState, ProxyState};
+#[cfg(feature = "desktop")]
+use core::{
+    ArchiveState, ChatStreamState, ClientState, MemoryState, MockServerState, ProxyState,
+    StatsState, SuperChatState, ViewerState,
+};
+#[cfg(feature = "desktop")]
+use tauri::Manager;
 
+#[cfg(feature = "desktop")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -16,23 +26,71 @@ pub fn run() {
         )
         .manage(ClientState::default())
         .manage(ProxyState::default())
+        .manage(MockServerState::default())
+        .manage(SuperChatState::default())
+        .manage(StatsState::default())
+        .manage(ChatStreamState::default())
+        .manage(MemoryState::default())
         .invoke_handler(tauri::generate_handler![
             api::connect_bilibili,
             api::disconnect_bilibili,
             api::get_connection_status,
+            api::get_heartbeat_stats,
+            api::list_bilibili_sessions,
+            api::get_parse_diagnostics,
+            api::list_super_chats,
+            api::get_super_chat_status,
+            api::get_live_stats,
+            api::list_live_stats,
+            api::query_archive,
+            api::get_viewer_profile,
+            api::list_viewers,
             api::load_config_from_file,
             api::save_config_to_file,
             api::start_proxy_server,
             api::stop_proxy_server,
             api::get_proxy_status,
-            services::text_to_speech,
-            services::chat_with_openai,
+            api::start_mock_server,
+            api::stop_mock_server,
+            api::get_mock_server_status,
+            api::text_to_speech,
+            api::chat_with_openai,
+            api::chat_with_openai_stream,
+            api::cancel_chat_stream,
+            api::get_conversation,
+            api::list_conversations,
+            api::reset_conversation,
             api::chat_and_speak
         ])
-        .setup(|_app| {
+        .setup(|app| {
+            // 打开消息归档数据库，失败时只记录日志，不影响其他功能
+            let archive = app
+                .path()
+                .app_data_dir()
+                .map_err(|e| e.to_string())
+                .and_then(|dir| services::archive::EventArchive::open(dir.join("archive.sqlite3")))
+                .inspect_err(|e| log::error!("消息归档不可用: {}", e))
+                .ok();
+            app.manage(ArchiveState::new(tokio::sync::Mutex::new(archive)));
+
+            // 载入观众档案，数据库不可用时退回到只在内存中记录
+            let viewers = app
+                .path()
+                .app_data_dir()
+                .map_err(|e| e.to_string())
+                .and_then(|dir| services::viewer::ViewerRegistry::open(dir.join("viewers.sqlite3")))
+                .unwrap_or_else(|e| {
+                    log::error!("观众档案数据库不可用，仅在内存中记录: {}", e);
+                    services::viewer::ViewerRegistry::in_memory()
+                });
+            app.manage(ViewerState::new(viewers));
+
+            api::lifecycle::install_panic_hook(app.handle());
+
             log::info!("AIVtuber 应用启动完成");
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(api::lifecycle::handle_run_event);
 }
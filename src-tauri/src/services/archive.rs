@@ -0,0 +1,462 @@
+//! 直播消息归档
+//!
+//! 把所有直播消息写入本地 SQLite 数据库：`events` 表记录公共字段和原始 JSON，
+//! 弹幕、礼物、醒目留言和大航海另有明细表，弹幕内容建立 FTS5 全文索引。
+//! 写入由独立线程批量完成，查询时使用单独的只读连接。
+
+use crate::core::BilibiliMessage;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OpenFlags, params, params_from_iter};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+
+/// 单次查询最多返回的条数
+const MAX_QUERY_LIMIT: u32 = 1000;
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session TEXT NOT NULL,
+    room_id INTEGER,
+    cmd TEXT NOT NULL,
+    open_id TEXT,
+    uname TEXT,
+    msg_id TEXT,
+    received_at INTEGER NOT NULL,
+    payload TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_events_received_at ON events(received_at);
+CREATE INDEX IF NOT EXISTS idx_events_open_id ON events(open_id, received_at);
+CREATE INDEX IF NOT EXISTS idx_events_cmd ON events(cmd, received_at);
+CREATE INDEX IF NOT EXISTS idx_events_session ON events(session, received_at);
+
+CREATE TABLE IF NOT EXISTS danmaku (
+    event_id INTEGER PRIMARY KEY REFERENCES events(id),
+    msg TEXT NOT NULL,
+    dm_type INTEGER,
+    guard_level INTEGER,
+    fans_medal_level INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS gifts (
+    event_id INTEGER PRIMARY KEY REFERENCES events(id),
+    gift_id INTEGER,
+    gift_name TEXT,
+    gift_num INTEGER,
+    price INTEGER,
+    r_price INTEGER,
+    paid INTEGER,
+    combo_id TEXT
+);
+
+CREATE TABLE IF NOT EXISTS super_chats (
+    event_id INTEGER PRIMARY KEY REFERENCES events(id),
+    message_id INTEGER,
+    message TEXT,
+    rmb INTEGER,
+    start_time INTEGER,
+    end_time INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS guards (
+    event_id INTEGER PRIMARY KEY REFERENCES events(id),
+    guard_level INTEGER,
+    guard_num INTEGER,
+    guard_unit TEXT,
+    price INTEGER
+);
+
+-- trigram 分词支持中文子串检索，少于 3 个字的查询改用 LIKE
+CREATE VIRTUAL TABLE IF NOT EXISTS danmaku_fts USING fts5(
+    msg,
+    content = 'danmaku',
+    content_rowid = 'event_id',
+    tokenize = 'trigram'
+);
+"#;
+
+/// 归档查询条件，各条件之间为“且”的关系
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveQuery {
+    pub session: Option<String>,
+    pub room_id: Option<i64>,
+    /// 按观众查询
+    pub open_id: Option<String>,
+    /// 按消息类型查询，值为 `cmd`，如 `LIVE_OPEN_PLATFORM_SEND_GIFT`
+    pub cmds: Option<Vec<String>>,
+    /// 接收时间范围（毫秒时间戳），包含起点不包含终点
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    /// 弹幕全文检索，设置后只返回弹幕
+    pub text: Option<String>,
+    /// 只返回 id 小于该值的记录，用于向前翻页
+    pub before_id: Option<i64>,
+    /// 返回条数，默认 100，最多 1000
+    pub limit: Option<u32>,
+}
+
+/// 一条归档记录，按 id 倒序返回
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedEvent {
+    pub id: i64,
+    pub session: String,
+    pub room_id: Option<i64>,
+    pub cmd: String,
+    pub open_id: Option<String>,
+    pub uname: Option<String>,
+    pub received_at: i64,
+    pub message: BilibiliMessage,
+}
+
+struct ArchiveBatch {
+    session: String,
+    received_at: i64,
+    messages: Vec<BilibiliMessage>,
+}
+
+enum WriterCommand {
+    Append(ArchiveBatch),
+    /// 之前提交的批次全部写入后回复
+    Flush(oneshot::Sender<()>),
+}
+
+/// 消息归档，克隆后共用同一个写入线程
+#[derive(Clone)]
+pub struct EventArchive {
+    path: PathBuf,
+    writer: mpsc::Sender<WriterCommand>,
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+impl EventArchive {
+    /// 打开（不存在时创建）归档数据库并启动写入线程
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| format!("创建归档目录失败: {}", e))?;
+        }
+
+        let conn = Connection::open(&path).map_err(|e| format!("打开归档数据库失败: {}", e))?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .and_then(|_| conn.pragma_update(None, "synchronous", "NORMAL"))
+            .and_then(|_| conn.execute_batch(SCHEMA))
+            .map_err(|e| format!("初始化归档数据库失败: {}", e))?;
+
+        let (writer, receiver) = mpsc::channel::<WriterCommand>();
+        std::thread::Builder::new()
+            .name("event-archive".to_string())
+            .spawn(move || {
+                let mut conn = conn;
+                // 所有发送端释放后通道关闭，线程随之退出
+                while let Ok(command) = receiver.recv() {
+                    match command {
+                        WriterCommand::Append(batch) => {
+                            if let Err(e) = write_batch(&mut conn, &batch) {
+                                log::error!("写入消息归档失败: {}", e);
+                            }
+                        }
+                        WriterCommand::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+                log::info!("消息归档写入线程已退出");
+            })
+            .map_err(|e| format!("启动归档写入线程失败: {}", e))?;
+
+        log::info!("消息归档数据库: {:?}", path);
+        Ok(Self { path, writer })
+    }
+
+    /// 提交一批消息，写入在后台线程中完成
+    pub fn append(&self, session: &str, messages: &[BilibiliMessage]) {
+        if messages.is_empty() {
+            return;
+        }
+        let batch = ArchiveBatch {
+            session: session.to_string(),
+            received_at: now_millis(),
+            messages: messages.to_vec(),
+        };
+        if self.writer.send(WriterCommand::Append(batch)).is_err() {
+            log::error!("消息归档写入线程已退出，丢弃 {} 条消息", messages.len());
+        }
+    }
+
+    /// 等待已提交的消息全部写入数据库
+    pub async fn flush(&self) {
+        let (done, wait) = oneshot::channel();
+        if self.writer.send(WriterCommand::Flush(done)).is_ok() {
+            let _ = wait.await;
+        }
+    }
+
+    /// 按条件查询归档，在阻塞线程中执行
+    pub async fn query(&self, query: ArchiveQuery) -> Result<Vec<ArchivedEvent>, String> {
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || run_query(&path, &query))
+            .await
+            .map_err(|e| format!("归档查询任务失败: {}", e))?
+            .map_err(|e| format!("归档查询失败: {}", e))
+    }
+}
+
+/// 消息的公共字段：房间号、open_id、用户名、msg_id
+fn common_fields(
+    message: &BilibiliMessage,
+) -> (Option<i64>, Option<&str>, Option<&str>, Option<&str>) {
+    match message {
+        BilibiliMessage::Danmaku { data } => (
+            Some(data.room_id),
+            Some(&data.open_id),
+            Some(&data.uname),
+            Some(&data.msg_id),
+        ),
+        BilibiliMessage::Gift { data } => (
+            Some(data.room_id),
+            Some(&data.open_id),
+            Some(&data.uname),
+            Some(&data.msg_id),
+        ),
+        BilibiliMessage::SuperChat { data } => (
+            Some(data.room_id),
+            Some(&data.open_id),
+            Some(&data.uname),
+            Some(&data.msg_id),
+        ),
+        BilibiliMessage::Guard { data } => (
+            Some(data.room_id),
+            Some(&data.user_info.open_id),
+            Some(&data.user_info.uname),
+            Some(&data.msg_id),
+        ),
+        BilibiliMessage::Like { data } => (
+            Some(data.room_id),
+            Some(&data.open_id),
+            Some(&data.uname),
+            Some(&data.msg_id),
+        ),
+        BilibiliMessage::LiveRoomEnter { data } => (
+            Some(data.room_id),
+            Some(&data.open_id),
+            Some(&data.uname),
+            None,
+        ),
+        BilibiliMessage::SuperChatDel { data } => {
+            (Some(data.room_id), None, None, Some(&data.msg_id))
+        }
+        BilibiliMessage::LiveStart { data } => (Some(data.room_id), None, None, None),
+        BilibiliMessage::LiveEnd { data } => (Some(data.room_id), None, None, None),
+        _ => (None, None, None, None),
+    }
+}
+
+fn write_batch(conn: &mut Connection, batch: &ArchiveBatch) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for message in &batch.messages {
+        let payload = match serde_json::to_string(message) {
+            Ok(payload) => payload,
+            Err(e) => {
+                log::warn!("序列化归档消息失败: {}", e);
+                continue;
+            }
+        };
+        let (room_id, open_id, uname, msg_id) = common_fields(message);
+        tx.prepare_cached(
+            "INSERT INTO events (session, room_id, cmd, open_id, uname, msg_id, received_at, payload)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?
+        .execute(params![
+            batch.session,
+            room_id,
+            message.cmd(),
+            open_id,
+            uname,
+            msg_id,
+            batch.received_at,
+            payload
+        ])?;
+        let event_id = tx.last_insert_rowid();
+
+        match message {
+            BilibiliMessage::Danmaku { data } => {
+                tx.prepare_cached(
+                    "INSERT INTO danmaku (event_id, msg, dm_type, guard_level, fans_medal_level)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )?
+                .execute(params![
+                    event_id,
+                    data.msg,
+                    data.dm_type,
+                    data.guard_level,
+                    data.fans_medal_level
+                ])?;
+                tx.prepare_cached("INSERT INTO danmaku_fts (rowid, msg) VALUES (?1, ?2)")?
+                    .execute(params![event_id, data.msg])?;
+            }
+            BilibiliMessage::Gift { data } => {
+                let combo_id = data.combo_info.as_ref().map(|combo| &combo.combo_id);
+                tx.prepare_cached(
+                    "INSERT INTO gifts (event_id, gift_id, gift_name, gift_num, price, r_price, paid, combo_id)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                )?
+                .execute(params![
+                    event_id,
+                    data.gift_id,
+                    data.gift_name,
+                    data.gift_num,
+                    data.price,
+                    data.r_price,
+                    data.paid,
+                    combo_id
+                ])?;
+            }
+            BilibiliMessage::SuperChat { data } => {
+                tx.prepare_cached(
+                    "INSERT INTO super_chats (event_id, message_id, message, rmb, start_time, end_time)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )?
+                .execute(params![
+                    event_id,
+                    data.message_id,
+                    data.message,
+                    data.rmb,
+                    data.start_time,
+                    data.end_time
+                ])?;
+            }
+            BilibiliMessage::Guard { data } => {
+                tx.prepare_cached(
+                    "INSERT INTO guards (event_id, guard_level, guard_num, guard_unit, price)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )?
+                .execute(params![
+                    event_id,
+                    data.guard_level,
+                    data.guard_num,
+                    data.guard_unit,
+                    data.price
+                ])?;
+            }
+            _ => {}
+        }
+    }
+    tx.commit()
+}
+
+fn run_query(path: &Path, query: &ArchiveQuery) -> rusqlite::Result<Vec<ArchivedEvent>> {
+    let conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+
+    let mut sql = String::from(
+        "SELECT e.id, e.session, e.room_id, e.cmd, e.open_id, e.uname, e.received_at, e.payload
+         FROM events e",
+    );
+    let mut conditions: Vec<String> = Vec::new();
+    let mut values: Vec<SqlValue> = Vec::new();
+
+    if let Some(text) = query
+        .text
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        if text.chars().count() >= 3 {
+            // 整体作为一个短语匹配，避免用户输入被解析为 FTS 语法
+            sql.push_str(" JOIN danmaku_fts f ON f.rowid = e.id");
+            conditions.push("danmaku_fts MATCH ?".to_string());
+            values.push(SqlValue::Text(format!("\"{}\"", text.replace('"', "\"\""))));
+        } else {
+            sql.push_str(" JOIN danmaku d ON d.event_id = e.id");
+            conditions.push("d.msg LIKE ? ESCAPE '\\'".to_string());
+            let escaped = text
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            values.push(SqlValue::Text(format!("%{}%", escaped)));
+        }
+    }
+
+    if let Some(session) = &query.session {
+        conditions.push("e.session = ?".to_string());
+        values.push(SqlValue::Text(session.clone()));
+    }
+    if let Some(room_id) = query.room_id {
+        conditions.push("e.room_id = ?".to_string());
+        values.push(SqlValue::Integer(room_id));
+    }
+    if let Some(open_id) = &query.open_id {
+        conditions.push("e.open_id = ?".to_string());
+        values.push(SqlValue::Text(open_id.clone()));
+    }
+    if let Some(cmds) = query.cmds.as_ref().filter(|cmds| !cmds.is_empty()) {
+        let placeholders = vec!["?"; cmds.len()].join(", ");
+        conditions.push(format!("e.cmd IN ({})", placeholders));
+        values.extend(cmds.iter().cloned().map(SqlValue::Text));
+    }
+    if let Some(since) = query.since {
+        conditions.push("e.received_at >= ?".to_string());
+        values.push(SqlValue::Integer(since));
+    }
+    if let Some(until) = query.until {
+        conditions.push("e.received_at < ?".to_string());
+        values.push(SqlValue::Integer(until));
+    }
+    if let Some(before_id) = query.before_id {
+        conditions.push("e.id < ?".to_string());
+        values.push(SqlValue::Integer(before_id));
+    }
+
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY e.id DESC LIMIT ?");
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_QUERY_LIMIT);
+    values.push(SqlValue::Integer(limit as i64));
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(values), |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<i64>>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, i64>(6)?,
+            row.get::<_, String>(7)?,
+        ))
+    })?;
+
+    let mut events = Vec::new();
+    for row in rows {
+        let (id, session, room_id, cmd, open_id, uname, received_at, payload) = row?;
+        match serde_json::from_str::<BilibiliMessage>(&payload) {
+            Ok(message) => events.push(ArchivedEvent {
+                id,
+                session,
+                room_id,
+                cmd,
+                open_id,
+                uname,
+                received_at,
+                message,
+            }),
+            Err(e) => log::warn!("解析归档消息 {} 失败: {}", id, e),
+        }
+    }
+    Ok(events)
+}
@@ -1,4 +1,14 @@
-use crate::core::{BilibiliMessage, Proto, UnknownMessage};
+use crate::core::{
+    BilibiliMessage, ConnectionEvent, ParseDiagnostics, Proto, ProtoDecoder, ProtoError,
+};
+use crate::services::heartbeat::{
+    ConnectionHealth, HeartbeatConfig, HeartbeatMonitor, HeartbeatStatsSnapshot,
+};
+use crate::services::pipeline::{
+    self, InboundReceiver, InboundSender, PipelineConfig, PipelineStats,
+};
+use crate::services::recorder::{ReplayConfig, SessionReader, SessionRecorder};
+use crate::services::source::{LiveEvent, LiveEventSource};
 use futures_util::{SinkExt, StreamExt};
 use hmac::{Hmac, Mac};
 use reqwest::Client;
@@ -8,46 +18,235 @@ use sha2::Sha256;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::broadcast;
-use tokio::sync::mpsc;
-use tokio::time::Duration;
+use tokio::sync::{Mutex, Notify};
+use tokio::time::{Duration, Instant};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 use url::Url;
 
 type HmacSha256 = Hmac<Sha256>;
 
-#[derive(Debug)]
-pub struct BilibiliError {
-    message: String,
+/// 开放平台接口返回的业务错误码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenPlatformCode {
+    /// 4000 参数错误
+    InvalidParams,
+    /// 4001 应用无效
+    InvalidApp,
+    /// 4002 签名异常
+    SignatureError,
+    /// 4003 请求过期（本地时间偏差过大）
+    RequestExpired,
+    /// 4004 重复请求（nonce 重复）
+    DuplicateRequest,
+    /// 4005 来源IP异常
+    InvalidSourceIp,
+    /// 5000 服务异常
+    ServiceError,
+    /// 5001 请求超时
+    ServiceTimeout,
+    /// 5002 内部错误
+    InternalError,
+    /// 7000 不在游戏内
+    NotInGame,
+    /// 7001 请求冷却期
+    RequestCooldown,
+    /// 7002 房间重复游戏
+    DuplicateGame,
+    /// 7003 心跳过期或 game_id 错误
+    HeartbeatExpired,
+    /// 7007 身份码错误
+    InvalidIdCode,
+    /// 其他未收录的错误码
+    Other(i64),
+}
+
+impl OpenPlatformCode {
+    pub fn from_code(code: i64) -> Self {
+        match code {
+            4000 => OpenPlatformCode::InvalidParams,
+            4001 => OpenPlatformCode::InvalidApp,
+            4002 => OpenPlatformCode::SignatureError,
+            4003 => OpenPlatformCode::RequestExpired,
+            4004 => OpenPlatformCode::DuplicateRequest,
+            4005 => OpenPlatformCode::InvalidSourceIp,
+            5000 => OpenPlatformCode::ServiceError,
+            5001 => OpenPlatformCode::ServiceTimeout,
+            5002 => OpenPlatformCode::InternalError,
+            7000 => OpenPlatformCode::NotInGame,
+            7001 => OpenPlatformCode::RequestCooldown,
+            7002 => OpenPlatformCode::DuplicateGame,
+            7003 => OpenPlatformCode::HeartbeatExpired,
+            7007 => OpenPlatformCode::InvalidIdCode,
+            other => OpenPlatformCode::Other(other),
+        }
+    }
+
+    /// 是否属于鉴权类错误（密钥、签名、身份码等配置问题）
+    pub fn is_auth(self) -> bool {
+        matches!(
+            self,
+            OpenPlatformCode::InvalidApp
+                | OpenPlatformCode::SignatureError
+                | OpenPlatformCode::InvalidSourceIp
+                | OpenPlatformCode::InvalidIdCode
+        )
+    }
+
+    /// 稍后重试是否可能成功
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            OpenPlatformCode::RequestExpired
+                | OpenPlatformCode::DuplicateRequest
+                | OpenPlatformCode::ServiceError
+                | OpenPlatformCode::ServiceTimeout
+                | OpenPlatformCode::InternalError
+                | OpenPlatformCode::RequestCooldown
+                | OpenPlatformCode::DuplicateGame
+                | OpenPlatformCode::HeartbeatExpired
+        )
+    }
+
+    /// 游戏会话是否已在平台侧失效，需要重新调用 `/v2/app/start`
+    pub fn is_session_expired(self) -> bool {
+        matches!(
+            self,
+            OpenPlatformCode::NotInGame | OpenPlatformCode::HeartbeatExpired
+        )
+    }
+}
+
+/// Bilibili客户端错误
+#[derive(Debug, Clone)]
+pub enum BilibiliError {
+    /// 网络传输失败（HTTP请求、WebSocket连接）
+    Transport { message: String },
+    /// 协议或数据解析失败
+    Protocol { message: String },
+    /// 鉴权失败：签名、密钥、身份码或WebSocket认证
+    Auth { code: Option<i64>, message: String },
+    /// 开放平台接口返回了非0的业务错误码
+    Api { code: i64, message: String },
+    /// 本地配置错误（地址、密钥格式等）
+    Config { message: String },
+}
+
+/// 返回给前端的错误内容
+#[derive(Debug, Serialize)]
+struct BilibiliErrorPayload<'a> {
+    kind: &'static str,
+    code: Option<i64>,
+    message: &'a str,
+    retryable: bool,
+}
+
+impl BilibiliError {
+    /// 根据开放平台返回的错误码构造错误，鉴权类错误码归为 `Auth`
+    pub fn api(code: i64, message: impl Into<String>) -> Self {
+        let message = message.into();
+        if OpenPlatformCode::from_code(code).is_auth() {
+            BilibiliError::Auth {
+                code: Some(code),
+                message,
+            }
+        } else {
+            BilibiliError::Api { code, message }
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BilibiliError::Transport { .. } => "transport",
+            BilibiliError::Protocol { .. } => "protocol",
+            BilibiliError::Auth { .. } => "auth",
+            BilibiliError::Api { .. } => "api",
+            BilibiliError::Config { .. } => "config",
+        }
+    }
+
+    /// 开放平台返回的错误码
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            BilibiliError::Auth { code, .. } => *code,
+            BilibiliError::Api { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            BilibiliError::Transport { message }
+            | BilibiliError::Protocol { message }
+            | BilibiliError::Auth { message, .. }
+            | BilibiliError::Api { message, .. }
+            | BilibiliError::Config { message } => message,
+        }
+    }
+
+    /// 稍后重试是否可能成功
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BilibiliError::Transport { .. } => true,
+            BilibiliError::Api { code, .. } => OpenPlatformCode::from_code(*code).is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// 游戏会话是否已在平台侧失效
+    pub fn is_session_expired(&self) -> bool {
+        self.code()
+            .is_some_and(|code| OpenPlatformCode::from_code(code).is_session_expired())
+    }
 }
 
 impl fmt::Display for BilibiliError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.message)
+        match self {
+            BilibiliError::Transport { message } => write!(f, "网络错误: {}", message),
+            BilibiliError::Protocol { message } => write!(f, "协议错误: {}", message),
+            BilibiliError::Auth {
+                code: Some(code),
+                message,
+            } => write!(f, "鉴权失败({}): {}", code, message),
+            BilibiliError::Auth {
+                code: None,
+                message,
+            } => write!(f, "鉴权失败: {}", message),
+            BilibiliError::Api { code, message } => write!(f, "接口错误({}): {}", code, message),
+            BilibiliError::Config { message } => write!(f, "配置错误: {}", message),
+        }
     }
 }
 
 impl Error for BilibiliError {}
 
-impl From<String> for BilibiliError {
-    fn from(message: String) -> Self {
-        BilibiliError { message }
+impl Serialize for BilibiliError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BilibiliErrorPayload {
+            kind: self.kind(),
+            code: self.code(),
+            message: self.message(),
+            retryable: self.is_retryable(),
+        }
+        .serialize(serializer)
     }
 }
 
-impl From<&str> for BilibiliError {
-    fn from(message: &str) -> Self {
-        BilibiliError {
-            message: message.to_string(),
+impl From<ProtoError> for BilibiliError {
+    fn from(err: ProtoError) -> Self {
+        BilibiliError::Protocol {
+            message: err.to_string(),
         }
     }
 }
 
 impl From<serde_json::Error> for BilibiliError {
     fn from(err: serde_json::Error) -> Self {
-        BilibiliError {
+        BilibiliError::Protocol {
             message: err.to_string(),
         }
     }
@@ -55,7 +254,7 @@ impl From<serde_json::Error> for BilibiliError {
 
 impl From<reqwest::Error> for BilibiliError {
     fn from(err: reqwest::Error) -> Self {
-        BilibiliError {
+        BilibiliError::Transport {
             message: err.to_string(),
         }
     }
@@ -63,7 +262,7 @@ impl From<reqwest::Error> for BilibiliError {
 
 impl From<tokio_tungstenite::tungstenite::Error> for BilibiliError {
     fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
-        BilibiliError {
+        BilibiliError::Transport {
             message: err.to_string(),
         }
     }
@@ -71,7 +270,7 @@ impl From<tokio_tungstenite::tungstenite::Error> for BilibiliError {
 
 impl From<url::ParseError> for BilibiliError {
     fn from(err: url::ParseError) -> Self {
-        BilibiliError {
+        BilibiliError::Config {
             message: err.to_string(),
         }
     }
@@ -79,7 +278,7 @@ impl From<url::ParseError> for BilibiliError {
 
 impl From<hmac::digest::InvalidLength> for BilibiliError {
     fn from(err: hmac::digest::InvalidLength) -> Self {
-        BilibiliError {
+        BilibiliError::Config {
             message: err.to_string(),
         }
     }
@@ -87,7 +286,7 @@ impl From<hmac::digest::InvalidLength> for BilibiliError {
 
 impl From<std::time::SystemTimeError> for BilibiliError {
     fn from(err: std::time::SystemTimeError) -> Self {
-        BilibiliError {
+        BilibiliError::Config {
             message: err.to_string(),
         }
     }
@@ -95,7 +294,7 @@ impl From<std::time::SystemTimeError> for BilibiliError {
 
 impl From<reqwest::header::InvalidHeaderValue> for BilibiliError {
     fn from(err: reqwest::header::InvalidHeaderValue) -> Self {
-        BilibiliError {
+        BilibiliError::Config {
             message: err.to_string(),
         }
     }
@@ -108,6 +307,15 @@ pub struct BilibiliConfig {
     pub access_key: String,
     pub access_secret: String,
     pub host: String,
+    pub reconnect: ReconnectPolicy,
+    /// 录制原始WebSocket帧的文件路径
+    pub record_path: Option<PathBuf>,
+    /// 设置后从录制文件回放，不连接开放平台
+    pub replay: Option<ReplayConfig>,
+    /// 消费端消息管道的容量与丢弃策略
+    pub pipeline: PipelineConfig,
+    /// 心跳间隔与失效判定
+    pub heartbeat: HeartbeatConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -118,15 +326,24 @@ struct AppStartRequest {
 
 #[derive(Debug, Serialize, Deserialize)]
 struct AppStartResponse {
-    code: i32,
+    code: i64,
     message: String,
-    data: AppStartData,
+    // 出错时平台返回的 data 可能为空对象或 null，确认成功后再按结构解析
+    #[serde(default)]
+    data: Value,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct AppStartData {
     game_info: GameInfo,
     websocket_info: WebSocketInfo,
+    #[serde(default)]
+    anchor_info: Option<AnchorInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AnchorInfo {
+    room_id: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -151,192 +368,799 @@ struct AppEndRequest {
     app_id: u64,
 }
 
-pub struct BilibiliClient {
+type WsStream =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+type WsSink = Arc<Mutex<futures_util::stream::SplitSink<WsStream, Message>>>;
+
+/// 读取循环按优先级把解析出的消息放进入站队列，发送时从不等待消费端，
+/// 消费端积压不会拖慢心跳回复的读取和取消处理；积压时可丢弃的消息在入队时就被丢弃
+type MessageSender = InboundSender;
+
+/// 断线重连的退避策略
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// 第一次重连前的等待时间
+    pub initial_delay: Duration,
+    /// 退避等待时间的上限
+    pub max_delay: Duration,
+    /// 最大重连次数，`None` 表示一直重试
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// 计算第 `attempt` 次重连前的等待时间（指数退避 + 随机抖动）
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let base = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt.min(16)))
+            .min(self.max_delay);
+        let half_ms = (base.as_millis() / 2) as u64;
+        Duration::from_millis(half_ms + rand::random_range(0..=half_ms))
+    }
+}
+
+/// 一次 `/v2/app/start` 得到的游戏会话信息
+#[derive(Debug, Clone)]
+struct GameSession {
+    game_id: String,
+    room_id: Option<i64>,
+    wss_links: Vec<String>,
+    auth_body: String,
+}
+
+/// 单帧消息中携带的认证结果
+enum AuthReply {
+    Success,
+    Failed(String),
+}
+
+/// 一条WebSocket连接结束的原因
+enum ConnectionEnd {
+    /// 客户端主动关闭
+    Cancelled,
+    /// 连接断开，可以换一个地址重连
+    Lost(String),
+    /// 认证失败，需要重新获取游戏会话
+    AuthFailed(String),
+    /// 应用心跳判定游戏会话已失效
+    SessionExpired,
+    /// 开放平台结束了互动玩法，不再重连
+    InteractionEnded,
+}
+
+/// 在客户端与后台任务之间共享的连接状态
+#[derive(Clone)]
+struct SharedState {
     config: BilibiliConfig,
     client: Client,
-    game_id: Option<String>,
-    // 用于控制心跳任务停止的取消令牌
-    heartbeat_cancel_tx: Option<broadcast::Sender<()>>,
-    // 用于保存WebSocket连接，便于主动关闭
-    ws_sink: Option<
-        Arc<
-            tokio::sync::Mutex<
-                futures_util::stream::SplitSink<
-                    tokio_tungstenite::WebSocketStream<
-                        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
-                    >,
-                    Message,
-                >,
-            >,
-        >,
-    >,
+    session: Arc<Mutex<Option<GameSession>>>,
+    // 当前WebSocket连接的写端，便于主动关闭
+    ws_sink: Arc<Mutex<Option<WsSink>>>,
+    // 应用心跳判定会话失效时通知正在运行的连接，使用 `notify_waiters` 不保留许可，
+    // 重连等待期间发出的通知不会误伤之后新建的连接
+    session_lost: Arc<Notify>,
+    // 收到互动结束消息时通知连接守护任务停止
+    interaction_ended: Arc<Notify>,
+    // 连接生命周期事件的广播通道及最新状态
+    events: broadcast::Sender<ConnectionEvent>,
+    state: Arc<std::sync::Mutex<ConnectionEvent>>,
+    // 会话录制器
+    recorder: Option<Arc<SessionRecorder>>,
+    // 消息管道计数
+    pipeline_stats: Arc<PipelineStats>,
+    // 心跳健康监测
+    heartbeat: Arc<HeartbeatMonitor>,
+    // 用于控制后台任务停止的取消令牌，断开连接时取出
+    cancel: Arc<std::sync::Mutex<Option<broadcast::Sender<()>>>>,
+}
+
+impl SharedState {
+    /// 录制一个原始二进制帧，录制失败不影响消息处理
+    fn record_frame(&self, data: &[u8]) {
+        if let Some(recorder) = &self.recorder
+            && let Err(e) = recorder.record(data)
+        {
+            log::warn!("录制消息帧失败: {}", e);
+        }
+    }
+
+    /// 记录最新的连接状态并广播给订阅者
+    fn emit_event(&self, event: ConnectionEvent) {
+        log::info!("连接状态变化: {:?}", event);
+        if let Ok(mut state) = self.state.lock() {
+            *state = event.clone();
+        }
+        // 没有订阅者时发送会失败，忽略即可
+        let _ = self.events.send(event);
+    }
+
+    /// 保存本次连接的取消通道
+    fn set_cancel(&self, cancel_tx: broadcast::Sender<()>) {
+        if let Ok(mut cancel) = self.cancel.lock() {
+            *cancel = Some(cancel_tx);
+        }
+    }
+
+    /// 心跳成功后，如果连接从降级中恢复则广播 `HeartbeatRecovered`
+    fn heartbeat_succeeded(&self, previous: ConnectionHealth) {
+        if previous != ConnectionHealth::Healthy
+            && self.heartbeat.health() == ConnectionHealth::Healthy
+        {
+            self.emit_event(ConnectionEvent::HeartbeatRecovered {
+                timestamp: ConnectionEvent::now(),
+            });
+        }
+    }
+}
+
+pub struct BilibiliClient {
+    shared: SharedState,
+    // 作为 `LiveEventSource` 使用时保存的接收端
+    source: Option<SourceReceivers>,
+}
+
+/// 客户端的共享句柄
+///
+/// 客户端作为 `LiveEventSource` 交给消费端读取后，仍可以通过句柄查询连接状态和断开连接
+#[derive(Clone)]
+pub struct ClientHandle {
+    shared: SharedState,
+}
+
+/// `LiveEventSource::connect` 之后由 `next_event` 读取的消息和连接事件
+struct SourceReceivers {
+    messages: InboundReceiver,
+    events: broadcast::Receiver<ConnectionEvent>,
 }
 
 impl BilibiliClient {
     pub fn new(config: BilibiliConfig) -> Self {
+        let (events, _) = broadcast::channel(64);
+        let heartbeat = Arc::new(HeartbeatMonitor::new(config.heartbeat.clone()));
         Self {
-            config,
-            client: Client::new(),
-            game_id: None,
-            heartbeat_cancel_tx: None,
-            ws_sink: None,
+            shared: SharedState {
+                config,
+                client: Client::new(),
+                session: Arc::new(Mutex::new(None)),
+                ws_sink: Arc::new(Mutex::new(None)),
+                session_lost: Arc::new(Notify::new()),
+                interaction_ended: Arc::new(Notify::new()),
+                events,
+                state: Arc::new(std::sync::Mutex::new(ConnectionEvent::closed("未连接"))),
+                recorder: None,
+                pipeline_stats: Arc::new(PipelineStats::default()),
+                heartbeat,
+                cancel: Arc::new(std::sync::Mutex::new(None)),
+            },
+            source: None,
         }
     }
 
-    pub async fn connect(
-        &mut self,
-    ) -> Result<mpsc::UnboundedReceiver<BilibiliMessage>, BilibiliError> {
+    /// 获取客户端的共享句柄，录制器在连接时创建，连接之后获取的句柄才能在断开时报告录制结果
+    pub fn handle(&self) -> ClientHandle {
+        ClientHandle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// 订阅连接生命周期事件，需在 `connect` 之前订阅才能收到 `Connecting`
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.shared.events.subscribe()
+    }
+
+    pub async fn connect(&mut self) -> Result<InboundReceiver, BilibiliError> {
         log::info!("=== 开始连接哔哩哔哩直播间 ===");
-        let (sender, receiver) = mpsc::unbounded_channel();
+        let (sender, receiver) = pipeline::inbound(
+            &self.shared.config.pipeline,
+            self.shared.pipeline_stats.clone(),
+        );
+        self.shared.emit_event(ConnectionEvent::Connecting {
+            timestamp: ConnectionEvent::now(),
+        });
+
+        if let Some(replay) = self.shared.config.replay.clone() {
+            // 回放时不录制，避免录制路径与回放文件相同时把回放文件清空
+            if self.shared.config.record_path.is_some() {
+                log::info!("回放模式下不录制会话");
+            }
+            return self.connect_replay(replay, sender, receiver).await;
+        }
 
-        // 获取websocket连接信息
-        log::info!("正在获取WebSocket连接信息...");
-        let (ws_url, auth_body) = self.get_websocket_info().await?;
-        log::info!("WebSocket URL: {}", ws_url);
+        if let Some(path) = &self.shared.config.record_path {
+            match SessionRecorder::create(path) {
+                Ok(recorder) => {
+                    self.shared.recorder = Some(Arc::new(recorder));
+                }
+                Err(e) => {
+                    let e = BilibiliError::Config {
+                        message: format!("创建录制文件失败: {}", e),
+                    };
+                    self.shared
+                        .emit_event(ConnectionEvent::closed(e.to_string()));
+                    return Err(e);
+                }
+            }
+        }
+
+        let opened = async {
+            // 获取websocket连接信息
+            log::info!("正在获取WebSocket连接信息...");
+            let session = Self::start_game_session(&self.shared).await?;
 
-        // 连接websocket
-        log::info!("正在连接WebSocket...");
-        let url = Url::parse(&ws_url)?;
-        let (ws_stream, _) = connect_async(url).await?;
+            // 连接websocket
+            log::info!("正在连接WebSocket...");
+            let (ws_stream, link_index) = Self::connect_any(&session.wss_links, 0).await?;
+            Ok::<_, BilibiliError>((session, ws_stream, link_index))
+        }
+        .await;
+
+        let (session, ws_stream, link_index) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                self.shared
+                    .emit_event(ConnectionEvent::closed(format!("连接失败: {}", e)));
+                return Err(e);
+            }
+        };
         log::info!("WebSocket连接成功！");
 
+        // 创建取消通道
+        let (cancel_tx, _cancel_rx) = broadcast::channel(1);
+        self.shared.set_cancel(cancel_tx.clone());
+
         // 启动各种任务
-        self.start_tasks(ws_stream, auth_body, sender).await?;
+        Self::spawn_app_heartbeat(self.shared.clone(), cancel_tx.subscribe());
+        tokio::spawn(Self::supervise(
+            self.shared.clone(),
+            ws_stream,
+            session.auth_body,
+            link_index,
+            sender,
+            cancel_tx.subscribe(),
+        ));
 
         Ok(receiver)
     }
 
-    async fn start_tasks(
+    /// 从录制文件回放消息，不需要开放平台凭据
+    async fn connect_replay(
         &mut self,
-        ws_stream: tokio_tungstenite::WebSocketStream<
-            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
-        >,
-        auth_body: String,
-        sender: mpsc::UnboundedSender<BilibiliMessage>,
-    ) -> Result<(), BilibiliError> {
-        let (mut ws_sink, mut ws_stream) = ws_stream.split();
+        replay: ReplayConfig,
+        sender: MessageSender,
+        receiver: InboundReceiver,
+    ) -> Result<InboundReceiver, BilibiliError> {
+        log::info!(
+            "正在打开录制文件: {:?}，倍速: {}",
+            replay.path,
+            replay.speed
+        );
+        let opened = match replay.validate() {
+            Ok(()) => SessionReader::open(&replay.path)
+                .await
+                .map_err(|e| format!("打开录制文件失败: {}", e)),
+            Err(e) => Err(e),
+        };
+        let reader = match opened {
+            Ok(reader) => reader,
+            Err(message) => {
+                let e = BilibiliError::Config { message };
+                self.shared
+                    .emit_event(ConnectionEvent::closed(e.to_string()));
+                return Err(e);
+            }
+        };
 
-        // 创建心跳取消通道
         let (cancel_tx, _cancel_rx) = broadcast::channel(1);
-        self.heartbeat_cancel_tx = Some(cancel_tx.clone());
+        self.shared.set_cancel(cancel_tx.clone());
 
-        // 发送认证
-        log::info!("正在发送认证信息...");
-        let mut auth_proto = Proto::new();
-        auth_proto.body = auth_body.into_bytes();
-        auth_proto.op = 7;
-        let auth_packet = auth_proto.pack();
+        tokio::spawn(Self::run_replay(
+            self.shared.clone(),
+            reader,
+            replay,
+            sender,
+            cancel_tx.subscribe(),
+        ));
 
-        ws_sink.send(Message::Binary(auth_packet)).await?;
-        log::info!("认证信息发送成功！");
+        Ok(receiver)
+    }
+
+    /// 按录制时的时间间隔（除以倍速）把每一帧送入 `handle_message`
+    async fn run_replay(
+        shared: SharedState,
+        mut reader: SessionReader,
+        replay: ReplayConfig,
+        sender: MessageSender,
+        mut cancel_rx: broadcast::Receiver<()>,
+    ) {
+        let started = tokio::time::Instant::now();
+        let mut first_timestamp = None;
+        let mut frames = 0u64;
+
+        loop {
+            let frame = match reader.next_frame().await {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(e) => {
+                    log::error!("读取录制文件失败: {}", e);
+                    shared.emit_event(ConnectionEvent::closed(format!("读取录制文件失败: {}", e)));
+                    return;
+                }
+            };
+
+            let base = *first_timestamp.get_or_insert(frame.timestamp_ms);
+            let Some(deadline) = replay
+                .delay_for(frame.timestamp_ms.saturating_sub(base))
+                .and_then(|delay| started.checked_add(delay))
+            else {
+                log::error!("回放等待时间超出范围，倍速: {}", replay.speed);
+                shared.emit_event(ConnectionEvent::closed(format!(
+                    "回放等待时间超出范围，倍速: {}",
+                    replay.speed
+                )));
+                return;
+            };
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => {}
+                _ = cancel_rx.recv() => return,
+            }
+
+            match Self::handle_message(&shared, &frame.data, &sender).await {
+                Ok(Some(AuthReply::Success)) => shared.emit_event(ConnectionEvent::Authenticated {
+                    timestamp: ConnectionEvent::now(),
+                }),
+                Ok(Some(AuthReply::Failed(reason))) => {
+                    shared.emit_event(ConnectionEvent::AuthFailed {
+                        reason,
+                        timestamp: ConnectionEvent::now(),
+                    })
+                }
+                Ok(None) => {}
+                Err(e) => log::error!("处理回放消息失败: {}", e),
+            }
+            frames += 1;
+        }
+
+        log::info!("回放结束，共 {} 帧", frames);
+        shared.emit_event(ConnectionEvent::closed(format!(
+            "回放结束，共 {} 帧",
+            frames
+        )));
+    }
 
-        // 启动WebSocket心跳任务
-        let ws_sink = Arc::new(tokio::sync::Mutex::new(ws_sink));
-        self.ws_sink = Some(ws_sink.clone());
-        let ws_sink_clone = ws_sink.clone();
-        let mut cancel_rx_ws = cancel_tx.subscribe();
+    /// 依次尝试 `wss_links` 中的地址，从 `start` 开始轮换，返回第一个连上的连接及其下标
+    async fn connect_any(
+        wss_links: &[String],
+        start: usize,
+    ) -> Result<(WsStream, usize), BilibiliError> {
+        let mut last_error = BilibiliError::Config {
+            message: "没有可用的WebSocket地址".to_string(),
+        };
+        for offset in 0..wss_links.len() {
+            let index = (start + offset) % wss_links.len();
+            log::info!("WebSocket URL: {}", wss_links[index]);
+            let url = match Url::parse(&wss_links[index]) {
+                Ok(url) => url,
+                Err(e) => {
+                    last_error = e.into();
+                    continue;
+                }
+            };
+            match connect_async(url).await {
+                Ok((ws_stream, _)) => return Ok((ws_stream, index)),
+                Err(e) => {
+                    log::warn!("连接WebSocket地址失败: {}", e);
+                    last_error = e.into();
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// 启动应用心跳任务，失败时按配置重试，连续失败达到上限时通知重连任务重新获取游戏会话
+    fn spawn_app_heartbeat(shared: SharedState, mut cancel_rx: broadcast::Receiver<()>) {
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(20));
+            let config = shared.heartbeat.config().clone();
+            let mut interval = tokio::time::interval(config.app_interval());
             loop {
                 tokio::select! {
-                    _ = interval.tick() => {
-                        let mut proto = Proto::new();
-                        proto.op = 2;
-                        let packet = proto.pack();
-
-                        let mut sink = ws_sink_clone.lock().await;
-                        if let Err(e) = sink.send(Message::Binary(packet)).await {
-                            log::error!("发送WebSocket心跳失败: {}", e);
+                    _ = interval.tick() => {}
+                    _ = cancel_rx.recv() => {
+                        log::info!("应用心跳任务已停止");
+                        return;
+                    }
+                }
+
+                // 失败后间隔一小段时间重试，直到成功或判定会话失效
+                loop {
+                    let game_id = match shared.session.lock().await.as_ref() {
+                        Some(session) => session.game_id.clone(),
+                        None => break,
+                    };
+                    let heartbeat_req = HeartbeatRequest { game_id };
+
+                    let started = Instant::now();
+                    match Self::send_app_heartbeat(&shared.client, &shared.config, &heartbeat_req)
+                        .await
+                    {
+                        Ok(_) => {
+                            let latency = started.elapsed();
+                            log::info!("发送应用心跳成功，延迟: {:?}", latency);
+                            let previous = shared.heartbeat.app_success(latency);
+                            shared.heartbeat_succeeded(previous);
                             break;
                         }
-                        log::info!("发送WebSocket心跳成功");
+                        Err(e) => {
+                            let failures = shared.heartbeat.app_failure();
+                            log::error!(
+                                "发送应用心跳失败({}/{}): {}",
+                                failures,
+                                config.app_max_failures,
+                                e
+                            );
+                            shared.emit_event(ConnectionEvent::HeartbeatLost {
+                                reason: format!(
+                                    "应用心跳失败({}/{}): {}",
+                                    failures, config.app_max_failures, e
+                                ),
+                                timestamp: ConnectionEvent::now(),
+                            });
+                            // 平台明确告知会话失效时无需继续重试
+                            if e.is_session_expired() || failures >= config.app_max_failures {
+                                shared.session_lost.notify_waiters();
+                                break;
+                            }
+                        }
                     }
-                    _ = cancel_rx_ws.recv() => {
-                        log::info!("WebSocket心跳任务已停止");
-                        break;
+
+                    tokio::select! {
+                        _ = tokio::time::sleep(config.app_retry_delay()) => {}
+                        _ = cancel_rx.recv() => {
+                            log::info!("应用心跳任务已停止");
+                            return;
+                        }
                     }
                 }
             }
         });
+    }
+
+    /// 连接守护任务：连接断开后按退避策略轮换地址重连，必要时重新获取游戏会话
+    async fn supervise(
+        shared: SharedState,
+        mut ws_stream: WsStream,
+        mut auth_body: String,
+        mut link_index: usize,
+        sender: MessageSender,
+        mut cancel_rx: broadcast::Receiver<()>,
+    ) {
+        let policy = shared.config.reconnect.clone();
+        let mut attempt = 0;
+
+        loop {
+            let (end, authenticated) =
+                Self::run_connection(&shared, ws_stream, auth_body, &sender, &mut cancel_rx).await;
+
+            let mut reason = match end {
+                ConnectionEnd::Cancelled => return,
+                ConnectionEnd::InteractionEnded => {
+                    log::info!("互动玩法已结束，停止重连");
+                    return;
+                }
+                ConnectionEnd::Lost(reason) => {
+                    log::warn!("WebSocket连接断开: {}", reason);
+                    reason
+                }
+                ConnectionEnd::AuthFailed(reason) => {
+                    log::warn!("认证失败，将重新获取游戏会话: {}", reason);
+                    Self::drop_game_session(&shared).await;
+                    format!("认证失败: {}", reason)
+                }
+                ConnectionEnd::SessionExpired => {
+                    log::warn!("游戏会话已失效，将重新获取游戏会话");
+                    Self::drop_game_session(&shared).await;
+                    "游戏会话已失效".to_string()
+                }
+            };
+
+            if authenticated {
+                attempt = 0;
+            }
+
+            (ws_stream, auth_body) = loop {
+                if policy.max_attempts.is_some_and(|max| attempt >= max) {
+                    log::error!("重连次数已达上限({})，停止重连", attempt);
+                    Self::give_up(
+                        &shared,
+                        format!("重连次数已达上限({}): {}", attempt, reason),
+                    )
+                    .await;
+                    return;
+                }
+
+                let delay = policy.delay_for(attempt);
+                attempt += 1;
+                log::info!("{:?}后进行第{}次重连...", delay, attempt);
+                shared.emit_event(ConnectionEvent::Reconnecting {
+                    attempt,
+                    delay_ms: delay.as_millis() as u64,
+                    reason: reason.clone(),
+                    timestamp: ConnectionEvent::now(),
+                });
 
-        // 启动应用心跳任务
-        let client = self.client.clone();
-        let config = self.config.clone();
-        let game_id = self.game_id.clone().unwrap();
-        let mut cancel_rx_app = cancel_tx.subscribe();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(20));
-            loop {
                 tokio::select! {
-                    _ = interval.tick() => {
-                        let heartbeat_req = HeartbeatRequest {
-                            game_id: game_id.clone(),
-                        };
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = cancel_rx.recv() => return,
+                }
 
-                        match Self::send_app_heartbeat(&client, &config, &heartbeat_req).await {
-                            Ok(_) => log::info!("发送应用心跳成功"),
-                            Err(e) => {
-                                log::error!("发送应用心跳失败: {}", e);
-                                // 如果心跳失败，也停止任务
-                                break;
-                            }
-                        }
+                match Self::reconnect_once(&shared, link_index + 1).await {
+                    Ok((stream, index, body)) => {
+                        link_index = index;
+                        log::info!("WebSocket重连成功！");
+                        break (stream, body);
                     }
-                    _ = cancel_rx_app.recv() => {
-                        log::info!("应用心跳任务已停止");
-                        break;
+                    Err(e) => {
+                        log::warn!("第{}次重连失败: {}", attempt, e);
+                        // 鉴权和配置错误重试也不会成功，直接放弃
+                        if matches!(e, BilibiliError::Auth { .. } | BilibiliError::Config { .. }) {
+                            Self::give_up(&shared, format!("重连失败，停止重连: {}", e)).await;
+                            return;
+                        }
+                        reason = e.to_string();
                     }
                 }
+            };
+        }
+    }
+
+    /// 放弃重连：停止应用心跳并结束游戏会话，再广播最终的 `Closed` 事件
+    async fn give_up(shared: &SharedState, reason: String) {
+        let cancel_tx = shared
+            .cancel
+            .lock()
+            .ok()
+            .and_then(|mut cancel| cancel.take());
+        if let Some(cancel_tx) = cancel_tx {
+            let _ = cancel_tx.send(());
+        }
+        Self::drop_game_session(shared).await;
+        shared.emit_event(ConnectionEvent::closed(reason));
+    }
+
+    /// 丢弃当前游戏会话并尽量调用 `/v2/app/end` 结束它，失败只记录日志
+    async fn drop_game_session(shared: &SharedState) {
+        let session = shared.session.lock().await.take();
+        if let Some(session) = session
+            && let Err(e) = Self::end_game_session(shared, session).await
+        {
+            log::warn!("结束旧的游戏会话失败: {}", e);
+        }
+    }
+
+    /// 调用 `/v2/app/end` 结束游戏会话
+    async fn end_game_session(
+        shared: &SharedState,
+        session: GameSession,
+    ) -> Result<(), BilibiliError> {
+        let url = format!("{}/v2/app/end", shared.config.host);
+        let request = AppEndRequest {
+            game_id: session.game_id,
+            app_id: shared.config.app_id,
+        };
+
+        let body = serde_json::to_string(&request)?;
+        let headers = Self::sign_static(&shared.config, &body)?;
+
+        let response = shared
+            .client
+            .post(&url)
+            .headers(headers)
+            .body(body)
+            .send()
+            .await?;
+
+        let response_text = response.text().await?;
+        log::info!("关闭应用成功: {}", response_text);
+        Ok(())
+    }
+
+    /// 进行一次重连：没有可用的游戏会话时先重新调用 `/v2/app/start`
+    async fn reconnect_once(
+        shared: &SharedState,
+        start: usize,
+    ) -> Result<(WsStream, usize, String), BilibiliError> {
+        let existing = shared.session.lock().await.clone();
+        let session = match existing {
+            Some(session) => session,
+            None => Self::start_game_session(shared).await?,
+        };
+
+        let (ws_stream, index) = Self::connect_any(&session.wss_links, start).await?;
+        Ok((ws_stream, index, session.auth_body))
+    }
+
+    /// 在一条WebSocket连接上完成认证、心跳和消息接收，直到连接结束
+    ///
+    /// 返回连接结束的原因，以及这条连接是否认证成功过
+    async fn run_connection(
+        shared: &SharedState,
+        ws_stream: WsStream,
+        auth_body: String,
+        sender: &MessageSender,
+        cancel_rx: &mut broadcast::Receiver<()>,
+    ) -> (ConnectionEnd, bool) {
+        let (mut ws_sink, mut ws_stream) = ws_stream.split();
+        // 在整条连接期间只注册一次，处理消息的间隙发出的失效通知也不会丢失
+        let session_lost = shared.session_lost.notified();
+        tokio::pin!(session_lost);
+
+        // 发送认证
+        log::info!("正在发送认证信息...");
+        let mut auth_proto = Proto::new();
+        auth_proto.body = auth_body.into_bytes();
+        auth_proto.op = 7;
+        let auth_packet = auth_proto.pack();
+
+        if let Err(e) = ws_sink.send(Message::Binary(auth_packet)).await {
+            return (
+                ConnectionEnd::Lost(format!("发送认证信息失败: {}", e)),
+                false,
+            );
+        }
+        log::info!("认证信息发送成功！");
+
+        // 启动WebSocket心跳任务，发送失败或连续未回复达到上限时结束并返回原因
+        let ws_sink = Arc::new(Mutex::new(ws_sink));
+        *shared.ws_sink.lock().await = Some(ws_sink.clone());
+        shared.heartbeat.reset_ws();
+        let heartbeat_shared = shared.clone();
+        let mut heartbeat_task = tokio::spawn(async move {
+            let config = heartbeat_shared.heartbeat.config().clone();
+            let mut interval = tokio::time::interval(config.ws_interval());
+            loop {
+                interval.tick().await;
+                let missed = heartbeat_shared.heartbeat.ws_sent();
+                if missed >= config.ws_max_missed {
+                    return format!("连续{}次WebSocket心跳未收到回复", missed);
+                }
+                if missed > 0 {
+                    log::warn!(
+                        "WebSocket心跳未收到回复({}/{})",
+                        missed,
+                        config.ws_max_missed
+                    );
+                    heartbeat_shared.emit_event(ConnectionEvent::HeartbeatLost {
+                        reason: format!(
+                            "WebSocket心跳未收到回复({}/{})",
+                            missed, config.ws_max_missed
+                        ),
+                        timestamp: ConnectionEvent::now(),
+                    });
+                }
+
+                let mut proto = Proto::new();
+                proto.op = 2;
+                let packet = proto.pack();
+
+                let mut sink = ws_sink.lock().await;
+                if let Err(e) = sink.send(Message::Binary(packet)).await {
+                    log::error!("发送WebSocket心跳失败: {}", e);
+                    return format!("WebSocket心跳发送失败: {}", e);
+                }
+                log::info!("发送WebSocket心跳成功");
             }
         });
 
-        // 启动消息接收任务
-        let mut cancel_rx_msg = cancel_tx.subscribe();
-        tokio::spawn(async move {
-            loop {
-                tokio::select! {
-                    msg = ws_stream.next() => {
-                        match msg {
-                            Some(Ok(Message::Binary(data))) => {
-                                if let Err(e) = Self::handle_message(&data, &sender).await {
-                                    log::error!("处理消息失败: {}", e);
+        // 消息接收循环
+        let mut authenticated = false;
+        let end = loop {
+            tokio::select! {
+                msg = ws_stream.next() => {
+                    match msg {
+                        Some(Ok(Message::Binary(data))) => {
+                            shared.record_frame(&data);
+                            match Self::handle_message(shared, &data, sender).await {
+                                Ok(Some(AuthReply::Success)) => {
+                                    authenticated = true;
+                                    shared.emit_event(ConnectionEvent::Authenticated {
+                                        timestamp: ConnectionEvent::now(),
+                                    });
                                 }
+                                Ok(Some(AuthReply::Failed(reason))) => {
+                                    shared.emit_event(ConnectionEvent::AuthFailed {
+                                        reason: reason.clone(),
+                                        timestamp: ConnectionEvent::now(),
+                                    });
+                                    break ConnectionEnd::AuthFailed(reason);
+                                }
+                                Ok(None) => {}
+                                Err(e) => log::error!("处理消息失败: {}", e),
                             }
-                            Some(Ok(Message::Close(_))) => {
-                                log::info!("WebSocket连接关闭");
-                                break;
-                            }
-                            Some(Err(e)) => {
-                                log::error!("WebSocket错误: {}", e);
-                                break;
-                            }
-                            None => {
-                                log::info!("WebSocket连接结束");
-                                break;
-                            }
-                            _ => {}
                         }
-                    }
-                    _ = cancel_rx_msg.recv() => {
-                        log::info!("消息接收任务已停止");
-                        break;
+                        Some(Ok(Message::Close(_))) => {
+                            log::info!("WebSocket连接关闭");
+                            break ConnectionEnd::Lost("服务器关闭了连接".to_string());
+                        }
+                        Some(Err(e)) => {
+                            log::error!("WebSocket错误: {}", e);
+                            break ConnectionEnd::Lost(e.to_string());
+                        }
+                        None => {
+                            log::info!("WebSocket连接结束");
+                            break ConnectionEnd::Lost("连接已结束".to_string());
+                        }
+                        _ => {}
                     }
                 }
+                reason = &mut heartbeat_task => {
+                    let reason = reason.unwrap_or_else(|e| format!("WebSocket心跳任务异常退出: {}", e));
+                    shared.emit_event(ConnectionEvent::HeartbeatLost {
+                        reason: reason.clone(),
+                        timestamp: ConnectionEvent::now(),
+                    });
+                    break ConnectionEnd::Lost(reason);
+                }
+                _ = &mut session_lost => {
+                    break ConnectionEnd::SessionExpired;
+                }
+                _ = shared.interaction_ended.notified() => {
+                    break ConnectionEnd::InteractionEnded;
+                }
+                _ = cancel_rx.recv() => {
+                    log::info!("消息接收任务已停止");
+                    break ConnectionEnd::Cancelled;
+                }
             }
-        });
+        };
 
-        Ok(())
+        heartbeat_task.abort();
+        log::info!("WebSocket心跳任务已停止");
+
+        // 主动关闭时由 close() 负责关闭写端
+        if !matches!(end, ConnectionEnd::Cancelled) {
+            shared.ws_sink.lock().await.take();
+        }
+
+        (end, authenticated)
     }
 
     async fn handle_message(
+        shared: &SharedState,
         data: &[u8],
-        sender: &mpsc::UnboundedSender<BilibiliMessage>,
-    ) -> Result<(), BilibiliError> {
+        sender: &MessageSender,
+    ) -> Result<Option<AuthReply>, BilibiliError> {
         log::debug!("收到原始数据，长度: {}", data.len());
 
-        let mut proto = Proto::new();
-        proto.unpack(data).map_err(|e| BilibiliError::from(e))?;
+        // 一帧中可能拼接了多个包，压缩包展开后也可能包含多条消息
+        let packets = ProtoDecoder::decode_all(data)?;
+        log::debug!("本帧共解析出 {} 个子包", packets.len());
+
+        let mut auth_reply = None;
+        for proto in packets {
+            match Self::handle_packet(shared, proto, sender).await {
+                Ok(Some(reply)) => auth_reply = Some(reply),
+                Ok(None) => {}
+                Err(e) => log::error!("处理子包失败: {}", e),
+            }
+        }
+
+        Ok(auth_reply)
+    }
 
+    async fn handle_packet(
+        shared: &SharedState,
+        proto: Proto,
+        sender: &MessageSender,
+    ) -> Result<Option<AuthReply>, BilibiliError> {
         log::debug!(
             "解析协议包: op={}, ver={}, len={}",
             proto.op,
@@ -346,27 +1170,30 @@ impl BilibiliClient {
 
         match proto.op {
             3 => {
-                // 心跳回复
-                log::debug!("收到心跳回复");
+                // 心跳回复，包体为人气值
+                match proto.get_body_string() {
+                    Ok(popularity) => log::debug!("收到心跳回复，人气值: {}", popularity),
+                    Err(_) => log::debug!("收到心跳回复"),
+                }
+                let previous = shared.heartbeat.ws_reply();
+                shared.heartbeat_succeeded(previous);
             }
             8 => {
                 // 认证回复
-                let body_str = proto
-                    .get_body_string()
-                    .map_err(|e| BilibiliError::from(e))?;
+                let body_str = proto.get_body_string()?;
                 log::info!("认证回复: {}", body_str);
                 let auth_resp: Value = serde_json::from_str(&body_str)?;
                 if auth_resp["code"].as_i64() == Some(0) {
                     log::info!("认证成功");
+                    return Ok(Some(AuthReply::Success));
                 } else {
                     log::error!("认证失败: {}", body_str);
+                    return Ok(Some(AuthReply::Failed(body_str)));
                 }
             }
             5 => {
                 // 业务消息
-                let body_str = proto
-                    .get_body_string()
-                    .map_err(|e| BilibiliError::from(e))?;
+                let body_str = proto.get_body_string()?;
                 log::info!("收到业务消息: {}", body_str);
 
                 // 首先尝试解析为通用JSON
@@ -380,42 +1207,47 @@ impl BilibiliClient {
                             log::info!("消息类型: {}", cmd);
                         }
 
-                        // 尝试解析为BilibiliMessage
-                        match serde_json::from_str::<BilibiliMessage>(&body_str) {
+                        // 尝试解析为BilibiliMessage，未知类型会落入 Unknown 分支
+                        match serde_json::from_value::<BilibiliMessage>(json_value) {
                             Ok(message) => {
-                                log::info!("成功解析为BilibiliMessage: {:?}", message);
+                                ParseDiagnostics::global().record_message(&message);
+                                if let BilibiliMessage::Unknown { cmd, data } = &message {
+                                    log::warn!(
+                                        "未知或无法解析的消息类型: cmd={}, data={}",
+                                        cmd,
+                                        data
+                                    );
+                                } else {
+                                    log::info!("成功解析为BilibiliMessage: {:?}", message);
+                                }
 
-                                // 检查是否是交互结束消息
-                                if matches!(&message, BilibiliMessage::InteractionEnd { .. }) {
+                                // 交互结束后游戏会话已失效，不再调用 /v2/app/end，也不再重连
+                                if let BilibiliMessage::InteractionEnd { data } = &message {
                                     log::info!("收到交互结束消息，连接即将断开");
+                                    let mut session = shared.session.lock().await;
+                                    if session.as_ref().is_some_and(|session| {
+                                        data.game_id.is_empty() || session.game_id == data.game_id
+                                    }) {
+                                        session.take();
+                                        shared.interaction_ended.notify_one();
+                                    }
+                                    drop(session);
+                                    shared.emit_event(ConnectionEvent::InteractionEnd {
+                                        game_id: data.game_id.clone(),
+                                        timestamp: ConnectionEvent::now(),
+                                    });
                                 }
 
-                                if sender.send(message).is_err() {
+                                // 按优先级入队，积压时点赞、进房和弹幕在这里被丢弃
+                                if !sender.send(message) {
                                     log::error!("发送消息到通道失败");
                                 } else {
                                     log::info!("消息发送到前端成功");
                                 }
                             }
                             Err(e) => {
-                                log::warn!("无法解析为BilibiliMessage: {}", e);
-
-                                // 尝试解析为通用消息
-                                match serde_json::from_str::<UnknownMessage>(&body_str) {
-                                    Ok(unknown_msg) => {
-                                        log::info!(
-                                            "解析为未知消息类型: cmd={}, data={}",
-                                            unknown_msg.cmd,
-                                            unknown_msg.data
-                                        );
-                                    }
-                                    Err(e2) => {
-                                        log::error!(
-                                            "完全无法解析消息: {}, 原始数据: {}",
-                                            e2,
-                                            body_str
-                                        );
-                                    }
-                                }
+                                // 连 cmd 字段都没有的消息无法转发
+                                log::error!("完全无法解析消息: {}, 原始数据: {}", e, body_str);
                             }
                         }
                     }
@@ -435,20 +1267,21 @@ impl BilibiliClient {
             }
         }
 
-        Ok(())
+        Ok(None)
     }
 
-    async fn get_websocket_info(&mut self) -> Result<(String, String), BilibiliError> {
-        let url = format!("{}/v2/app/start", self.config.host);
+    /// 调用 `/v2/app/start` 开启游戏会话，并保存到共享状态中
+    async fn start_game_session(shared: &SharedState) -> Result<GameSession, BilibiliError> {
+        let url = format!("{}/v2/app/start", shared.config.host);
         let request = AppStartRequest {
-            code: self.config.id_code.clone(),
-            app_id: self.config.app_id,
+            code: shared.config.id_code.clone(),
+            app_id: shared.config.app_id,
         };
 
         let body = serde_json::to_string(&request)?;
-        let headers = self.sign(&body)?;
+        let headers = Self::sign_static(&shared.config, &body)?;
 
-        let response = self
+        let response = shared
             .client
             .post(&url)
             .headers(headers)
@@ -460,18 +1293,40 @@ impl BilibiliClient {
         let response_data: AppStartResponse = serde_json::from_str(&response_text)?;
 
         if response_data.code != 0 {
-            return Err(BilibiliError::from(format!(
-                "获取WebSocket信息失败: {}",
-                response_data.message
-            )));
+            return Err(BilibiliError::api(
+                response_data.code,
+                format!("获取WebSocket信息失败: {}", response_data.message),
+            ));
         }
 
-        self.game_id = Some(response_data.data.game_info.game_id);
+        if response_data.data.is_null() {
+            return Err(BilibiliError::Protocol {
+                message: "获取WebSocket信息失败: 响应中缺少data".to_string(),
+            });
+        }
+        let data: AppStartData = serde_json::from_value(response_data.data)?;
+
+        if data.websocket_info.wss_link.is_empty() {
+            return Err(BilibiliError::Protocol {
+                message: "获取WebSocket信息失败: 没有可用的WebSocket地址".to_string(),
+            });
+        }
 
-        Ok((
-            response_data.data.websocket_info.wss_link[0].clone(),
-            response_data.data.websocket_info.auth_body,
-        ))
+        let session = GameSession {
+            game_id: data.game_info.game_id,
+            room_id: data.anchor_info.map(|anchor| anchor.room_id),
+            wss_links: data.websocket_info.wss_link,
+            auth_body: data.websocket_info.auth_body,
+        };
+        log::info!(
+            "游戏会话已开启: game_id={}, 可用地址数: {}",
+            session.game_id,
+            session.wss_links.len()
+        );
+        *shared.session.lock().await = Some(session.clone());
+        shared.heartbeat.reset_app();
+
+        Ok(session)
     }
 
     async fn send_app_heartbeat(
@@ -488,20 +1343,25 @@ impl BilibiliClient {
         let response_text = response.text().await?;
         let response_data: Value = serde_json::from_str(&response_text)?;
 
-        if response_data["code"].as_i64() != Some(0) {
-            return Err(BilibiliError::from(format!(
-                "应用心跳失败: {}",
-                response_text
-            )));
+        match response_data["code"].as_i64() {
+            Some(0) => {}
+            Some(code) => {
+                let message = response_data["message"].as_str().unwrap_or_default();
+                return Err(BilibiliError::api(
+                    code,
+                    format!("应用心跳失败: {}", message),
+                ));
+            }
+            None => {
+                return Err(BilibiliError::Protocol {
+                    message: format!("应用心跳响应格式错误: {}", response_text),
+                });
+            }
         }
 
         Ok(())
     }
 
-    fn sign(&self, params: &str) -> Result<reqwest::header::HeaderMap, BilibiliError> {
-        Self::sign_static(&self.config, params)
-    }
-
     fn sign_static(
         config: &BilibiliConfig,
         params: &str,
@@ -555,16 +1415,69 @@ impl BilibiliClient {
     }
 
     pub async fn close(&mut self) -> Result<(), BilibiliError> {
-        log::info!("=== 开始断开连接流程 ===");
+        self.handle().close().await
+    }
+
+    /// 断开连接并结束游戏会话，`reason` 作为最终 `Closed` 事件的原因
+    pub async fn close_with_reason(&mut self, reason: &str) -> Result<(), BilibiliError> {
+        self.handle().close_with_reason(reason).await
+    }
+}
+
+impl ClientHandle {
+    /// 当前游戏会话对应的主播直播间号，尚未开启会话时为 `None`
+    pub async fn room_id(&self) -> Option<i64> {
+        self.shared
+            .session
+            .lock()
+            .await
+            .as_ref()
+            .and_then(|session| session.room_id)
+    }
+
+    /// 消息管道计数，消费端创建消息管道时传入，便于随连接状态一起查询
+    pub fn pipeline_stats(&self) -> Arc<PipelineStats> {
+        self.shared.pipeline_stats.clone()
+    }
+
+    /// 心跳健康状态与延迟
+    pub fn heartbeat_stats(&self) -> HeartbeatStatsSnapshot {
+        self.shared.heartbeat.snapshot()
+    }
+
+    /// 最近一次的连接状态
+    pub fn connection_state(&self) -> ConnectionEvent {
+        match self.shared.state.lock() {
+            Ok(state) => state.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
 
-        // 首先停止心跳任务
-        if let Some(cancel_tx) = self.heartbeat_cancel_tx.take() {
+    pub async fn close(&self) -> Result<(), BilibiliError> {
+        self.close_with_reason("主动断开连接").await
+    }
+
+    /// 断开连接并结束游戏会话，`reason` 作为最终 `Closed` 事件的原因
+    pub async fn close_with_reason(&self, reason: &str) -> Result<(), BilibiliError> {
+        log::info!("=== 开始断开连接流程: {} ===", reason);
+
+        // 先广播关闭事件，事件源的消费端在消息通道关闭前就能收到最终状态
+        self.shared.emit_event(ConnectionEvent::closed(reason));
+
+        // 然后停止后台任务
+        let cancel_tx = self
+            .shared
+            .cancel
+            .lock()
+            .ok()
+            .and_then(|mut cancel| cancel.take());
+        if let Some(cancel_tx) = cancel_tx {
             log::info!("正在停止心跳任务...");
             let _ = cancel_tx.send(());
         }
 
         // 主动关闭WebSocket连接
-        if let Some(ws_sink) = self.ws_sink.take() {
+        if let Some(ws_sink) = self.shared.ws_sink.lock().await.take() {
             log::info!("正在关闭WebSocket连接...");
             let mut sink = ws_sink.lock().await;
             if let Err(e) = sink.close().await {
@@ -574,34 +1487,57 @@ impl BilibiliClient {
             }
         }
 
-        // 然后关闭应用连接
-        if let Some(game_id) = &self.game_id {
+        // 然后关闭应用连接，同时清理游戏会话
+        let session = self.shared.session.lock().await.take();
+        if let Some(session) = session {
             log::info!("正在关闭应用连接...");
-            let url = format!("{}/v2/app/end", self.config.host);
-            let request = AppEndRequest {
-                game_id: game_id.clone(),
-                app_id: self.config.app_id,
-            };
-
-            let body = serde_json::to_string(&request)?;
-            let headers = self.sign(&body)?;
-
-            let response = self
-                .client
-                .post(&url)
-                .headers(headers)
-                .body(body)
-                .send()
-                .await?;
+            BilibiliClient::end_game_session(&self.shared, session).await?;
+        }
 
-            let response_text = response.text().await?;
-            log::info!("关闭应用成功: {}", response_text);
+        if let Some(recorder) = &self.shared.recorder {
+            log::info!(
+                "会话录制完成: {:?}，共 {} 帧",
+                recorder.path(),
+                recorder.frames()
+            );
         }
 
-        // 清理game_id
-        self.game_id = None;
         log::info!("=== 断开连接流程完成 ===");
 
         Ok(())
     }
 }
+
+impl LiveEventSource for BilibiliClient {
+    async fn connect(&mut self) -> Result<(), BilibiliError> {
+        // 先订阅再连接，才能收到 `Connecting`
+        let events = self.subscribe_events();
+        let messages = BilibiliClient::connect(self).await?;
+        self.source = Some(SourceReceivers { messages, events });
+        Ok(())
+    }
+
+    async fn next_event(&mut self) -> Option<LiveEvent> {
+        let source = self.source.as_mut()?;
+        loop {
+            // 优先取连接事件，保证消息通道关闭前的 `Closed` 先于 `None` 送出
+            tokio::select! {
+                biased;
+                event = source.events.recv() => match event {
+                    Ok(event) => return Some(LiveEvent::Connection(event)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("连接状态事件积压，跳过 {} 条", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return source.messages.recv().await.map(LiveEvent::Message);
+                    }
+                },
+                message = source.messages.recv() => return message.map(LiveEvent::Message),
+            }
+        }
+    }
+
+    async fn close(&mut self) -> Result<(), BilibiliError> {
+        BilibiliClient::close(self).await
+    }
+}
@@ -0,0 +1,96 @@
+//! 可取消的流式对话
+//!
+//! 进行中的流式请求按 `stream_id` 登记取消信号，同一个 `stream_id` 只保留最新的请求，
+//! 新请求登记时旧请求被取消。请求结束或被丢弃后登记项随之移除。
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::oneshot;
+
+struct Entry {
+    /// 区分同一个 `stream_id` 先后登记的请求
+    token: u64,
+    cancel: oneshot::Sender<()>,
+}
+
+/// 请求结束或被丢弃时移除登记
+struct Registration<'a> {
+    registry: &'a ChatStreamRegistry,
+    stream_id: &'a str,
+    token: u64,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.registry.remove(self.stream_id, self.token);
+    }
+}
+
+/// 流式对话登记表
+#[derive(Default)]
+pub struct ChatStreamRegistry {
+    streams: Mutex<HashMap<String, Entry>>,
+    next_token: AtomicU64,
+}
+
+impl ChatStreamRegistry {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        match self.streams.lock() {
+            Ok(streams) => streams,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// 运行一个可取消的请求，被取消时返回 `None`
+    ///
+    /// 取消时 `future` 被直接丢弃，其中的连接随之关闭；返回的 future 被调用方丢弃时同样会移除登记。
+    pub async fn run<F: Future>(&self, stream_id: &str, future: F) -> Option<F::Output> {
+        let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+        let (cancel, cancelled) = oneshot::channel();
+        if let Some(previous) = self
+            .lock()
+            .insert(stream_id.to_string(), Entry { token, cancel })
+        {
+            // 同一个 stream_id 只保留最新的请求
+            let _ = previous.cancel.send(());
+        }
+
+        let _registration = Registration {
+            registry: self,
+            stream_id,
+            token,
+        };
+
+        tokio::select! {
+            output = future => Some(output),
+            _ = cancelled => {
+                log::info!("流式对话 {} 已取消", stream_id);
+                None
+            }
+        }
+    }
+
+    /// 取消进行中的请求，返回是否找到了对应的请求
+    pub fn cancel(&self, stream_id: &str) -> bool {
+        self.lock()
+            .remove(stream_id)
+            .is_some_and(|entry| entry.cancel.send(()).is_ok())
+    }
+
+    /// 是否有使用该 `stream_id` 的请求正在进行
+    pub fn is_running(&self, stream_id: &str) -> bool {
+        self.lock().contains_key(stream_id)
+    }
+
+    /// 移除登记项；已经被新请求替换时保留新请求的登记
+    fn remove(&self, stream_id: &str, token: u64) {
+        let mut streams = self.lock();
+        if streams
+            .get(stream_id)
+            .is_some_and(|entry| entry.token == token)
+        {
+            streams.remove(stream_id);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! 礼物连击聚合
+//!
+//! 按 `combo_id`（没有时按 open_id + gift_id）累计同一连击的礼物，连击超时后
+//! 输出一条汇总事件，便于对一次连击只答谢一次。
+
+use crate::core::{BilibiliMessage, GiftData};
+use crate::utils::sleep_until_optional;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+
+/// 连击聚合配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComboConfig {
+    pub enabled: bool,
+    /// 礼物没有携带 `combo_timeout` 时使用的超时毫秒数
+    pub default_timeout_ms: u64,
+}
+
+impl Default for ComboConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default_timeout_ms: 5000,
+        }
+    }
+}
+
+/// 一次连击结束后的汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComboFinished {
+    /// 聚合键：`combo_id`，或 `open_id:gift_id`
+    pub combo_key: String,
+    pub room_id: i64,
+    pub uid: i64,
+    pub open_id: String,
+    pub uname: String,
+    pub uface: String,
+    pub gift_id: i64,
+    pub gift_name: String,
+    /// 礼物总数
+    pub total_num: i64,
+    /// 总价值，单位与 `price` 相同（1/1000 元）
+    pub total_value: i64,
+    pub paid: bool,
+    /// 聚合到的礼物消息条数
+    pub events: u32,
+    pub first_timestamp: i64,
+    pub last_timestamp: i64,
+}
+
+struct PendingCombo {
+    summary: ComboFinished,
+    deadline: Instant,
+}
+
+/// 连击聚合器，只负责计数和超时判断，不涉及异步任务
+pub struct ComboAggregator {
+    config: ComboConfig,
+    pending: HashMap<String, PendingCombo>,
+}
+
+impl ComboAggregator {
+    pub fn new(config: ComboConfig) -> Self {
+        Self {
+            config,
+            pending: HashMap::new(),
+        }
+    }
+
+    fn combo_key(gift: &GiftData) -> String {
+        // 开放平台的 uid 恒为 0，只有 open_id 能区分观众
+        gift.combo_info
+            .as_ref()
+            .map(|combo| combo.combo_id.clone())
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| format!("{}:{}", gift.open_id, gift.gift_id))
+    }
+
+    fn timeout(&self, gift: &GiftData) -> Duration {
+        // combo_timeout 单位为秒
+        match gift.combo_info.as_ref().map(|combo| combo.combo_timeout) {
+            Some(secs) if secs > 0 => Duration::from_secs(secs as u64),
+            _ => Duration::from_millis(self.config.default_timeout_ms),
+        }
+    }
+
+    /// 累计一条礼物消息，每次收到同一连击的礼物都会重新计时
+    pub fn feed(&mut self, gift: &GiftData, now: Instant) {
+        let key = Self::combo_key(gift);
+        let deadline = now + self.timeout(gift);
+        let value = gift.price.saturating_mul(gift.gift_num);
+
+        match self.pending.get_mut(&key) {
+            Some(pending) => {
+                let summary = &mut pending.summary;
+                summary.total_num += gift.gift_num;
+                summary.total_value += value;
+                summary.paid |= gift.paid;
+                summary.events += 1;
+                summary.last_timestamp = gift.timestamp;
+                pending.deadline = deadline;
+            }
+            None => {
+                let summary = ComboFinished {
+                    combo_key: key.clone(),
+                    room_id: gift.room_id,
+                    uid: gift.uid,
+                    open_id: gift.open_id.clone(),
+                    uname: gift.uname.clone(),
+                    uface: gift.uface.clone(),
+                    gift_id: gift.gift_id,
+                    gift_name: gift.gift_name.clone(),
+                    total_num: gift.gift_num,
+                    total_value: value,
+                    paid: gift.paid,
+                    events: 1,
+                    first_timestamp: gift.timestamp,
+                    last_timestamp: gift.timestamp,
+                };
+                self.pending.insert(key, PendingCombo { summary, deadline });
+            }
+        }
+    }
+
+    /// 取出所有已超时的连击
+    pub fn expire(&mut self, now: Instant) -> Vec<ComboFinished> {
+        let expired: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        let mut finished: Vec<ComboFinished> = expired
+            .into_iter()
+            .filter_map(|key| self.pending.remove(&key))
+            .map(|pending| pending.summary)
+            .collect();
+        finished.sort_by_key(|summary| summary.last_timestamp);
+        finished
+    }
+
+    /// 取出所有未结束的连击，用于连接关闭时收尾
+    pub fn flush(&mut self) -> Vec<ComboFinished> {
+        let mut finished: Vec<ComboFinished> = self
+            .pending
+            .drain()
+            .map(|(_, pending)| pending.summary)
+            .collect();
+        finished.sort_by_key(|summary| summary.last_timestamp);
+        finished
+    }
+
+    /// 最早超时的时间点
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().map(|pending| pending.deadline).min()
+    }
+}
+
+/// 运行连击聚合任务：从通道接收礼物消息，连击结束时调用 `emit`
+///
+/// 通道关闭后输出所有未结束的连击再返回
+pub async fn run_combo_aggregator(
+    mut receiver: mpsc::UnboundedReceiver<BilibiliMessage>,
+    config: ComboConfig,
+    mut emit: impl FnMut(ComboFinished),
+) {
+    let mut aggregator = ComboAggregator::new(config);
+
+    loop {
+        let deadline = aggregator.next_deadline();
+        tokio::select! {
+            message = receiver.recv() => match message {
+                Some(BilibiliMessage::Gift { data }) => aggregator.feed(&data, Instant::now()),
+                Some(_) => {}
+                None => break,
+            },
+            _ = sleep_until_optional(deadline) => {
+                for summary in aggregator.expire(Instant::now()) {
+                    log::info!(
+                        "连击结束: {} 送出 {} 个 {}",
+                        summary.uname,
+                        summary.total_num,
+                        summary.gift_name
+                    );
+                    emit(summary);
+                }
+            }
+        }
+    }
+
+    for summary in aggregator.flush() {
+        emit(summary);
+    }
+}
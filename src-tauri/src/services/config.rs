@@ -0,0 +1,130 @@
+//! 应用配置
+//!
+//! `config.json` 的结构定义和加载，图形界面和命令行程序共用。
+
+use crate::services::bilibili::{BilibiliConfig, ReconnectPolicy};
+use crate::services::combo::ComboConfig;
+use crate::services::heartbeat::HeartbeatConfig;
+use crate::services::memory::MemoryConfig;
+use crate::services::openai::SamplingParams;
+use crate::services::persona::PersonaConfig;
+use crate::services::pipeline::PipelineConfig;
+use crate::services::recorder::ReplayConfig;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 未指定路径时依次查找的配置文件位置
+const CONFIG_SEARCH_PATHS: &[&str] = &[
+    "config.json",           // 项目根目录
+    "src-tauri/config.json", // Tauri 目录
+    "config/config.json",    // 配置目录
+    "../config.json",        // 上级目录
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAIConfig {
+    pub api_url: String,
+    pub api_key: String,
+    pub model: String,
+    /// 默认采样参数，与上面的字段写在同一层；其他未知字段会原样放进请求体
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
+}
+
+impl OpenAIConfig {
+    /// 用单次调用传入的采样参数覆盖配置中的默认值，结果在发送请求前校验
+    pub fn with_sampling(&self, overrides: Option<&SamplingParams>) -> OpenAIConfig {
+        let mut config = self.clone();
+        if let Some(overrides) = overrides {
+            config.sampling = self.sampling.merge(overrides);
+        }
+        config
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TtsConfig {
+    pub api_url: String,
+    pub model: String,
+    pub voice: String,
+    pub response_format: String,
+    pub speed: String,
+    pub authorization: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppConfig {
+    pub id_code: String,
+    pub app_id: u64,
+    pub access_key: String,
+    pub access_secret: String,
+    pub host: String,
+    pub openai: Option<OpenAIConfig>,
+    pub indextts: Option<TtsConfig>,
+    /// 录制原始WebSocket帧的文件路径
+    #[serde(default)]
+    pub record_path: Option<String>,
+    /// 从录制文件回放，设置后无需开放平台凭据
+    #[serde(default)]
+    pub replay: Option<ReplayConfig>,
+    /// 消息管道的容量、丢弃和打包策略
+    #[serde(default)]
+    pub pipeline: Option<PipelineConfig>,
+    /// 礼物连击聚合配置
+    #[serde(default)]
+    pub combo: Option<ComboConfig>,
+    /// 心跳间隔与失效判定
+    #[serde(default)]
+    pub heartbeat: Option<HeartbeatConfig>,
+    /// 人设与系统提示词
+    #[serde(default)]
+    pub persona: Option<PersonaConfig>,
+    /// 对话记忆
+    #[serde(default)]
+    pub memory: Option<MemoryConfig>,
+}
+
+impl AppConfig {
+    /// 生成连接开放平台所需的客户端配置
+    pub fn bilibili_config(&self) -> BilibiliConfig {
+        BilibiliConfig {
+            id_code: self.id_code.clone(),
+            app_id: self.app_id,
+            access_key: self.access_key.clone(),
+            access_secret: self.access_secret.clone(),
+            host: self.host.clone(),
+            reconnect: ReconnectPolicy::default(),
+            record_path: self.record_path.as_ref().map(PathBuf::from),
+            replay: self.replay.clone(),
+            pipeline: self.pipeline.clone().unwrap_or_default(),
+            heartbeat: self.heartbeat.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// 从指定路径加载配置
+pub fn load_app_config_from(path: &Path) -> Result<AppConfig, String> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        log::error!("读取配置文件失败: {}", e);
+        format!("读取配置文件失败: {}", e)
+    })?;
+    let config = serde_json::from_str::<AppConfig>(&content).map_err(|e| {
+        log::error!("解析配置文件失败: {}", e);
+        format!("解析配置文件失败: {}", e)
+    })?;
+    log::info!("成功加载配置文件");
+    Ok(config)
+}
+
+/// 在默认位置查找并加载配置，找不到时返回“未找到配置文件”
+pub fn load_app_config() -> Result<AppConfig, String> {
+    for config_path in CONFIG_SEARCH_PATHS.iter().map(Path::new) {
+        if config_path.exists() {
+            log::info!("找到配置文件: {:?}", config_path);
+            return load_app_config_from(config_path);
+        }
+    }
+
+    Err("未找到配置文件".to_string())
+}
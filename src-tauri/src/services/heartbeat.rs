@@ -0,0 +1,207 @@
+//! 心跳健康监测
+//!
+//! 记录 WebSocket 心跳（op 2 / op 3）和应用心跳（`/v2/app/heartbeat`）的结果与延迟，
+//! 根据连续未回复或连续失败的次数判定连接为正常、降级或失效。
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 心跳配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    /// WebSocket 心跳的发送间隔毫秒数
+    pub ws_interval_ms: u64,
+    /// 连续多少次 WebSocket 心跳没有收到回复后认为连接失效并重连
+    pub ws_max_missed: u32,
+    /// 应用心跳的发送间隔毫秒数
+    pub app_interval_ms: u64,
+    /// 应用心跳连续失败多少次后认为游戏会话失效
+    pub app_max_failures: u32,
+    /// 应用心跳失败后重试的间隔毫秒数
+    pub app_retry_delay_ms: u64,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            ws_interval_ms: 20_000,
+            ws_max_missed: 3,
+            app_interval_ms: 20_000,
+            app_max_failures: 3,
+            app_retry_delay_ms: 2_000,
+        }
+    }
+}
+
+impl HeartbeatConfig {
+    pub fn ws_interval(&self) -> Duration {
+        Duration::from_millis(self.ws_interval_ms.max(1))
+    }
+
+    pub fn app_interval(&self) -> Duration {
+        Duration::from_millis(self.app_interval_ms.max(1))
+    }
+
+    pub fn app_retry_delay(&self) -> Duration {
+        Duration::from_millis(self.app_retry_delay_ms)
+    }
+}
+
+/// 连接健康状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionHealth {
+    #[default]
+    Healthy,
+    /// 有心跳未回复或失败，但还没到判定失效的次数
+    Degraded,
+    /// 心跳判定连接或游戏会话已失效，即将重连
+    Dead,
+}
+
+/// 心跳统计快照，延迟单位为毫秒
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HeartbeatStatsSnapshot {
+    pub health: ConnectionHealth,
+    /// 连续未回复的 WebSocket 心跳数
+    pub ws_missed: u32,
+    pub ws_replies: u64,
+    pub ws_last_latency_ms: Option<u64>,
+    /// WebSocket 心跳延迟的指数滑动平均
+    pub ws_avg_latency_ms: Option<f64>,
+    /// 连续失败的应用心跳数
+    pub app_failures: u32,
+    pub app_successes: u64,
+    pub app_last_latency_ms: Option<u64>,
+    pub app_avg_latency_ms: Option<f64>,
+}
+
+#[derive(Default)]
+struct Inner {
+    ws_pending_since: Option<Instant>,
+    snapshot: HeartbeatStatsSnapshot,
+}
+
+/// 滑动平均中新样本的权重
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+fn smooth(average: Option<f64>, sample: u64) -> Option<f64> {
+    Some(match average {
+        Some(average) => average + (sample as f64 - average) * LATENCY_SMOOTHING,
+        None => sample as f64,
+    })
+}
+
+/// 一个客户端的心跳监测，由心跳任务和消息处理共同更新
+pub struct HeartbeatMonitor {
+    config: HeartbeatConfig,
+    inner: Mutex<Inner>,
+}
+
+impl HeartbeatMonitor {
+    pub fn new(config: HeartbeatConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    pub fn config(&self) -> &HeartbeatConfig {
+        &self.config
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn update_health(&self, inner: &mut Inner) -> ConnectionHealth {
+        let snapshot = &mut inner.snapshot;
+        snapshot.health = if snapshot.ws_missed >= self.config.ws_max_missed
+            || snapshot.app_failures >= self.config.app_max_failures
+        {
+            ConnectionHealth::Dead
+        } else if snapshot.ws_missed > 0 || snapshot.app_failures > 0 {
+            ConnectionHealth::Degraded
+        } else {
+            ConnectionHealth::Healthy
+        };
+        snapshot.health
+    }
+
+    /// 新的 WebSocket 连接建立时清空未回复计数
+    pub fn reset_ws(&self) {
+        let mut inner = self.lock();
+        inner.ws_pending_since = None;
+        inner.snapshot.ws_missed = 0;
+        self.update_health(&mut inner);
+    }
+
+    /// 即将发送一次 WebSocket 心跳，上一次心跳仍未回复时计为一次未回复
+    ///
+    /// 返回更新后的连续未回复次数
+    pub fn ws_sent(&self) -> u32 {
+        let mut inner = self.lock();
+        if inner.ws_pending_since.is_some() {
+            inner.snapshot.ws_missed += 1;
+        }
+        inner.ws_pending_since = Some(Instant::now());
+        self.update_health(&mut inner);
+        inner.snapshot.ws_missed
+    }
+
+    /// 收到 WebSocket 心跳回复，返回回复前的健康状态
+    pub fn ws_reply(&self) -> ConnectionHealth {
+        let mut inner = self.lock();
+        let previous = inner.snapshot.health;
+        if let Some(sent) = inner.ws_pending_since.take() {
+            let latency = sent.elapsed().as_millis() as u64;
+            inner.snapshot.ws_last_latency_ms = Some(latency);
+            inner.snapshot.ws_avg_latency_ms = smooth(inner.snapshot.ws_avg_latency_ms, latency);
+        }
+        inner.snapshot.ws_replies += 1;
+        inner.snapshot.ws_missed = 0;
+        self.update_health(&mut inner);
+        previous
+    }
+
+    /// 应用心跳成功，返回成功前的健康状态
+    pub fn app_success(&self, latency: Duration) -> ConnectionHealth {
+        let mut inner = self.lock();
+        let previous = inner.snapshot.health;
+        let latency = latency.as_millis() as u64;
+        inner.snapshot.app_last_latency_ms = Some(latency);
+        inner.snapshot.app_avg_latency_ms = smooth(inner.snapshot.app_avg_latency_ms, latency);
+        inner.snapshot.app_successes += 1;
+        inner.snapshot.app_failures = 0;
+        self.update_health(&mut inner);
+        previous
+    }
+
+    /// 应用心跳失败，返回更新后的连续失败次数
+    pub fn app_failure(&self) -> u32 {
+        let mut inner = self.lock();
+        inner.snapshot.app_failures += 1;
+        self.update_health(&mut inner);
+        inner.snapshot.app_failures
+    }
+
+    /// 游戏会话重新开启后清空应用心跳失败计数
+    pub fn reset_app(&self) {
+        let mut inner = self.lock();
+        inner.snapshot.app_failures = 0;
+        self.update_health(&mut inner);
+    }
+
+    pub fn health(&self) -> ConnectionHealth {
+        self.lock().snapshot.health
+    }
+
+    pub fn snapshot(&self) -> HeartbeatStatsSnapshot {
+        self.lock().snapshot.clone()
+    }
+}
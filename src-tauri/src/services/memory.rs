@@ -0,0 +1,344 @@
+//! 对话记忆
+//!
+//! 按会话（可选再按观众 `open_id`）保存最近的对话，发请求时作为历史消息带上。近期对话超出
+//! token 预算后，最早的几轮移出窗口，交给大模型合并进一段滚动摘要，摘要以系统消息的形式保留。
+//!
+//! token 数只是粗略估算：中日韩文字按每字 1 个，其他字符按每 4 个 1 个。
+
+use crate::services::config::OpenAIConfig;
+use crate::services::openai::{OpenAIMessage, chat_completion};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 最多同时保存的对话数，超出后丢弃最久没有更新的对话
+const MAX_CONVERSATIONS: usize = 1000;
+
+/// 对话记忆配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// 是否启用对话记忆
+    pub enabled: bool,
+    /// 按观众分别记忆，关闭时同一会话的所有观众共用一段记忆
+    pub per_viewer: bool,
+    /// 近期对话的 token 预算（估算值），超出后最早的对话移入摘要
+    pub max_tokens: usize,
+    /// 无论是否超出预算都保留的最近消息条数
+    pub min_messages: usize,
+    /// 是否把移出窗口的对话总结为摘要，关闭时直接丢弃
+    pub summarize: bool,
+    /// 摘要的字数上限
+    pub summary_max_chars: usize,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            per_viewer: false,
+            max_tokens: 1500,
+            min_messages: 2,
+            summarize: true,
+            summary_max_chars: 300,
+        }
+    }
+}
+
+/// 对话的标识，`open_id` 为空表示整个会话共用的对话
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ConversationKey {
+    pub session: String,
+    pub open_id: Option<String>,
+}
+
+/// 一条对话消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationTurn {
+    pub role: String,
+    pub content: String,
+    /// 毫秒时间戳
+    pub timestamp: i64,
+}
+
+/// 一段对话的快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSnapshot {
+    pub session: String,
+    pub open_id: Option<String>,
+    /// 更早对话的滚动摘要
+    pub summary: Option<String>,
+    /// 窗口内的近期对话，按时间升序
+    pub turns: Vec<ConversationTurn>,
+    /// 近期对话的估算 token 数
+    pub tokens: usize,
+    /// 已移出窗口、等待合并进摘要的消息条数
+    pub pending_summary: usize,
+    pub updated_at: i64,
+}
+
+#[derive(Default)]
+struct Conversation {
+    summary: Option<String>,
+    turns: VecDeque<ConversationTurn>,
+    tokens: usize,
+    /// 移出窗口但还没合并进摘要的消息
+    evicted: Vec<ConversationTurn>,
+    /// 进行中的摘要任务编号
+    summary_job: Option<u64>,
+    updated_at: i64,
+}
+
+/// 待合并进摘要的内容，由 `ConversationStore::take_for_summary` 取出
+pub struct SummaryJob {
+    pub key: ConversationKey,
+    pub previous: Option<String>,
+    pub turns: Vec<ConversationTurn>,
+    /// 任务编号，对话被重置后旧任务的编号不再匹配
+    id: u64,
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// 粗略估算文本的 token 数
+pub fn estimate_tokens(text: &str) -> usize {
+    let (cjk, other) = text.chars().fold((0usize, 0usize), |(cjk, other), c| {
+        if is_cjk(c) {
+            (cjk + 1, other)
+        } else {
+            (cjk, other + 1)
+        }
+    });
+    cjk + other.div_ceil(4)
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'   // 日文假名
+        | '\u{3400}'..='\u{4dbf}' // 扩展 A
+        | '\u{4e00}'..='\u{9fff}' // 基本汉字
+        | '\u{ac00}'..='\u{d7af}' // 韩文
+        | '\u{3000}'..='\u{303f}' // 中文标点
+        | '\u{ff00}'..='\u{ffef}' // 全角字符
+    )
+}
+
+impl Conversation {
+    fn snapshot(&self, key: &ConversationKey) -> ConversationSnapshot {
+        ConversationSnapshot {
+            session: key.session.clone(),
+            open_id: key.open_id.clone(),
+            summary: self.summary.clone(),
+            turns: self.turns.iter().cloned().collect(),
+            tokens: self.tokens,
+            pending_summary: self.evicted.len(),
+            updated_at: self.updated_at,
+        }
+    }
+
+    /// 超出预算时从最早的消息开始移出窗口
+    fn trim(&mut self, config: &MemoryConfig) {
+        while self.turns.len() > config.min_messages {
+            // 窗口不以回复开头，避免留下没有提问的回答
+            let orphan = self
+                .turns
+                .front()
+                .is_some_and(|turn| turn.role == "assistant");
+            if self.tokens <= config.max_tokens && !orphan {
+                break;
+            }
+            let Some(turn) = self.turns.pop_front() else {
+                break;
+            };
+            self.tokens -= estimate_tokens(&turn.content);
+            if config.summarize {
+                self.evicted.push(turn);
+            }
+        }
+    }
+}
+
+/// 所有会话的对话记忆
+#[derive(Default)]
+pub struct ConversationStore {
+    conversations: Mutex<HashMap<ConversationKey, Conversation>>,
+    next_job: AtomicU64,
+}
+
+impl ConversationStore {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<ConversationKey, Conversation>> {
+        match self.conversations.lock() {
+            Ok(conversations) => conversations,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// 请求时要带上的历史消息：摘要（如果有）加窗口内的近期对话
+    pub fn history(&self, key: &ConversationKey) -> Vec<OpenAIMessage> {
+        let conversations = self.lock();
+        let Some(conversation) = conversations.get(key) else {
+            return Vec::new();
+        };
+        let summary = conversation.summary.iter().map(|summary| OpenAIMessage {
+            role: "system".to_string(),
+            content: format!("之前的对话摘要：{}", summary),
+        });
+        let turns = conversation.turns.iter().map(|turn| OpenAIMessage {
+            role: turn.role.clone(),
+            content: turn.content.clone(),
+        });
+        summary.chain(turns).collect()
+    }
+
+    /// 记录一轮对话并按预算裁剪，返回是否有消息等待合并进摘要
+    pub fn append(
+        &self,
+        key: &ConversationKey,
+        user: String,
+        assistant: String,
+        config: &MemoryConfig,
+    ) -> bool {
+        let now = now_millis();
+        let mut conversations = self.lock();
+        if !conversations.contains_key(key)
+            && conversations.len() >= MAX_CONVERSATIONS
+            && let Some(oldest) = conversations
+                .iter()
+                .min_by_key(|(_, conversation)| conversation.updated_at)
+                .map(|(key, _)| key.clone())
+        {
+            conversations.remove(&oldest);
+        }
+
+        let conversation = conversations.entry(key.clone()).or_default();
+        for (role, content) in [("user", user), ("assistant", assistant)] {
+            conversation.tokens += estimate_tokens(&content);
+            conversation.turns.push_back(ConversationTurn {
+                role: role.to_string(),
+                content,
+                timestamp: now,
+            });
+        }
+        conversation.updated_at = now;
+        conversation.trim(config);
+        !conversation.evicted.is_empty()
+    }
+
+    /// 取出等待合并进摘要的消息，同一段对话同时只有一个摘要任务
+    pub fn take_for_summary(&self, key: &ConversationKey) -> Option<SummaryJob> {
+        let mut conversations = self.lock();
+        let conversation = conversations.get_mut(key)?;
+        if conversation.summary_job.is_some() || conversation.evicted.is_empty() {
+            return None;
+        }
+        let id = self.next_job.fetch_add(1, Ordering::Relaxed);
+        conversation.summary_job = Some(id);
+        Some(SummaryJob {
+            key: key.clone(),
+            previous: conversation.summary.clone(),
+            turns: std::mem::take(&mut conversation.evicted),
+            id,
+        })
+    }
+
+    /// 摘要任务结束；失败时把消息放回，下次再合并
+    pub fn finish_summary(&self, job: SummaryJob, result: Result<String, String>) {
+        let mut conversations = self.lock();
+        // 摘要期间对话可能已被重置，重置后新建的对话（包括它自己的摘要任务）不接收旧摘要
+        let Some(conversation) = conversations
+            .get_mut(&job.key)
+            .filter(|conversation| conversation.summary_job == Some(job.id))
+        else {
+            return;
+        };
+        conversation.summary_job = None;
+        match result {
+            Ok(summary) => conversation.summary = Some(summary),
+            Err(_) => {
+                let mut turns = job.turns;
+                turns.append(&mut conversation.evicted);
+                conversation.evicted = turns;
+            }
+        }
+    }
+
+    pub fn get(&self, key: &ConversationKey) -> Option<ConversationSnapshot> {
+        self.lock()
+            .get(key)
+            .map(|conversation| conversation.snapshot(key))
+    }
+
+    /// 列出对话，最近更新的在前；指定 `session` 时只列出该会话的对话
+    pub fn list(&self, session: Option<&str>) -> Vec<ConversationSnapshot> {
+        let mut snapshots: Vec<_> = self
+            .lock()
+            .iter()
+            .filter(|(key, _)| session.is_none_or(|session| key.session == session))
+            .map(|(key, conversation)| conversation.snapshot(key))
+            .collect();
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.updated_at));
+        snapshots
+    }
+
+    /// 清空对话，`open_id` 为空时清空该会话的全部对话（包括按观众区分的），返回清空的对话数
+    pub fn reset(&self, session: &str, open_id: Option<&str>) -> usize {
+        let mut conversations = self.lock();
+        let before = conversations.len();
+        conversations.retain(|key, _| {
+            key.session != session
+                || open_id.is_some_and(|open_id| key.open_id.as_deref() != Some(open_id))
+        });
+        before - conversations.len()
+    }
+}
+
+/// 调用大模型把移出窗口的对话合并进摘要
+///
+/// 配置中的长度上限和停止序列是为聊天回复设定的，会截断摘要，这里不发送，长度由提示词约束。
+pub async fn summarize(
+    client: &reqwest::Client,
+    config: &OpenAIConfig,
+    job: &SummaryJob,
+    max_chars: usize,
+) -> Result<String, String> {
+    let mut transcript = String::new();
+    if let Some(previous) = &job.previous {
+        transcript.push_str(&format!("已有摘要：{}\n\n新增对话：\n", previous));
+    }
+    for turn in &job.turns {
+        let speaker = if turn.role == "assistant" {
+            "主播"
+        } else {
+            "观众"
+        };
+        transcript.push_str(&format!("{}：{}\n", speaker, turn.content));
+    }
+
+    let messages = vec![
+        OpenAIMessage {
+            role: "system".to_string(),
+            content: format!(
+                "你负责整理直播间的对话记录。把已有摘要和新增对话合并成一段新的摘要，\
+                 保留观众的名字、提到的事实和约定，省略寒暄，不超过{}字，只输出摘要本身。",
+                max_chars
+            ),
+        },
+        OpenAIMessage {
+            role: "user".to_string(),
+            content: transcript,
+        },
+    ];
+    let mut config = config.clone();
+    config.sampling.max_tokens = None;
+    config.sampling.stop = None;
+    config.sampling.extra.remove("max_completion_tokens");
+    let summary = chat_completion(client, &config, messages).await?;
+    Ok(summary.trim().to_string())
+}
@@ -0,0 +1,451 @@
+//! 本地模拟的哔哩哔哩开放平台
+//!
+//! 实现 `/v2/app/start`、`/v2/app/heartbeat`、`/v2/app/end` 三个接口并校验签名，
+//! 同时提供按 `Proto` 格式通信的WebSocket，用于无网络环境下的开发和测试。
+
+use crate::core::{Proto, VER_HEARTBEAT, VER_NORMAL, VER_ZLIB};
+use crate::utils::sleep_until_optional;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use futures_util::{SinkExt, StreamExt};
+use hmac::{Hmac, Mac};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::{Mutex, oneshot};
+use tokio::time::Duration;
+use warp::http::HeaderMap;
+use warp::ws::{Message, WebSocket};
+use warp::{Filter, Reply};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 参与签名的请求头
+const SIGNED_HEADERS: [&str; 6] = [
+    "x-bili-accesskeyid",
+    "x-bili-content-md5",
+    "x-bili-signature-method",
+    "x-bili-signature-nonce",
+    "x-bili-signature-version",
+    "x-bili-timestamp",
+];
+
+/// 模拟服务配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockConfig {
+    pub id_code: String,
+    pub app_id: u64,
+    pub access_key: String,
+    pub access_secret: String,
+    /// 认证成功后依次推送的业务消息
+    #[serde(default = "MockScriptItem::sample")]
+    pub script: Vec<MockScriptItem>,
+    /// 每条WebSocket连接在建立多少毫秒后被服务端主动断开，用于测试重连
+    #[serde(default)]
+    pub close_after_ms: Option<u64>,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            id_code: "MOCK_ID_CODE".to_string(),
+            app_id: 1,
+            access_key: "mock_access_key".to_string(),
+            access_secret: "mock_access_secret".to_string(),
+            script: MockScriptItem::sample(),
+            close_after_ms: None,
+        }
+    }
+}
+
+/// 脚本中的一条业务消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockScriptItem {
+    /// 距离上一条消息（或认证成功）的等待毫秒数
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// 完整的业务消息，包含 cmd 和 data
+    pub message: Value,
+    /// 是否以 zlib 压缩包的形式发送
+    #[serde(default)]
+    pub compressed: bool,
+}
+
+impl MockScriptItem {
+    /// 默认脚本：一条弹幕、一个礼物和一条点赞
+    pub fn sample() -> Vec<Self> {
+        vec![
+            MockScriptItem {
+                delay_ms: 500,
+                message: json!({
+                    "cmd": "LIVE_OPEN_PLATFORM_DM",
+                    "data": {
+                        "room_id": 1, "uid": 0, "open_id": "mock_open_id_1",
+                        "uname": "测试观众", "uface": "", "timestamp": 0,
+                        "msg": "你好呀", "msg_id": "mock-dm-1", "guard_level": 0,
+                        "fans_medal_wearing_status": false, "fans_medal_name": "",
+                        "fans_medal_level": 0, "dm_type": 0
+                    }
+                }),
+                compressed: false,
+            },
+            MockScriptItem {
+                delay_ms: 1000,
+                message: json!({
+                    "cmd": "LIVE_OPEN_PLATFORM_SEND_GIFT",
+                    "data": {
+                        "room_id": 1, "uid": 0, "open_id": "mock_open_id_2",
+                        "uname": "送礼观众", "uface": "", "gift_id": 31036,
+                        "gift_name": "小花花", "gift_num": 1, "price": 100,
+                        "r_price": 100, "paid": true, "fans_medal_level": 0,
+                        "fans_medal_name": "", "fans_medal_wearing_status": false,
+                        "guard_level": 0, "timestamp": 0, "msg_id": "mock-gift-1",
+                        "anchor_info": {
+                            "uid": 0, "open_id": "mock_anchor", "uname": "主播", "uface": ""
+                        }
+                    }
+                }),
+                compressed: true,
+            },
+            MockScriptItem {
+                delay_ms: 1000,
+                message: json!({
+                    "cmd": "LIVE_OPEN_PLATFORM_LIKE",
+                    "data": {
+                        "room_id": 1, "uid": 0, "open_id": "mock_open_id_1",
+                        "uname": "测试观众", "uface": "", "timestamp": 0,
+                        "like_text": "为主播点赞了", "like_count": 3,
+                        "fans_medal_level": 0, "fans_medal_name": "",
+                        "fans_medal_wearing_status": false, "msg_id": "mock-like-1"
+                    }
+                }),
+                compressed: false,
+            },
+        ]
+    }
+}
+
+/// 模拟服务的运行状态
+struct MockContext {
+    config: MockConfig,
+    game_id: Mutex<Option<String>>,
+    games_started: AtomicU64,
+}
+
+impl MockContext {
+    fn auth_body(&self, game_id: &str) -> String {
+        json!({ "game_id": game_id, "key": "mock_key" }).to_string()
+    }
+
+    /// 按配置计算主动断开WebSocket的时间点
+    fn close_deadline(&self) -> Option<tokio::time::Instant> {
+        self.config
+            .close_after_ms
+            .map(|ms| tokio::time::Instant::now() + Duration::from_millis(ms))
+    }
+}
+
+pub struct MockServer {
+    shutdown_sender: Option<oneshot::Sender<()>>,
+    addr: Option<SocketAddr>,
+    context: Option<Arc<MockContext>>,
+}
+
+impl MockServer {
+    pub fn new() -> Self {
+        Self {
+            shutdown_sender: None,
+            addr: None,
+            context: None,
+        }
+    }
+
+    /// 启动模拟服务，端口为 0 时由系统分配，返回实际监听的地址
+    pub async fn start(
+        &mut self,
+        port: u16,
+        config: MockConfig,
+    ) -> Result<SocketAddr, Box<dyn std::error::Error + Send + Sync>> {
+        let context = Arc::new(MockContext {
+            config,
+            game_id: Mutex::new(None),
+            games_started: AtomicU64::new(0),
+        });
+        self.context = Some(context.clone());
+        let with_context = warp::any().map(move || context.clone());
+
+        let app_route = warp::post()
+            .and(warp::path!("v2" / "app" / String))
+            .and(warp::header::headers_cloned())
+            .and(warp::body::bytes())
+            .and(with_context.clone())
+            .and_then(handle_app_api);
+
+        let ws_route = warp::path("sub").and(warp::ws()).and(with_context).map(
+            |ws: warp::ws::Ws, context: Arc<MockContext>| {
+                ws.on_upgrade(move |socket| handle_socket(socket, context))
+            },
+        );
+
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel();
+        let (addr, server) = warp::serve(app_route.or(ws_route)).try_bind_with_graceful_shutdown(
+            ([127, 0, 0, 1], port),
+            async {
+                shutdown_receiver.await.ok();
+            },
+        )?;
+        tokio::spawn(server);
+
+        info!("模拟开放平台启动在: http://{}", addr);
+        self.shutdown_sender = Some(shutdown_sender);
+        self.addr = Some(addr);
+        Ok(addr)
+    }
+
+    /// 可以填入 `BilibiliConfig.host` 的地址
+    pub fn host(&self) -> Option<String> {
+        self.addr.map(|addr| format!("http://{}", addr))
+    }
+
+    /// 进行中的游戏，调用 `/v2/app/end` 之后为 `None`
+    pub async fn active_game(&self) -> Option<String> {
+        match &self.context {
+            Some(context) => context.game_id.lock().await.clone(),
+            None => None,
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(sender) = self.shutdown_sender.take() {
+            let _ = sender.send(());
+            info!("模拟开放平台已停止");
+        }
+    }
+}
+
+impl Default for MockServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 校验 `sign_static` 生成的签名头，返回错误码和原因
+fn verify_signature(
+    config: &MockConfig,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), (i64, String)> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .ok_or((4000, format!("缺少请求头: {}", name)))
+    };
+
+    if header("x-bili-accesskeyid")? != config.access_key {
+        return Err((4001, "accesskeyid 不匹配".to_string()));
+    }
+    if header("x-bili-content-md5")? != format!("{:x}", md5::compute(body)) {
+        return Err((4002, "content-md5 不匹配".to_string()));
+    }
+
+    let mut sign_str = Vec::new();
+    for name in SIGNED_HEADERS {
+        sign_str.push(format!("{}:{}", name, header(name)?));
+    }
+
+    let mut mac = HmacSha256::new_from_slice(config.access_secret.as_bytes())
+        .map_err(|e| (5002, e.to_string()))?;
+    mac.update(sign_str.join("\n").as_bytes());
+    let expected = hex::encode(mac.finalize().into_bytes());
+
+    if header("Authorization")? != expected {
+        return Err((4002, "签名错误".to_string()));
+    }
+    Ok(())
+}
+
+fn api_reply(code: i64, message: &str, data: Value) -> warp::reply::Json {
+    warp::reply::json(&json!({
+        "code": code,
+        "message": message,
+        "request_id": "mock",
+        "data": data,
+    }))
+}
+
+async fn handle_app_api(
+    action: String,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+    context: Arc<MockContext>,
+) -> Result<Box<dyn Reply>, warp::Rejection> {
+    if let Err((code, message)) = verify_signature(&context.config, &headers, &body) {
+        warn!("模拟开放平台签名校验失败: {}", message);
+        return Ok(Box::new(api_reply(code, &message, json!({}))));
+    }
+
+    let request: Value = serde_json::from_slice(&body).unwrap_or_default();
+    let reply = match action.as_str() {
+        "start" => {
+            if request["code"].as_str() != Some(context.config.id_code.as_str()) {
+                api_reply(7007, "身份码错误", json!({}))
+            } else if request["app_id"].as_u64() != Some(context.config.app_id) {
+                api_reply(4001, "应用无效", json!({}))
+            } else {
+                let n = context.games_started.fetch_add(1, Ordering::Relaxed) + 1;
+                let game_id = format!("mock-game-{}", n);
+                // WebSocket地址沿用请求中的Host，端口由系统分配时也能连上
+                let host = headers
+                    .get("host")
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("127.0.0.1");
+                *context.game_id.lock().await = Some(game_id.clone());
+                info!("模拟开放平台开启游戏: {}", game_id);
+                api_reply(
+                    0,
+                    "0",
+                    json!({
+                        "game_info": { "game_id": game_id },
+                        "websocket_info": {
+                            "auth_body": context.auth_body(&game_id),
+                            "wss_link": [format!("ws://{}/sub", host)],
+                        },
+                        "anchor_info": {
+                            "room_id": 1, "uname": "主播", "uface": "", "uid": 0,
+                            "open_id": "mock_anchor"
+                        }
+                    }),
+                )
+            }
+        }
+        "heartbeat" | "end" => {
+            let mut game_id = context.game_id.lock().await;
+            if game_id.is_none() || request["game_id"].as_str() != game_id.as_deref() {
+                api_reply(7003, "心跳过期或GameId错误", json!({}))
+            } else {
+                if action == "end" {
+                    // 不能放在日志宏里，未启用日志时宏参数不会求值
+                    let ended = game_id.take();
+                    info!("模拟开放平台结束游戏: {:?}", ended);
+                }
+                api_reply(0, "0", json!({}))
+            }
+        }
+        _ => api_reply(4000, "未知接口", json!({})),
+    };
+
+    Ok(Box::new(reply))
+}
+
+fn pack(ver: u16, op: u32, body: Vec<u8>) -> Vec<u8> {
+    let mut proto = Proto::new();
+    proto.ver = ver;
+    proto.op = op;
+    proto.body = body;
+    proto.pack()
+}
+
+/// 按脚本生成一条 op 5 消息，需要时包装为 zlib 压缩包
+fn pack_script_item(item: &MockScriptItem) -> Vec<u8> {
+    let packet = pack(VER_NORMAL, 5, item.message.to_string().into_bytes());
+    if !item.compressed {
+        return packet;
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    match encoder.write_all(&packet).and_then(|_| encoder.finish()) {
+        Ok(compressed) => pack(VER_ZLIB, 5, compressed),
+        Err(e) => {
+            error!("压缩模拟消息失败: {}", e);
+            packet
+        }
+    }
+}
+
+async fn handle_socket(socket: WebSocket, context: Arc<MockContext>) {
+    let (mut sink, mut stream) = socket.split();
+    let (out_tx, mut out_rx) = tokio::sync::mpsc::unbounded_channel::<Vec<u8>>();
+
+    // 写任务：所有待发送的包都经过这里
+    let writer = tokio::spawn(async move {
+        while let Some(packet) = out_rx.recv().await {
+            if sink.send(Message::binary(packet)).await.is_err() {
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    });
+
+    let close_after = context.close_deadline();
+    let mut script_task = None;
+
+    loop {
+        let frame = tokio::select! {
+            frame = stream.next() => frame,
+            _ = sleep_until_optional(close_after) => {
+                info!("模拟开放平台按配置断开WebSocket连接");
+                break;
+            }
+        };
+
+        let data = match frame {
+            Some(Ok(msg)) if msg.is_binary() => msg.into_bytes(),
+            Some(Ok(msg)) if msg.is_close() => break,
+            Some(Ok(_)) => continue,
+            Some(Err(_)) | None => break,
+        };
+
+        let mut proto = Proto::new();
+        if proto.unpack(&data).is_err() {
+            warn!("模拟开放平台收到无法解析的包");
+            continue;
+        }
+
+        match proto.op {
+            // 认证
+            7 => {
+                let game_id = context.game_id.lock().await.clone();
+                let valid = game_id
+                    .as_deref()
+                    .is_some_and(|id| proto.body == context.auth_body(id).into_bytes());
+                let code = if valid { 0 } else { -101 };
+                let _ = out_tx.send(pack(
+                    VER_NORMAL,
+                    8,
+                    json!({ "code": code }).to_string().into_bytes(),
+                ));
+
+                if valid && script_task.is_none() {
+                    let out_tx = out_tx.clone();
+                    let script = context.config.script.clone();
+                    script_task = Some(tokio::spawn(async move {
+                        for item in script {
+                            tokio::time::sleep(Duration::from_millis(item.delay_ms)).await;
+                            if out_tx.send(pack_script_item(&item)).is_err() {
+                                break;
+                            }
+                        }
+                    }));
+                }
+            }
+            // 心跳，回复人气值
+            2 => {
+                let _ = out_tx.send(pack(VER_HEARTBEAT, 3, 1u32.to_be_bytes().to_vec()));
+            }
+            op => warn!("模拟开放平台收到未知操作码: {}", op),
+        }
+    }
+
+    if let Some(task) = script_task {
+        task.abort();
+    }
+    drop(out_tx);
+    let _ = writer.await;
+}
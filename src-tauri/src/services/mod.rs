@@ -2,10 +2,26 @@
 //!
 //! 包含各种业务服务，如Bilibili直播、代理服务、TTS、OpenAI等
 
+pub mod archive;
 pub mod bilibili;
+pub mod chat_stream;
+pub mod combo;
+pub mod config;
+pub mod heartbeat;
+pub mod memory;
+pub mod mock;
 pub mod openai;
+pub mod persona;
+pub mod pipeline;
 pub mod proxy;
+pub mod recorder;
+pub mod sentence;
+pub mod session;
+pub mod source;
+pub mod stats;
+pub mod superchat;
 pub mod tts;
+pub mod viewer;
 
 // 重新导出服务模块中的公开函数和类型
 pub use openai::*;
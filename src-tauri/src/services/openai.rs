@@ -1,4 +1,87 @@
+use crate::services::config::OpenAIConfig;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// 由请求本身决定、不允许通过额外字段覆盖的参数
+const RESERVED_FIELDS: [&str; 3] = ["model", "messages", "stream"];
+
+/// 采样参数，未设置的项不发送，由服务端使用默认值
+///
+/// 未收录的字段（例如各家服务商特有的参数）保存在 `extra` 中，原样放进请求体。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SamplingParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    /// 停止序列，最多 4 个
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl SamplingParams {
+    /// 用 `overrides` 中设置了的项覆盖当前参数
+    pub fn merge(&self, overrides: &SamplingParams) -> SamplingParams {
+        let mut extra = self.extra.clone();
+        extra.extend(overrides.extra.clone());
+        SamplingParams {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            max_tokens: overrides.max_tokens.or(self.max_tokens),
+            presence_penalty: overrides.presence_penalty.or(self.presence_penalty),
+            frequency_penalty: overrides.frequency_penalty.or(self.frequency_penalty),
+            stop: overrides.stop.clone().or_else(|| self.stop.clone()),
+            seed: overrides.seed.or(self.seed),
+            extra,
+        }
+    }
+
+    /// 检查参数是否在接口允许的范围内
+    pub fn validate(&self) -> Result<(), String> {
+        fn check(name: &str, value: Option<f32>, min: f32, max: f32) -> Result<(), String> {
+            match value {
+                Some(value) if !(min..=max).contains(&value) => Err(format!(
+                    "采样参数 {} 超出范围: {}，应在 {} 到 {} 之间",
+                    name, value, min, max
+                )),
+                _ => Ok(()),
+            }
+        }
+
+        check("temperature", self.temperature, 0.0, 2.0)?;
+        check("top_p", self.top_p, 0.0, 1.0)?;
+        check("presence_penalty", self.presence_penalty, -2.0, 2.0)?;
+        check("frequency_penalty", self.frequency_penalty, -2.0, 2.0)?;
+        if self.max_tokens == Some(0) {
+            return Err("采样参数 max_tokens 必须大于 0".to_string());
+        }
+        if let Some(stop) = &self.stop {
+            if stop.len() > 4 {
+                return Err(format!("停止序列最多 4 个，当前 {} 个", stop.len()));
+            }
+            if stop.iter().any(|sequence| sequence.is_empty()) {
+                return Err("停止序列不能为空字符串".to_string());
+            }
+        }
+        if let Some(field) = RESERVED_FIELDS
+            .iter()
+            .find(|field| self.extra.contains_key(**field))
+        {
+            return Err(format!("额外参数不能包含 {}", field));
+        }
+        Ok(())
+    }
+}
 
 // OpenAI API 相关数据结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,7 +94,11 @@ pub struct OpenAIMessage {
 pub struct OpenAIRequest {
     pub model: String,
     pub messages: Vec<OpenAIMessage>,
-    // pub temperature: f32,
+    /// 以 SSE 流式返回，默认关闭时不发送该字段
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,85 +125,219 @@ pub struct OpenAIResponse {
     pub usage: OpenAIUsage,
 }
 
-#[derive(Debug, Serialize)]
-pub struct ChatResponse {
-    pub success: bool,
-    pub message: String,
+/// 流式响应中的增量内容
+#[derive(Debug, Default, Deserialize)]
+pub struct OpenAIDelta {
+    #[serde(default)]
     pub content: Option<String>,
 }
 
-#[tauri::command]
-pub async fn chat_with_openai(
-    message: String,
-    // temperature: Option<f32>,
-) -> Result<ChatResponse, String> {
-    // 从配置文件读取OpenAI配置
-    let openai_config = match crate::api::config::load_openai_config().await {
-        Ok(config) => config,
-        Err(e) => {
-            log::error!("加载OpenAI配置失败: {}", e);
-            return Err(format!("加载OpenAI配置失败: {}", e));
-        }
-    };
+#[derive(Debug, Deserialize)]
+pub struct OpenAIStreamChoice {
+    #[serde(default)]
+    pub delta: OpenAIDelta,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
 
-    // let temperature = temperature.unwrap_or(0.7);
+/// 流式响应的一个数据块（`chat.completion.chunk`）
+#[derive(Debug, Deserialize)]
+pub struct OpenAIStreamChunk {
+    #[serde(default)]
+    pub choices: Vec<OpenAIStreamChoice>,
+}
 
+/// 调用聊天补全接口，返回第一条回复的内容
+pub async fn chat_completion(
+    client: &reqwest::Client,
+    config: &OpenAIConfig,
+    messages: Vec<OpenAIMessage>,
+) -> Result<String, String> {
+    config.sampling.validate()?;
     let openai_request = OpenAIRequest {
-        model: openai_config.model.clone(),
-        messages: vec![OpenAIMessage {
-            role: "user".to_string(),
-            content: message,
-        }],
-        // temperature,
+        model: config.model.clone(),
+        messages,
+        stream: false,
+        sampling: config.sampling.clone(),
     };
 
-    // 创建HTTP客户端
-    let client = reqwest::Client::new();
-
     log::info!("发送OpenAI API请求: {:?}", openai_request);
 
-    match client
-        .post(&openai_config.api_url)
+    let response = client
+        .post(&config.api_url)
         .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", openai_config.api_key))
+        .header("Authorization", format!("Bearer {}", config.api_key))
         .json(&openai_request)
         .send()
         .await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.json::<OpenAIResponse>().await {
-                    Ok(openai_response) => {
-                        log::info!("OpenAI API请求成功");
-
-                        if let Some(choice) = openai_response.choices.first() {
-                            Ok(ChatResponse {
-                                success: true,
-                                message: "对话成功".to_string(),
-                                content: Some(choice.message.content.clone()),
-                            })
-                        } else {
-                            Err("OpenAI API返回空响应".to_string())
-                        }
-                    }
-                    Err(e) => {
-                        log::error!("解析OpenAI API响应失败: {}", e);
-                        Err(format!("解析OpenAI API响应失败: {}", e))
-                    }
+        .map_err(|e| {
+            log::error!("发送OpenAI API请求失败: {}", e);
+            format!("发送OpenAI API请求失败: {}", e)
+        })?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "未知错误".to_string());
+        log::error!("OpenAI API请求失败: {} - {}", status, error_text);
+        return Err(format!("OpenAI API请求失败: {} - {}", status, error_text));
+    }
+
+    let openai_response = response.json::<OpenAIResponse>().await.map_err(|e| {
+        log::error!("解析OpenAI API响应失败: {}", e);
+        format!("解析OpenAI API响应失败: {}", e)
+    })?;
+    log::info!("OpenAI API请求成功");
+
+    openai_response
+        .choices
+        .into_iter()
+        .next()
+        .map(|choice| choice.message.content)
+        .ok_or_else(|| "OpenAI API返回空响应".to_string())
+}
+
+/// SSE 解码器，把任意切分的字节块还原为完整事件的 `data` 内容
+#[derive(Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    data: Option<String>,
+}
+
+impl SseDecoder {
+    /// 输入一段字节，返回其中已经完整的事件数据
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            // 空行表示一个事件结束
+            if line.is_empty() {
+                events.extend(self.data.take());
+                continue;
+            }
+            // 注释行和 event、id 等其他字段不需要处理
+            let Some(value) = line.strip_prefix("data:") else {
+                continue;
+            };
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match &mut self.data {
+                // 同一事件的多行 data 用换行连接
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
                 }
-            } else {
-                let status = response.status();
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "未知错误".to_string());
-                log::error!("OpenAI API请求失败: {} - {}", status, error_text);
-                Err(format!("OpenAI API请求失败: {} - {}", status, error_text))
+                None => self.data = Some(value.to_string()),
             }
         }
-        Err(e) => {
-            log::error!("发送OpenAI API请求失败: {}", e);
-            Err(format!("发送OpenAI API请求失败: {}", e))
+        events
+    }
+
+    /// 流结束时取出最后一个没有以空行结尾的事件
+    pub fn finish(&mut self) -> Option<String> {
+        if !self.buffer.is_empty() {
+            let rest = std::mem::take(&mut self.buffer);
+            self.push(&rest);
+            self.push(b"\n");
         }
+        self.data.take()
+    }
+}
+
+/// 以流式方式调用聊天补全接口
+///
+/// 每收到一段增量内容就调用一次 `on_delta`，返回拼接后的完整回复。
+/// 丢弃返回的 future 即可取消，连接会随之关闭。
+pub async fn chat_completion_stream(
+    client: &reqwest::Client,
+    config: &OpenAIConfig,
+    messages: Vec<OpenAIMessage>,
+    mut on_delta: impl FnMut(&str) + Send,
+) -> Result<String, String> {
+    config.sampling.validate()?;
+    let openai_request = OpenAIRequest {
+        model: config.model.clone(),
+        messages,
+        stream: true,
+        sampling: config.sampling.clone(),
+    };
+
+    log::info!("发送OpenAI流式请求: {:?}", openai_request);
+
+    let mut response = client
+        .post(&config.api_url)
+        .header("Content-Type", "application/json")
+        .header("Accept", "text/event-stream")
+        .header("Authorization", format!("Bearer {}", config.api_key))
+        .json(&openai_request)
+        .send()
+        .await
+        .map_err(|e| {
+            log::error!("发送OpenAI流式请求失败: {}", e);
+            format!("发送OpenAI流式请求失败: {}", e)
+        })?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "未知错误".to_string());
+        log::error!("OpenAI API请求失败: {} - {}", status, error_text);
+        return Err(format!("OpenAI API请求失败: {} - {}", status, error_text));
+    }
+
+    let mut decoder = SseDecoder::default();
+    let mut content = String::new();
+    let mut done = false;
+    let mut handle_event = |data: String, content: &mut String| -> Result<bool, String> {
+        if data.trim() == "[DONE]" {
+            return Ok(true);
+        }
+        let chunk = serde_json::from_str::<OpenAIStreamChunk>(&data).map_err(|e| {
+            log::error!("解析OpenAI流式数据失败: {} - {}", e, data);
+            format!("解析OpenAI流式数据失败: {}", e)
+        })?;
+        for choice in chunk.choices {
+            if let Some(delta) = choice.delta.content.filter(|delta| !delta.is_empty()) {
+                on_delta(&delta);
+                content.push_str(&delta);
+            }
+        }
+        Ok(false)
+    };
+
+    while !done {
+        let chunk = response.chunk().await.map_err(|e| {
+            log::error!("读取OpenAI流式响应失败: {}", e);
+            format!("读取OpenAI流式响应失败: {}", e)
+        })?;
+        let events = match chunk {
+            Some(bytes) => decoder.push(&bytes),
+            None => {
+                // 服务端没有发送 [DONE] 就关闭了连接
+                if let Some(data) = decoder.finish() {
+                    handle_event(data, &mut content)?;
+                }
+                break;
+            }
+        };
+        for data in events {
+            if handle_event(data, &mut content)? {
+                done = true;
+                break;
+            }
+        }
+    }
+
+    log::info!("OpenAI流式请求完成，共 {} 个字符", content.chars().count());
+    if content.is_empty() {
+        return Err("OpenAI API返回空响应".to_string());
     }
+    Ok(content)
 }
@@ -0,0 +1,195 @@
+//! 角色设定与提示词
+//!
+//! `PersonaConfig` 描述虚拟主播的人设，`PromptBuilder` 把人设和直播间上下文（主播名、直播标题、
+//! 时段、当前观众）拼成系统提示词，和观众的消息一起组成发给大模型的消息列表。
+
+use crate::services::openai::OpenAIMessage;
+use crate::services::viewer::ViewerProfile;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 默认的系统提示词模板
+const DEFAULT_SYSTEM_PROMPT: &str = "你是{name}，正在{streamer}的直播间和观众聊天的虚拟主播。\
+现在是{time_of_day}，本场直播的标题是「{title}」，分区是{area}。\
+请用简短、口语化的中文回复观众，每次不超过三句话。";
+
+/// 模板变量没有取值时的替代文字
+const UNKNOWN: &str = "未知";
+
+/// 人设配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersonaConfig {
+    /// 角色名
+    pub name: String,
+    /// 主播名
+    pub streamer_name: String,
+    /// 系统提示词模板，支持 {name} {streamer} {title} {area} {time_of_day} {viewer} 变量
+    pub system_prompt: String,
+    /// 说话风格，例如“活泼，喜欢用颜文字”
+    pub speaking_style: String,
+    /// 不允许谈论的话题
+    pub banned_topics: Vec<String>,
+    /// 计算时段用的时区，相对 UTC 的小时数
+    pub utc_offset_hours: i32,
+}
+
+impl Default for PersonaConfig {
+    fn default() -> Self {
+        Self {
+            name: "AI主播".to_string(),
+            streamer_name: "主播".to_string(),
+            system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+            speaking_style: String::new(),
+            banned_topics: Vec::new(),
+            utc_offset_hours: 8,
+        }
+    }
+}
+
+/// 生成提示词时的直播间上下文，缺少的项在模板中显示为“未知”
+#[derive(Debug, Clone, Default)]
+pub struct PromptContext {
+    /// 当前直播标题，来自开播消息
+    pub stream_title: Option<String>,
+    /// 当前直播分区
+    pub area_name: Option<String>,
+    /// 发言观众的昵称
+    pub viewer_name: Option<String>,
+    /// 发言观众的档案描述，见 `ViewerProfile::prompt_summary`
+    pub viewer_summary: Option<String>,
+}
+
+impl PromptContext {
+    /// 填入观众档案
+    pub fn with_viewer(mut self, profile: &ViewerProfile) -> Self {
+        self.viewer_name = Some(profile.uname.clone());
+        self.viewer_summary = Some(profile.prompt_summary());
+        self
+    }
+}
+
+/// 根据 Unix 秒级时间戳和时区计算时段
+pub fn time_of_day(timestamp: i64, utc_offset_hours: i32) -> &'static str {
+    let hour = (timestamp + i64::from(utc_offset_hours) * 3600).rem_euclid(86_400) / 3600;
+    match hour {
+        0..=4 => "凌晨",
+        5..=7 => "早上",
+        8..=10 => "上午",
+        11..=12 => "中午",
+        13..=16 => "下午",
+        17..=18 => "傍晚",
+        19..=22 => "晚上",
+        _ => "深夜",
+    }
+}
+
+/// 一次扫描替换模板变量，替换进来的内容（例如含有 `{title}` 的直播标题）不会再被展开
+fn substitute(template: &str, variables: &[(&str, String)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match variables.iter().find(|(key, _)| rest.starts_with(key)) {
+            Some((key, value)) => {
+                result.push_str(value);
+                rest = &rest[key.len()..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// 提示词生成器
+#[derive(Debug, Clone, Default)]
+pub struct PromptBuilder {
+    persona: PersonaConfig,
+}
+
+impl PromptBuilder {
+    pub fn new(persona: PersonaConfig) -> Self {
+        Self { persona }
+    }
+
+    pub fn persona(&self) -> &PersonaConfig {
+        &self.persona
+    }
+
+    /// 按当前时间生成系统提示词
+    pub fn system_prompt(&self, context: &PromptContext) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        self.system_prompt_at(context, now)
+    }
+
+    /// 按指定的 Unix 秒级时间戳生成系统提示词
+    pub fn system_prompt_at(&self, context: &PromptContext, timestamp: i64) -> String {
+        let persona = &self.persona;
+        let or_unknown = |value: &Option<String>| {
+            value
+                .as_deref()
+                .filter(|value| !value.is_empty())
+                .unwrap_or(UNKNOWN)
+                .to_string()
+        };
+        let variables = [
+            ("{name}", persona.name.clone()),
+            ("{streamer}", persona.streamer_name.clone()),
+            ("{title}", or_unknown(&context.stream_title)),
+            ("{area}", or_unknown(&context.area_name)),
+            (
+                "{time_of_day}",
+                time_of_day(timestamp, persona.utc_offset_hours).to_string(),
+            ),
+            ("{viewer}", or_unknown(&context.viewer_name)),
+        ];
+        let mut prompt = substitute(&persona.system_prompt, &variables);
+
+        if !persona.speaking_style.is_empty() {
+            prompt.push_str(&format!("\n说话风格：{}", persona.speaking_style));
+        }
+        if !persona.banned_topics.is_empty() {
+            prompt.push_str(&format!(
+                "\n不要谈论以下话题，被问到时礼貌地转移话题：{}",
+                persona.banned_topics.join("、")
+            ));
+        }
+        if let Some(summary) = &context.viewer_summary {
+            prompt.push_str(&format!("\n正在和你说话的是{}", summary));
+        }
+        prompt
+    }
+
+    /// 生成发给大模型的消息列表：系统提示词加观众的消息
+    pub fn build(&self, context: &PromptContext, message: String) -> Vec<OpenAIMessage> {
+        self.build_with_history(context, Vec::new(), message)
+    }
+
+    /// 生成带历史的消息列表：系统提示词、历史消息（见 `ConversationStore::history`）、观众的消息
+    pub fn build_with_history(
+        &self,
+        context: &PromptContext,
+        history: Vec<OpenAIMessage>,
+        message: String,
+    ) -> Vec<OpenAIMessage> {
+        let mut messages = Vec::with_capacity(history.len() + 2);
+        messages.push(OpenAIMessage {
+            role: "system".to_string(),
+            content: self.system_prompt(context),
+        });
+        messages.extend(history);
+        messages.push(OpenAIMessage {
+            role: "user".to_string(),
+            content: message,
+        });
+        messages
+    }
+}
@@ -0,0 +1,381 @@
+//! 直播消息管道
+//!
+//! 客户端的读取循环按优先级把消息放进入站队列，从不等待消费端：礼物、醒目留言和大航海
+//! 消息进入单独的无损队列，其余消息进入有界队列，积压时直接丢弃，点赞和进房消息丢得更早。
+//! 消费端再把消息送入有界通道：通道积压时优先丢弃点赞和进房消息，其余消息只会等待，
+//! 等待期间入站队列照常按上面的规则丢弃。另一端按固定间隔把消息打包发送，
+//! 同一批次中属于同一连击的礼物会被合并为一条。
+
+use crate::core::BilibiliMessage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+
+/// 消息管道配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipelineConfig {
+    /// 通道容量，也是客户端入站队列中可丢弃消息的容量
+    pub capacity: usize,
+    /// 通道或入站队列中积压的消息数达到该值后，丢弃新到的点赞和进房消息
+    pub low_priority_limit: usize,
+    /// 打包发送的间隔毫秒数，为 0 时收到消息立即发送
+    pub batch_interval_ms: u64,
+    /// 单个批次的最大消息数
+    pub max_batch_size: usize,
+    /// 是否合并同一批次中属于同一连击的礼物
+    pub coalesce_combo: bool,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            low_priority_limit: 256,
+            batch_interval_ms: 200,
+            max_batch_size: 200,
+            coalesce_combo: true,
+        }
+    }
+}
+
+/// 消息在管道中的优先级
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessagePriority {
+    /// 积压时可以丢弃：点赞、进房
+    Low,
+    /// 普通消息：弹幕、开关播及未知消息
+    Normal,
+    /// 绝不丢弃：礼物、醒目留言、大航海、互动结束
+    Critical,
+}
+
+impl MessagePriority {
+    pub fn of(message: &BilibiliMessage) -> Self {
+        match message {
+            BilibiliMessage::Like { .. } | BilibiliMessage::LiveRoomEnter { .. } => {
+                MessagePriority::Low
+            }
+            BilibiliMessage::Gift { .. }
+            | BilibiliMessage::SuperChat { .. }
+            | BilibiliMessage::SuperChatDel { .. }
+            | BilibiliMessage::Guard { .. }
+            | BilibiliMessage::InteractionEnd { .. } => MessagePriority::Critical,
+            _ => MessagePriority::Normal,
+        }
+    }
+}
+
+/// 管道运行计数
+#[derive(Debug, Default)]
+pub struct PipelineStats {
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    coalesced: AtomicU64,
+    batches: AtomicU64,
+}
+
+/// 管道计数快照，返回给前端
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineStatsSnapshot {
+    /// 进入通道的消息数
+    pub sent: u64,
+    /// 因积压被丢弃的消息数
+    pub dropped: u64,
+    /// 被合并进其他连击礼物的消息数
+    pub coalesced: u64,
+    /// 已发送的批次数
+    pub batches: u64,
+}
+
+impl PipelineStats {
+    pub fn snapshot(&self) -> PipelineStatsSnapshot {
+        PipelineStatsSnapshot {
+            sent: self.sent.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            batches: self.batches.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 记录一条被丢弃的消息
+fn drop_message(stats: &PipelineStats, message: &BilibiliMessage) {
+    let dropped = stats.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+    // 避免刷屏，每丢弃 100 条记录一次
+    if dropped % 100 == 1 {
+        log::warn!(
+            "消息积压，丢弃消息: {}，累计丢弃 {} 条",
+            message.cmd(),
+            dropped
+        );
+    }
+}
+
+/// 创建客户端的入站队列
+pub fn inbound(
+    config: &PipelineConfig,
+    stats: Arc<PipelineStats>,
+) -> (InboundSender, InboundReceiver) {
+    let (lossy, lossy_rx) = mpsc::channel(config.capacity.max(1));
+    let (critical, critical_rx) = mpsc::unbounded_channel();
+    let sender = InboundSender {
+        lossy,
+        critical,
+        low_priority_limit: config.low_priority_limit,
+        next_seq: AtomicU64::new(0),
+        stats,
+    };
+    let receiver = InboundReceiver {
+        lossy: lossy_rx,
+        critical: critical_rx,
+        lossy_next: None,
+        critical_next: None,
+    };
+    (sender, receiver)
+}
+
+/// 入站队列的发送端，由客户端的读取循环持有
+pub struct InboundSender {
+    lossy: mpsc::Sender<(u64, BilibiliMessage)>,
+    critical: mpsc::UnboundedSender<(u64, BilibiliMessage)>,
+    low_priority_limit: usize,
+    // 两个队列共用的序号，接收端据此恢复消息到达的顺序
+    next_seq: AtomicU64,
+    stats: Arc<PipelineStats>,
+}
+
+impl InboundSender {
+    /// 按优先级放入队列，从不等待，接收端已关闭时返回 `false`
+    ///
+    /// 礼物等消息进入无损队列；其余消息在队列满时丢弃，点赞和进房消息在积压达到
+    /// `low_priority_limit` 时就丢弃
+    pub fn send(&self, message: BilibiliMessage) -> bool {
+        let priority = MessagePriority::of(&message);
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        if priority == MessagePriority::Critical {
+            return self.critical.send((seq, message)).is_ok();
+        }
+
+        if priority == MessagePriority::Low {
+            let queued = self.lossy.max_capacity() - self.lossy.capacity();
+            if queued >= self.low_priority_limit {
+                if self.lossy.is_closed() {
+                    return false;
+                }
+                drop_message(&self.stats, &message);
+                return true;
+            }
+        }
+
+        match self.lossy.try_send((seq, message)) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full((_, message))) => {
+                drop_message(&self.stats, &message);
+                true
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+/// 入站队列的接收端，按到达顺序取出两个队列中的消息
+pub struct InboundReceiver {
+    lossy: mpsc::Receiver<(u64, BilibiliMessage)>,
+    critical: mpsc::UnboundedReceiver<(u64, BilibiliMessage)>,
+    // 已经取出但还没轮到的消息
+    lossy_next: Option<(u64, BilibiliMessage)>,
+    critical_next: Option<(u64, BilibiliMessage)>,
+}
+
+impl InboundReceiver {
+    /// 取出下一条消息，发送端已关闭且两个队列都为空时返回 `None`
+    pub async fn recv(&mut self) -> Option<BilibiliMessage> {
+        if self.lossy_next.is_none() && self.critical_next.is_none() {
+            tokio::select! {
+                Some(entry) = self.critical.recv() => self.critical_next = Some(entry),
+                Some(entry) = self.lossy.recv() => self.lossy_next = Some(entry),
+                else => return None,
+            }
+        }
+
+        // 发送端按序号顺序写入，先于已取出消息写入另一队列的消息此时一定可以取到
+        if self.critical_next.is_none() {
+            self.critical_next = self.critical.try_recv().ok();
+        }
+        if self.lossy_next.is_none() {
+            self.lossy_next = self.lossy.try_recv().ok();
+        }
+
+        let critical_first = match (&self.critical_next, &self.lossy_next) {
+            (Some((critical, _)), Some((lossy, _))) => critical < lossy,
+            (critical, _) => critical.is_some(),
+        };
+        let next = if critical_first {
+            self.critical_next.take()
+        } else {
+            self.lossy_next.take()
+        };
+        next.map(|(_, message)| message)
+    }
+}
+
+/// 创建一条消息管道
+pub fn channel(
+    config: &PipelineConfig,
+    stats: Arc<PipelineStats>,
+) -> (MessageSender, mpsc::Receiver<BilibiliMessage>) {
+    let (inner, receiver) = mpsc::channel(config.capacity.max(1));
+    let sender = MessageSender {
+        inner,
+        low_priority_limit: config.low_priority_limit,
+        stats,
+    };
+    (sender, receiver)
+}
+
+/// 按优先级发送消息的通道发送端
+#[derive(Clone)]
+pub struct MessageSender {
+    inner: mpsc::Sender<BilibiliMessage>,
+    low_priority_limit: usize,
+    stats: Arc<PipelineStats>,
+}
+
+impl MessageSender {
+    /// 发送一条消息，接收端已关闭时返回 `Err`
+    ///
+    /// 低优先级消息在积压时直接丢弃，其余消息在通道满时等待，由此对转发任务形成背压
+    pub async fn send(&self, message: BilibiliMessage) -> Result<(), BilibiliMessage> {
+        if MessagePriority::of(&message) == MessagePriority::Low {
+            let queued = self.inner.max_capacity() - self.inner.capacity();
+            if queued >= self.low_priority_limit {
+                drop_message(&self.stats, &message);
+                return Ok(());
+            }
+            return match self.inner.try_send(message) {
+                Ok(()) => {
+                    self.stats.sent.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                }
+                Err(mpsc::error::TrySendError::Full(message)) => {
+                    drop_message(&self.stats, &message);
+                    Ok(())
+                }
+                Err(mpsc::error::TrySendError::Closed(message)) => Err(message),
+            };
+        }
+
+        self.inner.send(message).await.map_err(|e| e.0)?;
+        self.stats.sent.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// 一批发送到前端的消息
+#[derive(Debug, Serialize)]
+pub struct MessageBatch<'a> {
+    pub messages: &'a [BilibiliMessage],
+}
+
+/// 把通道中的消息按时间间隔打包
+pub struct MessageBatcher {
+    receiver: mpsc::Receiver<BilibiliMessage>,
+    config: PipelineConfig,
+    stats: Arc<PipelineStats>,
+}
+
+impl MessageBatcher {
+    pub fn new(
+        receiver: mpsc::Receiver<BilibiliMessage>,
+        config: PipelineConfig,
+        stats: Arc<PipelineStats>,
+    ) -> Self {
+        Self {
+            receiver,
+            config,
+            stats,
+        }
+    }
+
+    /// 等待下一批消息，通道关闭且没有剩余消息时返回 `None`
+    pub async fn next_batch(&mut self) -> Option<Vec<BilibiliMessage>> {
+        let first = self.receiver.recv().await?;
+        let max_batch_size = self.config.max_batch_size.max(1);
+        let mut batch = vec![first];
+
+        if self.config.batch_interval_ms == 0 {
+            // 不等待，只取走已经到达的消息
+            while batch.len() < max_batch_size {
+                match self.receiver.try_recv() {
+                    Ok(message) => batch.push(message),
+                    Err(_) => break,
+                }
+            }
+        } else {
+            let deadline = Instant::now() + Duration::from_millis(self.config.batch_interval_ms);
+            while batch.len() < max_batch_size {
+                match tokio::time::timeout_at(deadline, self.receiver.recv()).await {
+                    Ok(Some(message)) => batch.push(message),
+                    Ok(None) | Err(_) => break,
+                }
+            }
+        }
+
+        if self.config.coalesce_combo {
+            let before = batch.len();
+            batch = coalesce_combo_gifts(batch);
+            self.stats
+                .coalesced
+                .fetch_add((before - batch.len()) as u64, Ordering::Relaxed);
+        }
+        self.stats.batches.fetch_add(1, Ordering::Relaxed);
+
+        Some(batch)
+    }
+}
+
+/// 合并同一批次中 `combo_id` 相同的礼物
+///
+/// 合并后的消息保留第一条的位置，礼物数量累加，其余字段取最后一条
+fn coalesce_combo_gifts(batch: Vec<BilibiliMessage>) -> Vec<BilibiliMessage> {
+    let mut result: Vec<BilibiliMessage> = Vec::with_capacity(batch.len());
+    let mut combo_index: HashMap<String, usize> = HashMap::new();
+
+    for message in batch {
+        let combo_id = match &message {
+            BilibiliMessage::Gift { data } => data
+                .combo_info
+                .as_ref()
+                .map(|combo| combo.combo_id.clone())
+                .filter(|id| !id.is_empty()),
+            _ => None,
+        };
+
+        let Some(combo_id) = combo_id else {
+            result.push(message);
+            continue;
+        };
+
+        match combo_index.get(&combo_id) {
+            Some(&index) => {
+                if let (BilibiliMessage::Gift { data: merged }, BilibiliMessage::Gift { data }) =
+                    (&mut result[index], message)
+                {
+                    let gift_num = merged.gift_num + data.gift_num;
+                    *merged = data;
+                    merged.gift_num = gift_num;
+                }
+            }
+            None => {
+                combo_index.insert(combo_id, result.len());
+                result.push(message);
+            }
+        }
+    }
+
+    result
+}
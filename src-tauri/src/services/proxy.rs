@@ -34,9 +34,6 @@ impl ProxyServer {
         &mut self,
         port: u16,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let (shutdown_sender, shutdown_receiver) = oneshot::channel();
-        self.shutdown_sender = Some(shutdown_sender);
-
         let client = Arc::new(Client::new());
 
         // 创建代理路由
@@ -65,14 +62,15 @@ impl ProxyServer {
             .with(cors)
             .recover(handle_rejection);
 
-        info!("代理服务启动在端口: {}", port);
-
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel();
         let (_, server) =
-            warp::serve(routes).bind_with_graceful_shutdown(([127, 0, 0, 1], port), async {
+            warp::serve(routes).try_bind_with_graceful_shutdown(([127, 0, 0, 1], port), async {
                 shutdown_receiver.await.ok();
-            });
+            })?;
+        tokio::spawn(server);
 
-        server.await;
+        info!("代理服务启动在端口: {}", port);
+        self.shutdown_sender = Some(shutdown_sender);
         Ok(())
     }
 
@@ -84,6 +82,12 @@ impl ProxyServer {
     }
 }
 
+impl Default for ProxyServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 async fn handle_proxy(
     query: ProxyQuery,
     client: Arc<Client>,
@@ -228,10 +232,10 @@ async fn handle_rejection(err: warp::Rejection) -> Result<impl Reply, Infallible
     if err.is_not_found() {
         code = StatusCode::NOT_FOUND;
         message = "路径未找到";
-    } else if let Some(_) = err.find::<warp::filters::body::BodyDeserializeError>() {
+    } else if err.find::<warp::filters::body::BodyDeserializeError>().is_some() {
         code = StatusCode::BAD_REQUEST;
         message = "请求体格式错误";
-    } else if let Some(_) = err.find::<warp::reject::MethodNotAllowed>() {
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
         code = StatusCode::METHOD_NOT_ALLOWED;
         message = "请求方法不被允许";
     } else {
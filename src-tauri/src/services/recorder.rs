@@ -0,0 +1,202 @@
+//! 直播会话录制与回放
+//!
+//! 录制文件格式：文件头为 `MAGIC`，之后每帧依次为
+//! 8 字节大端毫秒时间戳、4 字节大端长度和原始WebSocket二进制帧。
+//!
+//! 录制在单独的线程中写文件，接收消息的异步任务只负责把帧放进队列，不会被磁盘 I/O 阻塞。
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, BufReader};
+
+/// 录制文件头
+const MAGIC: &[u8; 8] = b"BLRC0001";
+
+/// 单帧最大长度，防止损坏的文件导致分配过大内存
+const MAX_FRAME_SIZE: u32 = 16 * 1024 * 1024;
+
+/// 回放配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayConfig {
+    /// 录制文件路径
+    pub path: PathBuf,
+    /// 回放倍速，1.0 为实时，必须大于 0；取很大的值时几乎不等待、尽快回放
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+impl ReplayConfig {
+    /// 检查倍速是否为有效的正数
+    pub fn validate(&self) -> Result<(), String> {
+        if self.speed.is_finite() && self.speed > 0.0 {
+            Ok(())
+        } else {
+            Err(format!("回放倍速必须大于 0: {}", self.speed))
+        }
+    }
+
+    /// 录制时间偏移为 `offset_ms` 的帧按倍速换算后应在回放开始多久后送出，超出范围时返回 `None`
+    pub fn delay_for(&self, offset_ms: u64) -> Option<Duration> {
+        Duration::try_from_secs_f64(offset_ms as f64 / 1000.0 / self.speed).ok()
+    }
+}
+
+/// 录制下来的一帧
+#[derive(Debug, Clone)]
+pub struct RecordedFrame {
+    pub timestamp_ms: u64,
+    pub data: Vec<u8>,
+}
+
+/// 会话录制器，把收到的每个原始二进制帧追加写入文件
+///
+/// 帧在收到时打上时间戳后交给写入线程，录制器被丢弃后写入线程写完剩余的帧再退出。
+pub struct SessionRecorder {
+    path: PathBuf,
+    sender: mpsc::Sender<RecordedFrame>,
+    frames: Arc<AtomicU64>,
+}
+
+impl SessionRecorder {
+    /// 创建录制文件并启动写入线程，已存在的文件会被覆盖
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writer.write_all(MAGIC)?;
+        writer.flush()?;
+        log::info!("开始录制会话: {:?}", path);
+
+        let (sender, receiver) = mpsc::channel();
+        let frames = Arc::new(AtomicU64::new(0));
+        let written = frames.clone();
+        std::thread::Builder::new()
+            .name("session-recorder".to_string())
+            .spawn(move || Self::write_frames(writer, receiver, written))?;
+
+        Ok(Self {
+            path,
+            sender,
+            frames,
+        })
+    }
+
+    /// 写入线程：写完队列中已有的帧后落盘，异常退出时也不丢失已写入的内容
+    fn write_frames(
+        mut writer: BufWriter<File>,
+        receiver: mpsc::Receiver<RecordedFrame>,
+        written: Arc<AtomicU64>,
+    ) {
+        let write_frame = |writer: &mut BufWriter<File>, frame: RecordedFrame| {
+            writer.write_all(&frame.timestamp_ms.to_be_bytes())?;
+            writer.write_all(&(frame.data.len() as u32).to_be_bytes())?;
+            writer.write_all(&frame.data)
+        };
+
+        let mut next = receiver.recv().ok();
+        while let Some(frame) = next {
+            if let Err(e) = write_frame(&mut writer, frame) {
+                log::error!("写入录制文件失败，停止录制: {}", e);
+                return;
+            }
+            written.fetch_add(1, Ordering::Relaxed);
+
+            next = match receiver.try_recv() {
+                Ok(frame) => Some(frame),
+                Err(mpsc::TryRecvError::Empty) => {
+                    if let Err(e) = writer.flush() {
+                        log::error!("写入录制文件失败，停止录制: {}", e);
+                        return;
+                    }
+                    receiver.recv().ok()
+                }
+                Err(mpsc::TryRecvError::Disconnected) => None,
+            };
+        }
+
+        if let Err(e) = writer.flush() {
+            log::error!("写入录制文件失败: {}", e);
+        }
+    }
+
+    /// 追加一帧，只放入写入队列，不会阻塞
+    pub fn record(&self, data: &[u8]) -> std::io::Result<()> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        self.sender
+            .send(RecordedFrame {
+                timestamp_ms,
+                data: data.to_vec(),
+            })
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "录制线程已退出"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 已写入文件的帧数
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+}
+
+/// 录制文件读取器
+pub struct SessionReader {
+    reader: BufReader<tokio::fs::File>,
+}
+
+impl SessionReader {
+    pub async fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(tokio::fs::File::open(path.as_ref()).await?);
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic).await?;
+        if &magic != MAGIC {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "不是有效的会话录制文件",
+            ));
+        }
+
+        Ok(Self { reader })
+    }
+
+    /// 读取下一帧，文件结束时返回 `Ok(None)`
+    pub async fn next_frame(&mut self) -> std::io::Result<Option<RecordedFrame>> {
+        let timestamp_ms = match self.reader.read_u64().await {
+            Ok(ts) => ts,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let len = self.reader.read_u32().await?;
+        if len > MAX_FRAME_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("录制帧长度异常: {}", len),
+            ));
+        }
+
+        let mut data = vec![0u8; len as usize];
+        self.reader.read_exact(&mut data).await?;
+
+        Ok(Some(RecordedFrame { timestamp_ms, data }))
+    }
+}
@@ -0,0 +1,106 @@
+//! 分句
+//!
+//! 把流式到达的回复文本按句子切开，每凑齐一句就可以交给 TTS，不必等整段回复生成完。
+//! 中文按 。！？；… 等标点断句，英文的 . ! ? 后面需要跟空白才算句末，避免拆开小数和缩写中间的点。
+//! 中文回复里常混用半角的 ! ? ;，它们后面直接跟中日文字符时也算句末；半角 . 后面跟中文
+//! 多半是序号（如“1.第一点”），仍然不断开。
+
+/// 句末标点
+fn is_terminator(c: char) -> bool {
+    matches!(
+        c,
+        '。' | '！' | '？' | '；' | '…' | '!' | '?' | ';' | '.' | '\n'
+    )
+}
+
+/// 可以跟在句末标点后面、仍属于本句的右引号和右括号
+fn is_closer(c: char) -> bool {
+    matches!(
+        c,
+        '”' | '’' | '」' | '』' | '）' | '》' | '】' | '"' | '\'' | ')' | ']'
+    )
+}
+
+/// 中日文字符
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3040}'..='\u{30FF}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}'
+    )
+}
+
+/// 以 `last` 结尾的标点串后面跟着 `next` 时是否算句末
+fn ends_sentence(last: char, next: char) -> bool {
+    match last {
+        '.' => next.is_whitespace(),
+        '!' | '?' | ';' => next.is_whitespace() || is_cjk(next),
+        _ => true,
+    }
+}
+
+/// 增量分句器
+#[derive(Debug, Default)]
+pub struct SentenceSplitter {
+    buffer: String,
+}
+
+impl SentenceSplitter {
+    /// 追加一段文本，返回其中已经完整的句子
+    ///
+    /// 句末标点之后还没有收到下一个字符时无法确定句子是否结束（可能还有连续的标点或右引号），
+    /// 这部分会留到下一次追加或 `finish` 时再处理。
+    pub fn push(&mut self, text: &str) -> Vec<String> {
+        self.buffer.push_str(text);
+        let mut sentences = Vec::new();
+        let mut start = 0;
+        let mut chars = self.buffer.char_indices().peekable();
+
+        while let Some((_, c)) = chars.next() {
+            if !is_terminator(c) {
+                continue;
+            }
+            // 连续的句末标点（如 ！？、……、...）和紧随的右引号归入同一句
+            let mut last = c;
+            while let Some(&(_, next)) = chars.peek() {
+                if is_terminator(next) || is_closer(next) {
+                    if is_terminator(next) {
+                        last = next;
+                    }
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            let Some(&(end, next)) = chars.peek() else {
+                break;
+            };
+            if !ends_sentence(last, next) {
+                continue;
+            }
+            push_sentence(&mut sentences, &self.buffer[start..end]);
+            start = end;
+        }
+
+        self.buffer.drain(..start);
+        sentences
+    }
+
+    /// 文本结束，取出剩余的最后一句
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buffer);
+        let mut sentences = Vec::new();
+        push_sentence(&mut sentences, &rest);
+        sentences.pop()
+    }
+}
+
+fn push_sentence(sentences: &mut Vec<String>, sentence: &str) {
+    let sentence = sentence.trim();
+    // 只有标点或空白的片段不值得单独合成
+    if sentence
+        .chars()
+        .any(|c| !c.is_whitespace() && !is_terminator(c) && !is_closer(c))
+    {
+        sentences.push(sentence.to_string());
+    }
+}
@@ -0,0 +1,151 @@
+//! 直播会话运行器
+//!
+//! 从任意 `LiveEventSource` 读取事件，在消息进入消息管道之前完成统计、归档、观众档案、
+//! 醒目留言登记和连击聚合，再由管道按批次输出。输出方式由 `SessionOutput` 决定，
+//! 图形界面把它实现为 Tauri 事件，测试中直接收集即可，运行器本身不依赖 Tauri。
+
+use crate::core::{BilibiliMessage, ConnectionEvent};
+use crate::services::archive::EventArchive;
+use crate::services::combo::{ComboConfig, ComboFinished, run_combo_aggregator};
+use crate::services::pipeline::{self, MessageBatcher, PipelineConfig, PipelineStats};
+use crate::services::source::{LiveEvent, LiveEventSource};
+use crate::services::stats::LiveStats;
+use crate::services::superchat::SuperChatRegistry;
+use crate::services::viewer::{ViewerProfile, ViewerRegistry};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// 会话的输出端
+pub trait SessionOutput: Send + Sync + 'static {
+    /// 连接状态变化，不经过消息管道，立即输出
+    fn connection(&self, event: &ConnectionEvent);
+
+    /// 消息管道打包好的一批消息
+    fn messages(&self, messages: &[BilibiliMessage]);
+
+    /// 隔了一段时间再次出现的老观众
+    fn viewer_returned(&self, profile: &ViewerProfile);
+
+    /// 一次连击结束
+    fn combo_finished(&self, summary: &ComboFinished);
+}
+
+/// 会话用到的登记表和配置
+pub struct SessionContext {
+    /// 会话名称
+    pub session: String,
+    /// 统计需要先调用 `LiveStats::start`
+    pub stats: Arc<LiveStats>,
+    /// 归档不可用时为 `None`
+    pub archive: Option<EventArchive>,
+    pub viewers: Arc<ViewerRegistry>,
+    pub superchats: Arc<SuperChatRegistry>,
+    pub pipeline: PipelineConfig,
+    pub pipeline_stats: Arc<PipelineStats>,
+    pub combo: ComboConfig,
+}
+
+impl SessionContext {
+    /// 处理一条原始消息，此时消息还没有被管道丢弃或合并
+    fn observe(
+        &self,
+        message: &BilibiliMessage,
+        combo: Option<&mpsc::UnboundedSender<BilibiliMessage>>,
+    ) -> Vec<ViewerProfile> {
+        let messages = std::slice::from_ref(message);
+        self.stats.record(&self.session, messages);
+        if let Some(archive) = &self.archive {
+            archive.append(&self.session, messages);
+        }
+        match message {
+            BilibiliMessage::Gift { .. } => {
+                if let Some(combo) = combo {
+                    let _ = combo.send(message.clone());
+                }
+            }
+            BilibiliMessage::SuperChat { data } => {
+                self.superchats.insert(&self.session, data.clone());
+            }
+            BilibiliMessage::SuperChatDel { data } => {
+                self.superchats.retract(&self.session, &data.message_ids);
+            }
+            _ => {}
+        }
+        self.viewers.record(messages)
+    }
+}
+
+/// 表示会话结束的消息，返回结束原因
+pub fn session_end_reason(message: &BilibiliMessage) -> Option<&'static str> {
+    match message {
+        BilibiliMessage::LiveEnd { .. } => Some("直播已结束"),
+        BilibiliMessage::InteractionEnd { .. } => Some("互动玩法已结束"),
+        _ => None,
+    }
+}
+
+/// 运行一个已经连接的事件源，直到事件源结束或收到结束消息
+///
+/// 读取事件与按批次输出同时进行，管道积压时只暂停读取，事件源自己的接收不受影响。
+/// 收到直播结束或互动结束消息时，把它和之前的消息输出完后返回结束原因；
+/// 事件源结束时返回 `None`。事件源不会被关闭，由调用方决定如何收尾。
+pub async fn run_session<S, O>(
+    source: &mut S,
+    context: SessionContext,
+    output: Arc<O>,
+) -> Option<&'static str>
+where
+    S: LiveEventSource,
+    O: SessionOutput,
+{
+    let (sender, receiver) = pipeline::channel(&context.pipeline, context.pipeline_stats.clone());
+    let mut batcher = MessageBatcher::new(
+        receiver,
+        context.pipeline.clone(),
+        context.pipeline_stats.clone(),
+    );
+
+    let combo = context.combo.enabled.then(|| {
+        let (combo_tx, combo_rx) = mpsc::unbounded_channel();
+        let output = output.clone();
+        tokio::spawn(run_combo_aggregator(
+            combo_rx,
+            context.combo.clone(),
+            move |summary| output.combo_finished(&summary),
+        ));
+        combo_tx
+    });
+
+    let read = async {
+        // 离开时释放管道发送端和连击发送端，批次和连击随之输出完毕
+        let (sender, combo) = (sender, combo);
+        while let Some(event) = source.next_event().await {
+            let message = match event {
+                LiveEvent::Message(message) => message,
+                LiveEvent::Connection(event) => {
+                    output.connection(&event);
+                    continue;
+                }
+            };
+            for profile in context.observe(&message, combo.as_ref()) {
+                output.viewer_returned(&profile);
+            }
+            let end_reason = session_end_reason(&message);
+            if sender.send(message).await.is_err() {
+                return None;
+            }
+            if end_reason.is_some() {
+                return end_reason;
+            }
+        }
+        None
+    };
+    let write = async {
+        while let Some(messages) = batcher.next_batch().await {
+            output.messages(&messages);
+        }
+    };
+
+    let (end_reason, ()) = tokio::join!(read, write);
+    end_reason
+}
@@ -0,0 +1,149 @@
+//! 直播事件源
+//!
+//! `LiveEventSource` 把“连接、逐条取事件、关闭”抽象出来，开放平台客户端、录制回放和
+//! 内存中的模拟源都实现了它。上层（AI 流水线、命令行机器人、直播叠加层等）只依赖这个
+//! trait，不需要关心消息来自哪里，也不依赖 Tauri。
+
+use crate::core::{BilibiliMessage, ConnectionEvent};
+use crate::services::bilibili::{BilibiliClient, BilibiliConfig, BilibiliError, ReconnectPolicy};
+use crate::services::heartbeat::HeartbeatConfig;
+use crate::services::pipeline::PipelineConfig;
+use crate::services::recorder::ReplayConfig;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::time::Duration;
+
+/// 事件源产生的事件
+// 绝大多数事件都是消息，不值得为了连接事件把消息装箱
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum LiveEvent {
+    /// 直播间业务消息
+    Message(BilibiliMessage),
+    /// 连接状态变化
+    Connection(ConnectionEvent),
+}
+
+/// 直播事件源
+///
+/// 先调用 `connect`，之后反复调用 `next_event` 直到返回 `None`（连接彻底结束），
+/// 不再需要时调用 `close`。
+pub trait LiveEventSource: Send {
+    /// 建立连接
+    fn connect(&mut self) -> impl Future<Output = Result<(), BilibiliError>> + Send;
+
+    /// 等待下一个事件，连接结束且没有剩余事件时返回 `None`
+    fn next_event(&mut self) -> impl Future<Output = Option<LiveEvent>> + Send;
+
+    /// 关闭连接，之后 `next_event` 会在剩余事件取完后返回 `None`
+    fn close(&mut self) -> impl Future<Output = Result<(), BilibiliError>> + Send;
+}
+
+/// 录制文件回放源，不需要开放平台凭据
+pub struct ReplaySource {
+    client: BilibiliClient,
+}
+
+impl ReplaySource {
+    pub fn new(replay: ReplayConfig) -> Self {
+        let config = BilibiliConfig {
+            id_code: String::new(),
+            app_id: 0,
+            access_key: String::new(),
+            access_secret: String::new(),
+            host: String::new(),
+            reconnect: ReconnectPolicy::default(),
+            record_path: None,
+            replay: Some(replay),
+            pipeline: PipelineConfig::default(),
+            heartbeat: HeartbeatConfig::default(),
+        };
+        Self {
+            client: BilibiliClient::new(config),
+        }
+    }
+}
+
+impl LiveEventSource for ReplaySource {
+    async fn connect(&mut self) -> Result<(), BilibiliError> {
+        LiveEventSource::connect(&mut self.client).await
+    }
+
+    async fn next_event(&mut self) -> Option<LiveEvent> {
+        self.client.next_event().await
+    }
+
+    async fn close(&mut self) -> Result<(), BilibiliError> {
+        LiveEventSource::close(&mut self.client).await
+    }
+}
+
+/// 内存中的模拟事件源，按顺序产生给定的消息，用于测试和离线调试
+pub struct MockSource {
+    messages: VecDeque<BilibiliMessage>,
+    interval: Duration,
+    pending: VecDeque<LiveEvent>,
+    connected: bool,
+}
+
+impl MockSource {
+    /// `interval` 为相邻两条消息之间的等待时间
+    pub fn new(messages: impl IntoIterator<Item = BilibiliMessage>, interval: Duration) -> Self {
+        Self {
+            messages: messages.into_iter().collect(),
+            interval,
+            pending: VecDeque::new(),
+            connected: false,
+        }
+    }
+}
+
+impl LiveEventSource for MockSource {
+    async fn connect(&mut self) -> Result<(), BilibiliError> {
+        self.connected = true;
+        self.pending
+            .push_back(LiveEvent::Connection(ConnectionEvent::Connecting {
+                timestamp: ConnectionEvent::now(),
+            }));
+        self.pending
+            .push_back(LiveEvent::Connection(ConnectionEvent::Authenticated {
+                timestamp: ConnectionEvent::now(),
+            }));
+        Ok(())
+    }
+
+    async fn next_event(&mut self) -> Option<LiveEvent> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        if !self.connected {
+            return None;
+        }
+        match self.messages.pop_front() {
+            Some(message) => {
+                if !self.interval.is_zero() {
+                    tokio::time::sleep(self.interval).await;
+                }
+                Some(LiveEvent::Message(message))
+            }
+            None => {
+                self.connected = false;
+                Some(LiveEvent::Connection(ConnectionEvent::closed(
+                    "模拟消息已全部发送",
+                )))
+            }
+        }
+    }
+
+    async fn close(&mut self) -> Result<(), BilibiliError> {
+        if self.connected {
+            self.connected = false;
+            self.messages.clear();
+            self.pending
+                .push_back(LiveEvent::Connection(ConnectionEvent::closed(
+                    "主动断开连接",
+                )));
+        }
+        Ok(())
+    }
+}
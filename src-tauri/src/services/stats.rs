@@ -0,0 +1,257 @@
+//! 直播统计
+//!
+//! 根据消息流计算每个会话的礼物收入、醒目留言、大航海、弹幕、点赞和进房等汇总数据，
+//! 并按分钟记录时间序列，供状态栏和下播后的报表共用。
+
+use crate::core::BilibiliMessage;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 每个会话最多保留的分钟数
+const MAX_MINUTES: usize = 24 * 60;
+
+/// 礼物汇总，价值单位与 `price` 相同（1/1000 元）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GiftTotals {
+    /// 礼物消息条数
+    pub events: u64,
+    /// 礼物个数
+    pub num: i64,
+    /// 按 `price` 计算的总价值
+    pub price: i64,
+    /// 按 `r_price` 计算的总价值
+    pub r_price: i64,
+}
+
+impl GiftTotals {
+    fn add(&mut self, num: i64, price: i64, r_price: i64) {
+        self.events += 1;
+        self.num += num;
+        self.price += price.saturating_mul(num);
+        self.r_price += r_price.saturating_mul(num);
+    }
+}
+
+/// 某个大航海等级的汇总
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GuardTotals {
+    pub events: u64,
+    /// 购买数量（`guard_num` 之和）
+    pub num: i64,
+    /// 总价值，单位与 `price` 相同
+    pub price: i64,
+}
+
+/// 一分钟内的统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MinuteStats {
+    /// 该分钟开始的毫秒时间戳
+    pub minute: i64,
+    pub danmaku: u64,
+    /// 付费礼物价值（`price`）
+    pub paid_gift_value: i64,
+    pub free_gift_value: i64,
+    /// 醒目留言金额（元）
+    pub super_chat_rmb: i64,
+    pub guards: u64,
+    pub likes: i64,
+    pub enters: u64,
+}
+
+/// 一个会话的统计快照
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionStatsSnapshot {
+    pub session: String,
+    /// 开始统计和最后一条消息的毫秒时间戳
+    pub started_at: i64,
+    pub last_message_at: Option<i64>,
+    /// 会话已结束（连接关闭）的毫秒时间戳
+    pub ended_at: Option<i64>,
+    /// 最近一次开播消息中的直播标题和分区
+    pub title: Option<String>,
+    pub area_name: Option<String>,
+    pub total_messages: u64,
+    pub paid_gifts: GiftTotals,
+    pub free_gifts: GiftTotals,
+    pub super_chats: u64,
+    /// 醒目留言总金额（元）
+    pub super_chat_rmb: i64,
+    /// 以 `guard_level` 为键的大航海汇总
+    pub guards: BTreeMap<i64, GuardTotals>,
+    pub danmaku: u64,
+    pub unique_danmaku_senders: usize,
+    /// 点赞数（`like_count` 之和）
+    pub likes: i64,
+    pub enters: u64,
+    /// 按分钟的时间序列，升序；定时推送的事件中为空
+    pub minutes: Vec<MinuteStats>,
+}
+
+#[derive(Default)]
+struct SessionStats {
+    snapshot: SessionStatsSnapshot,
+    danmaku_senders: HashSet<String>,
+    minutes: BTreeMap<i64, MinuteStats>,
+    generation: u64,
+}
+
+impl SessionStats {
+    fn minute(&mut self, now: i64) -> &mut MinuteStats {
+        let minute = now - now.rem_euclid(60_000);
+        if self.minutes.len() >= MAX_MINUTES
+            && !self.minutes.contains_key(&minute)
+            && let Some(&oldest) = self.minutes.keys().next()
+        {
+            self.minutes.remove(&oldest);
+        }
+        self.minutes.entry(minute).or_insert_with(|| MinuteStats {
+            minute,
+            ..Default::default()
+        })
+    }
+
+    fn record(&mut self, message: &BilibiliMessage, now: i64) {
+        self.snapshot.total_messages += 1;
+        self.snapshot.last_message_at = Some(now);
+
+        match message {
+            BilibiliMessage::Danmaku { data } => {
+                self.snapshot.danmaku += 1;
+                self.danmaku_senders.insert(data.open_id.clone());
+                self.snapshot.unique_danmaku_senders = self.danmaku_senders.len();
+                self.minute(now).danmaku += 1;
+            }
+            BilibiliMessage::Gift { data } => {
+                let value = data.price.saturating_mul(data.gift_num);
+                if data.paid {
+                    self.snapshot
+                        .paid_gifts
+                        .add(data.gift_num, data.price, data.r_price);
+                    self.minute(now).paid_gift_value += value;
+                } else {
+                    self.snapshot
+                        .free_gifts
+                        .add(data.gift_num, data.price, data.r_price);
+                    self.minute(now).free_gift_value += value;
+                }
+            }
+            BilibiliMessage::SuperChat { data } => {
+                self.snapshot.super_chats += 1;
+                self.snapshot.super_chat_rmb += data.rmb;
+                self.minute(now).super_chat_rmb += data.rmb;
+            }
+            BilibiliMessage::Guard { data } => {
+                let totals = self.snapshot.guards.entry(data.guard_level).or_default();
+                totals.events += 1;
+                totals.num += data.guard_num;
+                totals.price += data.price;
+                self.minute(now).guards += 1;
+            }
+            BilibiliMessage::Like { data } => {
+                self.snapshot.likes += data.like_count;
+                self.minute(now).likes += data.like_count;
+            }
+            BilibiliMessage::LiveRoomEnter { .. } => {
+                self.snapshot.enters += 1;
+                self.minute(now).enters += 1;
+            }
+            BilibiliMessage::LiveStart { data } => {
+                self.snapshot.title = Some(data.title.clone());
+                self.snapshot.area_name = Some(data.area_name.clone());
+            }
+            _ => {}
+        }
+    }
+
+    fn snapshot(&self, with_minutes: bool) -> SessionStatsSnapshot {
+        let mut snapshot = self.snapshot.clone();
+        if with_minutes {
+            snapshot.minutes = self.minutes.values().cloned().collect();
+        }
+        snapshot
+    }
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// 所有会话的直播统计
+#[derive(Default)]
+pub struct LiveStats {
+    sessions: Mutex<HashMap<String, SessionStats>>,
+}
+
+impl LiveStats {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, SessionStats>> {
+        match self.sessions.lock() {
+            Ok(sessions) => sessions,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// 开始统计一个会话，同名会话的旧数据会被清空，返回本次统计的代数
+    pub fn start(&self, session: &str) -> u64 {
+        let mut sessions = self.lock();
+        let generation = sessions
+            .get(session)
+            .map(|stats| stats.generation + 1)
+            .unwrap_or_default();
+        let mut stats = SessionStats {
+            generation,
+            ..Default::default()
+        };
+        stats.snapshot.session = session.to_string();
+        stats.snapshot.started_at = now_millis();
+        sessions.insert(session.to_string(), stats);
+        generation
+    }
+
+    /// 标记会话结束，统计数据保留到下次同名会话开始
+    pub fn finish(&self, session: &str, generation: u64) {
+        if let Some(stats) = self.lock().get_mut(session)
+            && stats.generation == generation
+        {
+            stats.snapshot.ended_at = Some(now_millis());
+        }
+    }
+
+    /// 统计一批消息
+    pub fn record(&self, session: &str, messages: &[BilibiliMessage]) {
+        let now = now_millis();
+        if let Some(stats) = self.lock().get_mut(session) {
+            for message in messages {
+                stats.record(message, now);
+            }
+        }
+    }
+
+    /// 获取会话快照，`with_minutes` 为假时不包含分钟序列
+    pub fn snapshot(&self, session: &str, with_minutes: bool) -> Option<SessionStatsSnapshot> {
+        self.lock()
+            .get(session)
+            .map(|stats| stats.snapshot(with_minutes))
+    }
+
+    /// 指定代数的统计是否仍在进行
+    pub fn is_running(&self, session: &str, generation: u64) -> bool {
+        self.lock().get(session).is_some_and(|stats| {
+            stats.generation == generation && stats.snapshot.ended_at.is_none()
+        })
+    }
+
+    pub fn list(&self, with_minutes: bool) -> Vec<SessionStatsSnapshot> {
+        let mut snapshots: Vec<SessionStatsSnapshot> = self
+            .lock()
+            .values()
+            .map(|stats| stats.snapshot(with_minutes))
+            .collect();
+        snapshots.sort_by(|a, b| a.session.cmp(&b.session));
+        snapshots
+    }
+}
@@ -0,0 +1,175 @@
+//! 醒目留言（SuperChat）生命周期管理
+//!
+//! 记录收到的醒目留言，在 `end_time` 后视为过期；收到 `SuperChatDel` 时标记为已撤回，
+//! 并唤醒正在为该留言生成回复或语音的任务，使其尽快放弃。
+
+use crate::core::SuperChatData;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+
+/// 过期或撤回的留言保留多少秒，便于之后查询状态
+const RETENTION_SECS: i64 = 600;
+
+/// 醒目留言当前的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuperChatStatus {
+    /// 仍在展示期内
+    Active,
+    /// 已超过 `end_time`
+    Expired,
+    /// 已被撤回（删除）
+    Retracted,
+}
+
+/// 一条醒目留言及其状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuperChatEntry {
+    pub session: String,
+    pub status: SuperChatStatus,
+    pub data: SuperChatData,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<i64, SuperChatEntry>,
+}
+
+/// 醒目留言登记表
+#[derive(Default)]
+pub struct SuperChatRegistry {
+    inner: Mutex<Inner>,
+    retracted: Notify,
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+impl SuperChatEntry {
+    fn refresh(&mut self, now: i64) {
+        if self.status == SuperChatStatus::Active
+            && self.data.end_time > 0
+            && self.data.end_time <= now
+        {
+            self.status = SuperChatStatus::Expired;
+        }
+    }
+}
+
+impl SuperChatRegistry {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// 登记一条新的醒目留言，同时清理保留期已过的旧记录
+    pub fn insert(&self, session: &str, data: SuperChatData) {
+        let now = now_secs();
+        let mut inner = self.lock();
+        inner.entries.retain(|_, entry| {
+            entry.refresh(now);
+            entry.status == SuperChatStatus::Active || entry.data.end_time + RETENTION_SECS > now
+        });
+
+        // 撤回可能先于留言本身到达，已撤回的记录保持撤回状态
+        if inner
+            .entries
+            .get(&data.message_id)
+            .is_some_and(|entry| entry.status == SuperChatStatus::Retracted)
+        {
+            return;
+        }
+
+        let mut entry = SuperChatEntry {
+            session: session.to_string(),
+            status: SuperChatStatus::Active,
+            data,
+        };
+        entry.refresh(now);
+        inner.entries.insert(entry.data.message_id, entry);
+    }
+
+    /// 撤回一组醒目留言，并唤醒所有等待撤回的任务
+    pub fn retract(&self, session: &str, message_ids: &[i64]) {
+        {
+            let mut inner = self.lock();
+            for &id in message_ids {
+                match inner.entries.get_mut(&id) {
+                    Some(entry) => entry.status = SuperChatStatus::Retracted,
+                    None => {
+                        // 记录一条占位，防止之后到达的留言被当作有效
+                        let data = SuperChatData {
+                            message_id: id,
+                            end_time: now_secs(),
+                            ..Default::default()
+                        };
+                        inner.entries.insert(
+                            id,
+                            SuperChatEntry {
+                                session: session.to_string(),
+                                status: SuperChatStatus::Retracted,
+                                data,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+        log::info!("醒目留言已撤回: {:?}", message_ids);
+        self.retracted.notify_waiters();
+    }
+
+    /// 查询一条醒目留言的状态，未登记时返回 `None`
+    pub fn status(&self, message_id: i64) -> Option<SuperChatStatus> {
+        let now = now_secs();
+        let mut inner = self.lock();
+        inner.entries.get_mut(&message_id).map(|entry| {
+            entry.refresh(now);
+            entry.status
+        })
+    }
+
+    pub fn is_retracted(&self, message_id: i64) -> bool {
+        self.status(message_id) == Some(SuperChatStatus::Retracted)
+    }
+
+    /// 等待指定的醒目留言被撤回，已撤回时立即返回
+    pub async fn wait_retracted(&self, message_id: i64) {
+        loop {
+            let notified = self.retracted.notified();
+            tokio::pin!(notified);
+            // 先注册再检查，避免错过检查与等待之间的通知
+            notified.as_mut().enable();
+            if self.is_retracted(message_id) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// 列出醒目留言，按开始时间排序；`include_inactive` 为假时只返回展示期内的留言
+    pub fn list(&self, include_inactive: bool) -> Vec<SuperChatEntry> {
+        let now = now_secs();
+        let mut inner = self.lock();
+        let mut entries: Vec<SuperChatEntry> = inner
+            .entries
+            .values_mut()
+            .map(|entry| {
+                entry.refresh(now);
+                entry.clone()
+            })
+            .filter(|entry| include_inactive || entry.status == SuperChatStatus::Active)
+            .collect();
+        entries.sort_by_key(|entry| (entry.data.start_time, entry.data.message_id));
+        entries
+    }
+}
@@ -1,4 +1,6 @@
+use crate::services::config::TtsConfig;
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
 
 // TTS相关数据结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,76 +12,80 @@ pub struct TtsRequest {
     pub speed: String,
 }
 
-#[derive(Debug, Serialize)]
-pub struct TtsResponse {
-    pub success: bool,
-    pub message: String,
-    pub audio_data: Option<Vec<u8>>, // 直接返回字节数组
-}
-
-#[tauri::command]
-pub async fn text_to_speech(text: String) -> Result<TtsResponse, String> {
-    // 从配置文件读取TTS配置
-    let tts_config = match crate::api::config::load_tts_config().await {
-        Ok(config) => config,
-        Err(e) => {
-            log::error!("加载IndexTTS配置失败: {}", e);
-            return Err(format!("加载IndexTTS配置失败: {}", e));
-        }
-    };
-
+/// 调用TTS接口把文本转换为音频，返回音频字节
+pub async fn synthesize(
+    client: &reqwest::Client,
+    config: &TtsConfig,
+    text: String,
+) -> Result<Vec<u8>, String> {
     let tts_request = TtsRequest {
-        model: tts_config.model.clone(),
+        model: config.model.clone(),
         input: text,
-        voice: tts_config.voice.clone(),
-        response_format: tts_config.response_format.clone(),
-        speed: tts_config.speed.clone(),
+        voice: config.voice.clone(),
+        response_format: config.response_format.clone(),
+        speed: config.speed.clone(),
     };
 
-    // 创建HTTP客户端
-    let client = reqwest::Client::new();
-
     log::info!("发送TTS请求: {:?}", tts_request);
 
-    match client
-        .post(&tts_config.api_url)
+    let response = client
+        .post(&config.api_url)
         .header("Content-Type", "application/json")
-        .header("Authorization", &tts_config.authorization)
+        .header("Authorization", &config.authorization)
         .json(&tts_request)
         .send()
         .await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.bytes().await {
-                    Ok(audio_bytes) => {
-                        log::info!("TTS请求成功，接收到 {} 字节的音频数据", audio_bytes.len());
-
-                        // 直接返回字节数组
-                        Ok(TtsResponse {
-                            success: true,
-                            message: "文本转语音成功".to_string(),
-                            audio_data: Some(audio_bytes.to_vec()),
-                        })
-                    }
-                    Err(e) => {
-                        log::error!("读取音频数据失败: {}", e);
-                        Err(format!("读取音频数据失败: {}", e))
-                    }
-                }
-            } else {
-                let status = response.status();
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "未知错误".to_string());
-                log::error!("TTS请求失败: {} - {}", status, error_text);
-                Err(format!("TTS请求失败: {} - {}", status, error_text))
-            }
-        }
-        Err(e) => {
+        .map_err(|e| {
             log::error!("发送TTS请求失败: {}", e);
-            Err(format!("发送TTS请求失败: {}", e))
-        }
+            format!("发送TTS请求失败: {}", e)
+        })?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "未知错误".to_string());
+        log::error!("TTS请求失败: {} - {}", status, error_text);
+        return Err(format!("TTS请求失败: {} - {}", status, error_text));
+    }
+
+    let audio_bytes = response.bytes().await.map_err(|e| {
+        log::error!("读取音频数据失败: {}", e);
+        format!("读取音频数据失败: {}", e)
+    })?;
+    log::info!("TTS请求成功，接收到 {} 字节的音频数据", audio_bytes.len());
+    Ok(audio_bytes.to_vec())
+}
+
+/// 逐句合成得到的一段音频
+#[derive(Debug, Clone, Serialize)]
+pub struct SpeechChunk {
+    /// 从 0 开始的句子序号
+    pub sequence: u32,
+    pub text: String,
+    /// 合成失败时为空，序号仍然占用，播放端跳过即可
+    pub audio_data: Option<Vec<u8>>,
+}
+
+/// 按到达顺序逐句合成，每合成完一句调用一次 `on_chunk`，返回句子总数
+///
+/// 发送端关闭且剩余句子都合成完后返回。
+pub async fn synthesize_sentences(
+    client: &reqwest::Client,
+    config: &TtsConfig,
+    mut sentences: mpsc::UnboundedReceiver<String>,
+    mut on_chunk: impl FnMut(SpeechChunk) + Send,
+) -> u32 {
+    let mut sequence = 0;
+    while let Some(text) = sentences.recv().await {
+        let audio_data = synthesize(client, config, text.clone()).await.ok();
+        on_chunk(SpeechChunk {
+            sequence,
+            text,
+            audio_data,
+        });
+        sequence += 1;
     }
+    sequence
 }
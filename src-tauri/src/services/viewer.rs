@@ -0,0 +1,423 @@
+//! 观众档案
+//!
+//! 以 `open_id` 为键记录观众的首次/最近出现时间、曾用名、累计送礼、大航海和粉丝牌状态、
+//! 发言次数和来访次数，供提示词构造和前端查询使用。档案保存在本地 SQLite 数据库，
+//! 启动时全部载入内存，更新由独立线程写回。
+
+use crate::core::BilibiliMessage;
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, mpsc};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+
+/// 两次出现间隔超过该毫秒数时视为新的一次来访
+const VISIT_GAP_MS: i64 = 30 * 60 * 1000;
+
+/// 最多保留的曾用名数量
+const MAX_NAME_HISTORY: usize = 10;
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS viewers (
+    open_id TEXT PRIMARY KEY,
+    last_seen INTEGER NOT NULL,
+    gift_value INTEGER NOT NULL,
+    profile TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_viewers_last_seen ON viewers(last_seen);
+"#;
+
+/// 一名观众的档案，时间均为毫秒时间戳，礼物价值单位与 `price` 相同（1/1000 元）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewerProfile {
+    pub open_id: String,
+    pub uname: String,
+    pub uface: String,
+    /// 曾用名，不含当前名字，最近的在后
+    pub name_history: Vec<String>,
+    pub first_seen: i64,
+    pub last_seen: i64,
+    /// 来访次数，间隔超过 30 分钟算一次新的来访
+    pub visits: u32,
+    /// 本次来访开始的时间
+    pub visit_started: i64,
+    /// 上一次来访最后出现的时间，首次来访时为 `None`
+    pub previous_seen: Option<i64>,
+    /// 弹幕条数
+    pub message_count: u64,
+    /// 付费礼物累计价值
+    pub gift_value: i64,
+    pub super_chat_count: u64,
+    /// 醒目留言累计金额（元）
+    pub super_chat_rmb: i64,
+    /// 当前大航海等级，0 表示没有
+    pub guard_level: i64,
+    /// 最近一次上舰的时间
+    pub guard_purchased_at: Option<i64>,
+    pub fans_medal_name: String,
+    pub fans_medal_level: i64,
+}
+
+impl ViewerProfile {
+    /// 是否来访过不止一次，用于“欢迎回来”
+    pub fn is_returning(&self) -> bool {
+        self.visits > 1
+    }
+
+    /// 给大模型看的一句话描述
+    pub fn prompt_summary(&self) -> String {
+        let mut parts = vec![format!("观众「{}」", self.uname)];
+        if self.is_returning() {
+            parts.push(format!("第 {} 次来直播间", self.visits));
+        } else {
+            parts.push("第一次来直播间".to_string());
+        }
+        match self.guard_level {
+            1 => parts.push("总督".to_string()),
+            2 => parts.push("提督".to_string()),
+            3 => parts.push("舰长".to_string()),
+            _ => {}
+        }
+        if !self.fans_medal_name.is_empty() && self.fans_medal_level > 0 {
+            parts.push(format!(
+                "佩戴粉丝牌「{}」{}级",
+                self.fans_medal_name, self.fans_medal_level
+            ));
+        }
+        if self.gift_value > 0 {
+            parts.push(format!(
+                "累计送礼 {:.1} 元",
+                self.gift_value as f64 / 1000.0
+            ));
+        }
+        if self.super_chat_rmb > 0 {
+            parts.push(format!("累计醒目留言 {} 元", self.super_chat_rmb));
+        }
+        if let Some(name) = self.name_history.last() {
+            parts.push(format!("曾用名「{}」", name));
+        }
+        parts.join("，")
+    }
+
+    fn touch(&mut self, uname: &str, uface: &str, now: i64) {
+        if self.first_seen == 0 {
+            self.first_seen = now;
+            self.visits = 1;
+            self.visit_started = now;
+        } else if now - self.last_seen > VISIT_GAP_MS {
+            self.visits += 1;
+            self.visit_started = now;
+            self.previous_seen = Some(self.last_seen);
+        }
+        self.last_seen = now;
+
+        if !uname.is_empty() && uname != self.uname {
+            if !self.uname.is_empty() {
+                let previous = std::mem::take(&mut self.uname);
+                self.name_history
+                    .retain(|name| name != &previous && name != uname);
+                self.name_history.push(previous);
+                if self.name_history.len() > MAX_NAME_HISTORY {
+                    self.name_history.remove(0);
+                }
+            }
+            self.uname = uname.to_string();
+        }
+        if !uface.is_empty() {
+            self.uface = uface.to_string();
+        }
+    }
+
+    fn update_medal(&mut self, wearing: bool, name: &str, level: i64) {
+        if wearing && !name.is_empty() {
+            self.fans_medal_name = name.to_string();
+            self.fans_medal_level = level;
+        }
+    }
+}
+
+enum WriterCommand {
+    Save(Vec<ViewerProfile>),
+    /// 之前提交的档案全部写入后回复
+    Flush(oneshot::Sender<()>),
+}
+
+/// 观众档案登记表
+pub struct ViewerRegistry {
+    profiles: Mutex<HashMap<String, ViewerProfile>>,
+    // 为 `None` 时只保存在内存中
+    writer: Option<Mutex<mpsc::Sender<WriterCommand>>>,
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+impl ViewerRegistry {
+    /// 只保存在内存中的登记表
+    pub fn in_memory() -> Self {
+        Self {
+            profiles: Mutex::new(HashMap::new()),
+            writer: None,
+        }
+    }
+
+    /// 打开档案数据库，载入全部档案并启动写入线程
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| format!("创建档案目录失败: {}", e))?;
+        }
+
+        let conn = Connection::open(path).map_err(|e| format!("打开观众档案失败: {}", e))?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .and_then(|_| conn.execute_batch(SCHEMA))
+            .map_err(|e| format!("初始化观众档案失败: {}", e))?;
+
+        let profiles = load_profiles(&conn).map_err(|e| format!("读取观众档案失败: {}", e))?;
+        log::info!("已载入 {} 份观众档案: {:?}", profiles.len(), path);
+
+        let (writer, receiver) = mpsc::channel::<WriterCommand>();
+        std::thread::Builder::new()
+            .name("viewer-profiles".to_string())
+            .spawn(move || {
+                let mut conn = conn;
+                while let Ok(command) = receiver.recv() {
+                    match command {
+                        WriterCommand::Save(profiles) => {
+                            if let Err(e) = save_profiles(&mut conn, &profiles) {
+                                log::error!("保存观众档案失败: {}", e);
+                            }
+                        }
+                        WriterCommand::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+            .map_err(|e| format!("启动档案写入线程失败: {}", e))?;
+
+        Ok(Self {
+            profiles: Mutex::new(profiles),
+            writer: Some(Mutex::new(writer)),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, ViewerProfile>> {
+        match self.profiles.lock() {
+            Ok(profiles) => profiles,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// 根据一批消息更新档案，返回本批中开始了新来访的老观众
+    pub fn record(&self, messages: &[BilibiliMessage]) -> Vec<ViewerProfile> {
+        let now = now_millis();
+        let mut changed: HashMap<String, ViewerProfile> = HashMap::new();
+        let mut returning = Vec::new();
+
+        {
+            let mut profiles = self.lock();
+            for message in messages {
+                let Some(open_id) = viewer_open_id(message) else {
+                    continue;
+                };
+                let profile =
+                    profiles
+                        .entry(open_id.to_string())
+                        .or_insert_with(|| ViewerProfile {
+                            open_id: open_id.to_string(),
+                            ..Default::default()
+                        });
+                let visits = profile.visits;
+                apply_message(profile, message, now);
+                if profile.visits != visits && profile.is_returning() {
+                    returning.push(profile.clone());
+                }
+                changed.insert(open_id.to_string(), profile.clone());
+            }
+        }
+
+        if !changed.is_empty()
+            && let Some(writer) = &self.writer
+            && let Ok(writer) = writer.lock()
+            && writer
+                .send(WriterCommand::Save(changed.into_values().collect()))
+                .is_err()
+        {
+            log::error!("观众档案写入线程已退出");
+        }
+        returning
+    }
+
+    /// 等待已提交的档案全部写入数据库
+    pub async fn flush(&self) {
+        let (done, wait) = oneshot::channel();
+        let sent = match &self.writer {
+            Some(writer) => writer
+                .lock()
+                .is_ok_and(|writer| writer.send(WriterCommand::Flush(done)).is_ok()),
+            None => false,
+        };
+        if sent {
+            let _ = wait.await;
+        }
+    }
+
+    pub fn get(&self, open_id: &str) -> Option<ViewerProfile> {
+        self.lock().get(open_id).cloned()
+    }
+
+    /// 按最近出现时间或累计送礼排序列出档案
+    pub fn list(&self, order: ViewerOrder, limit: usize) -> Vec<ViewerProfile> {
+        let mut profiles: Vec<ViewerProfile> = self.lock().values().cloned().collect();
+        match order {
+            ViewerOrder::Recent => profiles.sort_by_key(|p| Reverse(p.last_seen)),
+            ViewerOrder::GiftValue => {
+                profiles.sort_by_key(|p| Reverse(p.gift_value + p.super_chat_rmb * 1000))
+            }
+            ViewerOrder::Messages => profiles.sort_by_key(|p| Reverse(p.message_count)),
+        }
+        profiles.truncate(limit);
+        profiles
+    }
+}
+
+/// 档案列表的排序方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ViewerOrder {
+    #[default]
+    Recent,
+    GiftValue,
+    Messages,
+}
+
+fn viewer_open_id(message: &BilibiliMessage) -> Option<&str> {
+    let open_id = match message {
+        BilibiliMessage::Danmaku { data } => &data.open_id,
+        BilibiliMessage::Gift { data } => &data.open_id,
+        BilibiliMessage::SuperChat { data } => &data.open_id,
+        BilibiliMessage::Guard { data } => &data.user_info.open_id,
+        BilibiliMessage::Like { data } => &data.open_id,
+        BilibiliMessage::LiveRoomEnter { data } => &data.open_id,
+        _ => return None,
+    };
+    Some(open_id.as_str()).filter(|id| !id.is_empty())
+}
+
+fn apply_message(profile: &mut ViewerProfile, message: &BilibiliMessage, now: i64) {
+    match message {
+        BilibiliMessage::Danmaku { data } => {
+            profile.touch(&data.uname, &data.uface, now);
+            profile.message_count += 1;
+            profile.guard_level = data.guard_level;
+            profile.update_medal(
+                data.fans_medal_wearing_status,
+                &data.fans_medal_name,
+                data.fans_medal_level,
+            );
+        }
+        BilibiliMessage::Gift { data } => {
+            profile.touch(&data.uname, &data.uface, now);
+            if data.paid {
+                profile.gift_value += data.price.saturating_mul(data.gift_num);
+            }
+            profile.guard_level = data.guard_level;
+            profile.update_medal(
+                data.fans_medal_wearing_status,
+                &data.fans_medal_name,
+                data.fans_medal_level,
+            );
+        }
+        BilibiliMessage::SuperChat { data } => {
+            profile.touch(&data.uname, &data.uface, now);
+            profile.super_chat_count += 1;
+            profile.super_chat_rmb += data.rmb;
+            profile.guard_level = data.guard_level;
+            profile.update_medal(
+                data.fans_medal_wearing_status,
+                &data.fans_medal_name,
+                data.fans_medal_level,
+            );
+        }
+        BilibiliMessage::Guard { data } => {
+            profile.touch(&data.user_info.uname, &data.user_info.uface, now);
+            // 等级数字越小越高，同时持有时取较高的
+            if profile.guard_level == 0 || data.guard_level < profile.guard_level {
+                profile.guard_level = data.guard_level;
+            }
+            profile.guard_purchased_at = Some(now);
+            profile.update_medal(
+                data.fans_medal_wearing_status,
+                &data.fans_medal_name,
+                data.fans_medal_level,
+            );
+        }
+        BilibiliMessage::Like { data } => {
+            profile.touch(&data.uname, &data.uface, now);
+            profile.update_medal(
+                data.fans_medal_wearing_status,
+                &data.fans_medal_name,
+                data.fans_medal_level,
+            );
+        }
+        BilibiliMessage::LiveRoomEnter { data } => {
+            profile.touch(&data.uname, &data.uface, now);
+        }
+        _ => {}
+    }
+}
+
+fn load_profiles(conn: &Connection) -> rusqlite::Result<HashMap<String, ViewerProfile>> {
+    let mut stmt = conn.prepare("SELECT open_id, profile FROM viewers")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+
+    let mut profiles = HashMap::new();
+    for row in rows {
+        let (open_id, profile) = row?;
+        match serde_json::from_str::<ViewerProfile>(&profile) {
+            Ok(profile) => {
+                profiles.insert(open_id, profile);
+            }
+            Err(e) => log::warn!("解析观众档案 {} 失败: {}", open_id, e),
+        }
+    }
+    Ok(profiles)
+}
+
+fn save_profiles(conn: &mut Connection, profiles: &[ViewerProfile]) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for profile in profiles {
+        let json = match serde_json::to_string(profile) {
+            Ok(json) => json,
+            Err(e) => {
+                log::warn!("序列化观众档案失败: {}", e);
+                continue;
+            }
+        };
+        tx.prepare_cached(
+            "INSERT INTO viewers (open_id, last_seen, gift_value, profile) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(open_id) DO UPDATE SET
+                last_seen = excluded.last_seen,
+                gift_value = excluded.gift_value,
+                profile = excluded.profile",
+        )?
+        .execute(params![
+            profile.open_id,
+            profile.last_seen,
+            profile.gift_value,
+            json
+        ])?;
+    }
+    tx.commit()
+}
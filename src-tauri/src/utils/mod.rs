@@ -0,0 +1,11 @@
+//! 通用工具函数
+
+use tokio::time::Instant;
+
+/// 等待到指定时刻，未指定时永远等待，用于 `select!` 中可选的定时分支
+pub async fn sleep_until_optional(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,305 @@
+//! `services::archive` 的测试：写入临时目录中的 SQLite 数据库后查询
+
+use aivtuber_lib::core::BilibiliMessage;
+use aivtuber_lib::services::archive::{ArchiveQuery, ArchivedEvent, EventArchive};
+use rusqlite::Connection;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const SESSION: &str = "test";
+
+/// 每个测试单独的临时目录，结束时删除
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("aivtuber-archive-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        Self(dir)
+    }
+
+    fn db(&self) -> PathBuf {
+        self.0.join("archive.db")
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn fixture(name: &str) -> BilibiliMessage {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/messages")
+        .join(format!("{}.json", name));
+    let json =
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("读取 {:?} 失败: {}", path, e));
+    serde_json::from_str(&json).unwrap_or_else(|e| panic!("解析 {} 失败: {}", name, e))
+}
+
+fn danmaku(open_id: &str, msg: &str) -> BilibiliMessage {
+    let BilibiliMessage::Danmaku { mut data } = fixture("danmaku") else {
+        unreachable!();
+    };
+    data.open_id = open_id.to_string();
+    data.msg = msg.to_string();
+    BilibiliMessage::Danmaku { data }
+}
+
+fn texts(events: &[ArchivedEvent]) -> Vec<&str> {
+    events
+        .iter()
+        .map(|event| match &event.message {
+            BilibiliMessage::Danmaku { data } => data.msg.as_str(),
+            other => other.cmd(),
+        })
+        .collect()
+}
+
+async fn text_query(archive: &EventArchive, text: &str) -> Vec<ArchivedEvent> {
+    archive
+        .query(ArchiveQuery {
+            text: Some(text.to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn appended_messages_can_be_queried() {
+    let dir = TempDir::new("query");
+    let archive = EventArchive::open(dir.db()).unwrap();
+    archive.append(
+        SESSION,
+        &[
+            danmaku("viewer-a", "第一条"),
+            fixture("gift"),
+            danmaku("viewer-b", "第二条"),
+        ],
+    );
+    archive.append("other", &[danmaku("viewer-a", "别的会话")]);
+    archive.flush().await;
+
+    // 默认按 id 倒序返回全部
+    let all = archive.query(ArchiveQuery::default()).await.unwrap();
+    assert_eq!(
+        texts(&all),
+        vec![
+            "别的会话",
+            "第二条",
+            "LIVE_OPEN_PLATFORM_SEND_GIFT",
+            "第一条"
+        ]
+    );
+    assert_eq!(all[1].open_id.as_deref(), Some("viewer-b"));
+    assert_eq!(all[1].room_id, Some(7734200));
+    assert_eq!(all[1].session, SESSION);
+
+    let viewer = archive
+        .query(ArchiveQuery {
+            session: Some(SESSION.to_string()),
+            open_id: Some("viewer-a".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(texts(&viewer), vec!["第一条"]);
+
+    let gifts = archive
+        .query(ArchiveQuery {
+            cmds: Some(vec!["LIVE_OPEN_PLATFORM_SEND_GIFT".to_string()]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(gifts.len(), 1);
+    assert!(matches!(gifts[0].message, BilibiliMessage::Gift { .. }));
+
+    // 时间范围包含起点不包含终点
+    let received_at = all[0].received_at;
+    let range = |since, until| ArchiveQuery {
+        since: Some(since),
+        until: Some(until),
+        ..Default::default()
+    };
+    let hit = archive
+        .query(range(received_at, received_at + 1))
+        .await
+        .unwrap();
+    assert!(hit.iter().any(|event| event.id == all[0].id));
+    let miss = archive
+        .query(range(received_at + 1, i64::MAX))
+        .await
+        .unwrap();
+    assert!(miss.is_empty());
+}
+
+#[tokio::test]
+async fn pages_backwards_with_before_id() {
+    let dir = TempDir::new("paging");
+    let archive = EventArchive::open(dir.db()).unwrap();
+    let messages: Vec<_> = (0..5)
+        .map(|i| danmaku("viewer-a", &format!("弹幕{}", i)))
+        .collect();
+    archive.append(SESSION, &messages);
+    archive.flush().await;
+
+    let mut pages = Vec::new();
+    let mut before_id = None;
+    loop {
+        let page = archive
+            .query(ArchiveQuery {
+                before_id,
+                limit: Some(2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let Some(last) = page.last() else {
+            break;
+        };
+        before_id = Some(last.id);
+        pages.push(texts(&page).join(","));
+    }
+    assert_eq!(pages, vec!["弹幕4,弹幕3", "弹幕2,弹幕1", "弹幕0"]);
+
+    // 条数被限制在 1 到 1000 之间
+    let one = archive
+        .query(ArchiveQuery {
+            limit: Some(0),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(one.len(), 1);
+}
+
+#[tokio::test]
+async fn detail_tables_are_filled() {
+    let dir = TempDir::new("details");
+    let archive = EventArchive::open(dir.db()).unwrap();
+    archive.append(
+        SESSION,
+        &[
+            danmaku("viewer-a", "明细"),
+            fixture("gift_combo"),
+            fixture("super_chat"),
+            fixture("guard"),
+            fixture("like"),
+        ],
+    );
+    archive.flush().await;
+
+    let conn = Connection::open(dir.db()).unwrap();
+    let count = |table: &str| -> i64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    };
+    assert_eq!(count("events"), 5);
+    for table in ["danmaku", "gifts", "super_chats", "guards"] {
+        assert_eq!(count(table), 1, "{} 应当有一条明细", table);
+    }
+
+    let msg: String = conn
+        .query_row(
+            "SELECT d.msg FROM danmaku d JOIN events e ON e.id = d.event_id
+             WHERE e.cmd = 'LIVE_OPEN_PLATFORM_DM'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(msg, "明细");
+    let (gift_num, combo_id): (i64, Option<String>) = conn
+        .query_row("SELECT gift_num, combo_id FROM gifts", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!(gift_num, 1);
+    assert!(combo_id.is_some_and(|id| id.starts_with("gift:combo_id:")));
+    let (message_id, rmb): (i64, i64) = conn
+        .query_row("SELECT message_id, rmb FROM super_chats", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!((message_id, rmb), (1049213, 30));
+    let (guard_level, guard_unit): (i64, String) = conn
+        .query_row("SELECT guard_level, guard_unit FROM guards", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!((guard_level, guard_unit.as_str()), (3, "月"));
+}
+
+#[tokio::test]
+async fn text_search_uses_trigram_or_like() {
+    let dir = TempDir::new("search");
+    let archive = EventArchive::open(dir.db()).unwrap();
+    archive.append(
+        SESSION,
+        &[
+            danmaku("viewer-a", "今天唱什么歌"),
+            danmaku("viewer-b", "主播唱歌好听"),
+            danmaku("viewer-c", "100%_好评"),
+            fixture("gift"),
+        ],
+    );
+    archive.flush().await;
+
+    // 3 个字及以上走 FTS5 trigram 索引，可以匹配中文子串
+    assert_eq!(
+        texts(&text_query(&archive, "唱什么").await),
+        vec!["今天唱什么歌"]
+    );
+    // 输入被当作整体短语，不会被解析为 FTS 语法
+    assert!(text_query(&archive, "唱 OR 歌").await.is_empty());
+    assert!(text_query(&archive, "\"唱歌\"").await.is_empty());
+
+    // 少于 3 个字改用 LIKE
+    assert_eq!(
+        texts(&text_query(&archive, "唱").await),
+        vec!["主播唱歌好听", "今天唱什么歌"]
+    );
+    assert_eq!(
+        texts(&text_query(&archive, "唱歌").await),
+        vec!["主播唱歌好听"]
+    );
+    // LIKE 的通配符按字面匹配
+    assert_eq!(texts(&text_query(&archive, "%_").await), vec!["100%_好评"]);
+    assert_eq!(text_query(&archive, "_").await.len(), 1);
+
+    // 全文检索只返回弹幕，空白查询不作为条件
+    assert_eq!(text_query(&archive, "  ").await.len(), 4);
+}
+
+#[tokio::test]
+async fn writer_thread_drains_and_exits_when_dropped() {
+    let dir = TempDir::new("shutdown");
+    let wal = dir.0.join("archive.db-wal");
+    {
+        let archive = EventArchive::open(dir.db()).unwrap();
+        let clone = archive.clone();
+        archive.append(SESSION, &[danmaku("viewer-a", "关闭前写入")]);
+        drop(archive);
+        // 还有克隆存在时写入线程继续工作
+        clone.append(SESSION, &[danmaku("viewer-a", "克隆写入")]);
+        assert!(wal.exists());
+    }
+
+    // 所有句柄释放后，写入线程写完排队的消息再退出，关闭连接时删除 WAL 文件
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while wal.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("写入线程应当退出并关闭数据库");
+
+    let archive = EventArchive::open(dir.db()).unwrap();
+    let events = archive.query(ArchiveQuery::default()).await.unwrap();
+    assert_eq!(texts(&events), vec!["克隆写入", "关闭前写入"]);
+}
@@ -0,0 +1,402 @@
+//! `services::bilibili` 客户端的测试：连接本地的模拟开放平台
+
+use aivtuber_lib::core::{BilibiliMessage, ConnectionEvent};
+use aivtuber_lib::services::bilibili::{
+    BilibiliClient, BilibiliConfig, BilibiliError, ReconnectPolicy,
+};
+use aivtuber_lib::services::heartbeat::HeartbeatConfig;
+use aivtuber_lib::services::mock::{MockConfig, MockScriptItem, MockServer};
+use aivtuber_lib::services::pipeline::{InboundReceiver, PipelineConfig};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+fn fixture(name: &str) -> Value {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/messages")
+        .join(format!("{}.json", name));
+    let json =
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("读取 {:?} 失败: {}", path, e));
+    serde_json::from_str(&json).unwrap_or_else(|e| panic!("解析 {} 失败: {}", name, e))
+}
+
+/// 以 `fixture` 为模板、`msg_id` 为标记的一条脚本消息
+fn script_item(fixture_name: &str, msg_id: String) -> MockScriptItem {
+    let mut message = fixture(fixture_name);
+    message["data"]["msg_id"] = Value::String(msg_id);
+    MockScriptItem {
+        delay_ms: 0,
+        message,
+        compressed: false,
+    }
+}
+
+fn msg_id(message: &BilibiliMessage) -> &str {
+    match message {
+        BilibiliMessage::Danmaku { data } => &data.msg_id,
+        BilibiliMessage::Gift { data } => &data.msg_id,
+        BilibiliMessage::Like { data } => &data.msg_id,
+        other => panic!("意外的消息: {}", other.cmd()),
+    }
+}
+
+async fn start_mock(config: MockConfig) -> (MockServer, BilibiliConfig) {
+    let mut server = MockServer::new();
+    server.start(0, config.clone()).await.unwrap();
+    let client_config = BilibiliConfig {
+        id_code: config.id_code,
+        app_id: config.app_id,
+        access_key: config.access_key,
+        access_secret: config.access_secret,
+        host: server.host().unwrap(),
+        reconnect: ReconnectPolicy::default(),
+        record_path: None,
+        replay: None,
+        pipeline: PipelineConfig::default(),
+        heartbeat: HeartbeatConfig::default(),
+    };
+    (server, client_config)
+}
+
+/// 等待满足条件的连接事件，返回它之前收到的所有事件
+async fn wait_for_event(
+    events: &mut broadcast::Receiver<ConnectionEvent>,
+    matches: impl Fn(&ConnectionEvent) -> bool,
+) -> Vec<ConnectionEvent> {
+    let mut seen = Vec::new();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let event = events.recv().await.unwrap();
+            let done = matches(&event);
+            seen.push(event);
+            if done {
+                return;
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("等待连接事件超时，已收到: {:?}", seen));
+    seen
+}
+
+/// 按顺序取出 `count` 条消息的 msg_id
+async fn take_ids(receiver: &mut InboundReceiver, count: usize) -> Vec<String> {
+    let mut ids = Vec::new();
+    while ids.len() < count {
+        let message = tokio::time::timeout(Duration::from_secs(10), receiver.recv())
+            .await
+            .expect("等待消息超时")
+            .expect("消息通道已关闭");
+        ids.push(msg_id(&message).to_string());
+    }
+    ids
+}
+
+#[tokio::test]
+async fn app_start_must_be_signed_with_the_secret() {
+    let (_server, mut config) = start_mock(MockConfig::default()).await;
+    config.access_secret = "wrong_secret".to_string();
+    let mut client = BilibiliClient::new(config);
+
+    let Err(err) = client.connect().await else {
+        panic!("签名错误时不应当连接成功");
+    };
+
+    assert!(
+        matches!(
+            err,
+            BilibiliError::Auth {
+                code: Some(4002),
+                ..
+            }
+        ),
+        "{}",
+        err
+    );
+    assert!(matches!(
+        client.handle().connection_state(),
+        ConnectionEvent::Closed { .. }
+    ));
+}
+
+#[tokio::test]
+async fn authenticates_and_exchanges_heartbeats() {
+    let (_server, mut config) = start_mock(MockConfig {
+        script: Vec::new(),
+        ..Default::default()
+    })
+    .await;
+    config.heartbeat = HeartbeatConfig {
+        ws_interval_ms: 50,
+        app_interval_ms: 50,
+        ..Default::default()
+    };
+    let mut client = BilibiliClient::new(config);
+    let mut events = client.subscribe_events();
+    let _receiver = client.connect().await.unwrap();
+
+    let seen = wait_for_event(&mut events, |event| {
+        matches!(event, ConnectionEvent::Authenticated { .. })
+    })
+    .await;
+    assert!(matches!(seen[0], ConnectionEvent::Connecting { .. }));
+    assert_eq!(client.handle().room_id().await, Some(1));
+
+    // WebSocket心跳收到人气值回复，应用心跳通过签名校验
+    let handle = client.handle();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let stats = handle.heartbeat_stats();
+            if stats.ws_replies >= 2 && stats.app_successes >= 2 {
+                assert_eq!(stats.ws_missed, 0);
+                assert_eq!(stats.app_failures, 0);
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("心跳应当得到回复");
+
+    client.close().await.unwrap();
+    assert!(matches!(
+        handle.connection_state(),
+        ConnectionEvent::Closed { .. }
+    ));
+}
+
+#[tokio::test]
+async fn scripted_messages_are_delivered_in_order() {
+    let mut compressed = script_item("gift", "gift-1".to_string());
+    compressed.compressed = true;
+    let script = vec![
+        script_item("danmaku", "dm-1".to_string()),
+        compressed,
+        script_item("like", "like-1".to_string()),
+    ];
+    let (_server, config) = start_mock(MockConfig {
+        script,
+        ..Default::default()
+    })
+    .await;
+    let mut client = BilibiliClient::new(config);
+    let mut receiver = client.connect().await.unwrap();
+
+    assert_eq!(
+        take_ids(&mut receiver, 3).await,
+        vec!["dm-1", "gift-1", "like-1"]
+    );
+
+    // 关闭后消息通道随之关闭
+    client.close().await.unwrap();
+    let rest = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("关闭后消息通道应当关闭");
+    assert!(rest.is_none());
+}
+
+#[tokio::test]
+async fn reconnects_after_the_server_closes_the_socket() {
+    let (_server, mut config) = start_mock(MockConfig {
+        script: vec![script_item("danmaku", "dm-1".to_string())],
+        close_after_ms: Some(300),
+        ..Default::default()
+    })
+    .await;
+    config.reconnect = ReconnectPolicy {
+        initial_delay: Duration::from_millis(20),
+        max_delay: Duration::from_millis(100),
+        max_attempts: None,
+    };
+    let mut client = BilibiliClient::new(config);
+    let mut events = client.subscribe_events();
+    let mut receiver = client.connect().await.unwrap();
+
+    wait_for_event(&mut events, |event| {
+        matches!(event, ConnectionEvent::Authenticated { .. })
+    })
+    .await;
+    let seen = wait_for_event(&mut events, |event| {
+        matches!(event, ConnectionEvent::Authenticated { .. })
+    })
+    .await;
+    assert!(
+        seen.iter()
+            .any(|event| matches!(event, ConnectionEvent::Reconnecting { attempt: 1, .. })),
+        "{:?}",
+        seen
+    );
+
+    // 重连后沿用原来的游戏会话，服务端在新连接上重新推送脚本
+    assert_eq!(take_ids(&mut receiver, 2).await, vec!["dm-1", "dm-1"]);
+
+    client.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn giving_up_stops_heartbeats_and_ends_the_game() {
+    let (server, mut config) = start_mock(MockConfig {
+        script: Vec::new(),
+        close_after_ms: Some(200),
+        ..Default::default()
+    })
+    .await;
+    config.heartbeat = HeartbeatConfig {
+        app_interval_ms: 50,
+        ..Default::default()
+    };
+    // 不允许重连，连接第一次断开就放弃
+    config.reconnect.max_attempts = Some(0);
+    let mut client = BilibiliClient::new(config);
+    let mut events = client.subscribe_events();
+    let mut receiver = client.connect().await.unwrap();
+    assert!(server.active_game().await.is_some());
+
+    let seen = wait_for_event(&mut events, |event| {
+        matches!(event, ConnectionEvent::Closed { .. })
+    })
+    .await;
+    let Some(ConnectionEvent::Closed { reason, .. }) = seen.last() else {
+        unreachable!();
+    };
+    assert!(reason.contains("重连次数已达上限(0)"), "{}", reason);
+
+    assert_eq!(server.active_game().await, None, "放弃时应当结束游戏会话");
+    let handle = client.handle();
+    let successes = handle.heartbeat_stats().app_successes;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(
+        handle.heartbeat_stats().app_successes,
+        successes,
+        "放弃后不应当继续发送应用心跳"
+    );
+    let rest = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("放弃后消息通道应当关闭");
+    assert!(rest.is_none());
+}
+
+#[tokio::test]
+async fn reconnect_backs_off_until_max_attempts() {
+    let (mut server, mut config) = start_mock(MockConfig {
+        script: Vec::new(),
+        close_after_ms: Some(200),
+        ..Default::default()
+    })
+    .await;
+    config.reconnect = ReconnectPolicy {
+        initial_delay: Duration::from_millis(20),
+        max_delay: Duration::from_millis(40),
+        max_attempts: Some(3),
+    };
+    let mut client = BilibiliClient::new(config);
+    let mut events = client.subscribe_events();
+    let _receiver = client.connect().await.unwrap();
+    wait_for_event(&mut events, |event| {
+        matches!(event, ConnectionEvent::Authenticated { .. })
+    })
+    .await;
+    // 服务停止后不再接受新连接，每次重连都会失败
+    server.stop();
+
+    let seen = wait_for_event(&mut events, |event| {
+        matches!(event, ConnectionEvent::Closed { .. })
+    })
+    .await;
+    let delays: Vec<_> = seen
+        .iter()
+        .filter_map(|event| match event {
+            ConnectionEvent::Reconnecting {
+                attempt, delay_ms, ..
+            } => Some((*attempt, *delay_ms)),
+            _ => None,
+        })
+        .collect();
+    let attempts: Vec<_> = delays.iter().map(|(attempt, _)| *attempt).collect();
+    assert_eq!(attempts, vec![1, 2, 3]);
+    // 退避时间翻倍后不超过上限，随机抖动只会缩短到一半
+    assert!((10..=20).contains(&delays[0].1), "{:?}", delays);
+    assert!(
+        delays[1..]
+            .iter()
+            .all(|(_, delay)| (20..=40).contains(delay)),
+        "{:?}",
+        delays
+    );
+    let Some(ConnectionEvent::Closed { reason, .. }) = seen.last() else {
+        unreachable!();
+    };
+    assert!(reason.contains("重连次数已达上限(3)"), "{}", reason);
+}
+
+#[tokio::test]
+async fn flood_is_bounded_without_losing_gifts() {
+    // 200 条点赞之后是 500 条弹幕，每 50 条夹一个礼物，消费端在此期间一条都不读
+    let mut script = Vec::new();
+    let mut gifts = Vec::new();
+    for i in 0..700 {
+        if i % 50 == 0 {
+            gifts.push(format!("gift-{}", i));
+            script.push(script_item("gift", format!("gift-{}", i)));
+        }
+        if i < 200 {
+            script.push(script_item("like", format!("like-{}", i)));
+        } else {
+            script.push(script_item("danmaku", format!("dm-{}", i)));
+        }
+    }
+    let (_server, mut config) = start_mock(MockConfig {
+        script,
+        ..Default::default()
+    })
+    .await;
+    config.pipeline = PipelineConfig {
+        capacity: 64,
+        low_priority_limit: 16,
+        ..Default::default()
+    };
+    let mut client = BilibiliClient::new(config);
+    let mut receiver = client.connect().await.unwrap();
+    let stats = client.handle().pipeline_stats();
+
+    // 点赞在积压 16 条后开始丢弃，弹幕在队列满 64 条后开始丢弃
+    let expected_dropped = (200 - 16) + (500 - 48);
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while stats.snapshot().dropped < expected_dropped {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("洪泛消息应当在入站队列被丢弃");
+
+    let mut received = Vec::new();
+    while let Ok(Some(message)) =
+        tokio::time::timeout(Duration::from_millis(200), receiver.recv()).await
+    {
+        received.push(msg_id(&message).to_string());
+    }
+
+    assert_eq!(stats.snapshot().dropped, expected_dropped);
+    let received_gifts: Vec<_> = received
+        .iter()
+        .filter(|id| id.starts_with("gift-"))
+        .cloned()
+        .collect();
+    assert_eq!(received_gifts, gifts, "礼物一条都不能丢");
+    // 保留下来的是最早到达的消息，且与礼物保持到达顺序
+    let mut expected: Vec<String> = Vec::new();
+    for i in 0..700 {
+        if i % 50 == 0 {
+            expected.push(format!("gift-{}", i));
+        }
+        if i < 16 {
+            expected.push(format!("like-{}", i));
+        } else if (200..248).contains(&i) {
+            expected.push(format!("dm-{}", i));
+        }
+    }
+    assert_eq!(received, expected);
+
+    client.close().await.unwrap();
+}
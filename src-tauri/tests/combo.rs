@@ -0,0 +1,138 @@
+//! `services::combo` 的连击聚合测试
+
+use aivtuber_lib::core::{BilibiliMessage, GiftData};
+use aivtuber_lib::services::combo::{ComboAggregator, ComboConfig, run_combo_aggregator};
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+
+fn gift(name: &str, open_id: &str) -> GiftData {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/messages")
+        .join(format!("{}.json", name));
+    let json =
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("读取 {:?} 失败: {}", path, e));
+    match serde_json::from_str(&json).unwrap() {
+        BilibiliMessage::Gift { mut data } => {
+            data.open_id = open_id.to_string();
+            data
+        }
+        other => panic!("{} 不是礼物消息: {}", name, other.cmd()),
+    }
+}
+
+fn aggregator() -> ComboAggregator {
+    ComboAggregator::new(ComboConfig {
+        enabled: true,
+        default_timeout_ms: 5000,
+    })
+}
+
+#[test]
+fn gifts_from_two_viewers_are_kept_apart() {
+    // 开放平台的 uid 都是 0，只能靠 open_id 区分
+    let mut aggregator = aggregator();
+    let now = Instant::now();
+    let first = gift("gift", "viewer-a");
+    let second = gift("gift", "viewer-b");
+    assert_eq!(first.uid, second.uid);
+    aggregator.feed(&first, now);
+    aggregator.feed(&second, now);
+    aggregator.feed(&first, now);
+
+    let mut finished = aggregator.flush();
+    finished.sort_by(|a, b| a.open_id.cmp(&b.open_id));
+    let keys: Vec<_> = finished.iter().map(|s| s.combo_key.as_str()).collect();
+    assert_eq!(keys, vec!["viewer-a:31036", "viewer-b:31036"]);
+    assert_eq!(finished[0].events, 2);
+    assert_eq!(finished[0].total_num, 2);
+    assert_eq!(finished[0].total_value, 200);
+    assert_eq!(finished[1].events, 1);
+}
+
+#[test]
+fn combo_id_takes_precedence_over_the_fallback_key() {
+    let mut aggregator = aggregator();
+    let now = Instant::now();
+    let combo = gift("gift_combo", "viewer-a");
+    let combo_id = combo.combo_info.as_ref().unwrap().combo_id.clone();
+    aggregator.feed(&combo, now);
+    aggregator.feed(&combo, now);
+    // 同一观众没有 combo_id 的礼物走后备键，不并入连击
+    let mut plain = combo.clone();
+    plain.combo_info = None;
+    aggregator.feed(&plain, now);
+
+    let mut finished = aggregator.flush();
+    finished.sort_by_key(|summary| summary.events);
+    assert_eq!(finished.len(), 2);
+    assert_eq!(finished[0].combo_key, format!("viewer-a:{}", plain.gift_id));
+    assert_eq!(finished[1].combo_key, combo_id);
+    assert_eq!(finished[1].events, 2);
+
+    // combo_id 为空时同样走后备键
+    let mut empty = plain;
+    empty.combo_info = combo.combo_info.clone();
+    empty.combo_info.as_mut().unwrap().combo_id.clear();
+    aggregator.feed(&empty, now);
+    assert_eq!(
+        aggregator.flush()[0].combo_key,
+        format!("viewer-a:{}", empty.gift_id)
+    );
+}
+
+#[test]
+fn combos_finish_after_their_timeout() {
+    let mut aggregator = aggregator();
+    let start = Instant::now();
+    // 连击礼物自带 3 秒超时，普通礼物使用默认的 5 秒
+    aggregator.feed(&gift("gift_combo", "viewer-a"), start);
+    aggregator.feed(&gift("gift", "viewer-b"), start);
+    assert_eq!(
+        aggregator.next_deadline(),
+        Some(start + Duration::from_secs(3))
+    );
+
+    // 超时前再次收到同一连击的礼物会重新计时
+    aggregator.feed(
+        &gift("gift_combo", "viewer-a"),
+        start + Duration::from_secs(2),
+    );
+    assert!(aggregator.expire(start + Duration::from_secs(3)).is_empty());
+
+    let finished = aggregator.expire(start + Duration::from_secs(5));
+    assert_eq!(finished.len(), 2);
+    assert!(
+        finished
+            .iter()
+            .any(|s| s.open_id == "viewer-a" && s.events == 2)
+    );
+    assert!(
+        aggregator
+            .expire(start + Duration::from_secs(60))
+            .is_empty()
+    );
+    assert_eq!(aggregator.next_deadline(), None);
+}
+
+#[tokio::test]
+async fn closing_the_channel_flushes_pending_combos() {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    for open_id in ["viewer-a", "viewer-b"] {
+        sender
+            .send(BilibiliMessage::Gift {
+                data: gift("gift", open_id),
+            })
+            .unwrap();
+    }
+    drop(sender);
+
+    let mut finished = Vec::new();
+    run_combo_aggregator(receiver, ComboConfig::default(), |summary| {
+        finished.push(summary)
+    })
+    .await;
+
+    assert_eq!(finished.len(), 2);
+    assert!(finished.iter().all(|summary| summary.events == 1));
+}
@@ -0,0 +1,66 @@
+//! `core::proto` 的解析诊断计数测试
+//!
+//! 计数是全局的，放在单独的测试程序中且只有一个测试，才能断言准确的增量。
+
+use aivtuber_lib::core::{BilibiliMessage, ParseDiagnostics};
+use serde_json::Value;
+use std::path::PathBuf;
+
+fn fixture(name: &str) -> Value {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/messages")
+        .join(format!("{}.json", name));
+    let json =
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("读取 {:?} 失败: {}", path, e));
+    serde_json::from_str(&json).unwrap()
+}
+
+/// 解析一条载荷，返回解析结果和 `fields_defaulted` 的增量
+fn parse(payload: Value) -> (BilibiliMessage, u64) {
+    let before = ParseDiagnostics::global().snapshot().fields_defaulted;
+    let message = serde_json::from_value(payload).unwrap();
+    let after = ParseDiagnostics::global().snapshot().fields_defaulted;
+    (message, after - before)
+}
+
+#[test]
+fn missing_fields_are_counted_as_defaulted() {
+    let (_, defaulted) = parse(fixture("danmaku"));
+    assert_eq!(defaulted, 0, "完整的载荷不应计入回退");
+
+    // 去掉一个必有字段
+    let mut payload = fixture("danmaku");
+    payload["data"].as_object_mut().unwrap().remove("uface");
+    let (message, defaulted) = parse(payload);
+    let BilibiliMessage::Danmaku { data } = message else {
+        panic!("缺少字段不应导致整条消息解析失败");
+    };
+    assert_eq!(data.uface, "");
+    assert_eq!(data.msg, "晚上好呀");
+    assert_eq!(defaulted, 1);
+
+    // `Option` 字段本来就可以缺失
+    let mut payload = fixture("danmaku");
+    payload["data"].as_object_mut().unwrap().remove("union_id");
+    let (_, defaulted) = parse(payload);
+    assert_eq!(defaulted, 0);
+
+    // 嵌套结构整体缺失时只计一次
+    let mut payload = fixture("gift");
+    payload["data"]
+        .as_object_mut()
+        .unwrap()
+        .remove("anchor_info");
+    let (message, defaulted) = parse(payload);
+    let BilibiliMessage::Gift { data } = message else {
+        panic!("应解析为 Gift");
+    };
+    assert_eq!(data.anchor_info.uname, "");
+    assert_eq!(defaulted, 1);
+
+    // 值为 null 与缺失一样计入
+    let mut payload = fixture("danmaku");
+    payload["data"]["uface"] = Value::Null;
+    let (_, defaulted) = parse(payload);
+    assert_eq!(defaulted, 1);
+}
@@ -0,0 +1,149 @@
+//! `services::memory` 的摘要任务测试
+
+use aivtuber_lib::services::config::OpenAIConfig;
+use aivtuber_lib::services::memory::{ConversationKey, ConversationStore, MemoryConfig, summarize};
+use aivtuber_lib::services::openai::SamplingParams;
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+use warp::Filter;
+
+fn key() -> ConversationKey {
+    ConversationKey {
+        session: "test".to_string(),
+        open_id: None,
+    }
+}
+
+/// 每轮对话都超出预算，只保留最近两条
+fn config() -> MemoryConfig {
+    MemoryConfig {
+        max_tokens: 1,
+        ..Default::default()
+    }
+}
+
+fn chat(store: &ConversationStore, round: usize) -> bool {
+    store.append(
+        &key(),
+        format!("问题{}", round),
+        format!("回答{}", round),
+        &config(),
+    )
+}
+
+#[test]
+fn summary_replaces_evicted_turns() {
+    let store = ConversationStore::default();
+    assert!(!chat(&store, 1));
+    assert!(chat(&store, 2));
+
+    let job = store.take_for_summary(&key()).unwrap();
+    assert_eq!(job.turns.len(), 2);
+    assert!(
+        store.take_for_summary(&key()).is_none(),
+        "同时只有一个摘要任务"
+    );
+    store.finish_summary(job, Ok("摘要".to_string()));
+
+    let snapshot = store.get(&key()).unwrap();
+    assert_eq!(snapshot.summary.as_deref(), Some("摘要"));
+    assert_eq!(snapshot.pending_summary, 0);
+}
+
+#[test]
+fn failed_summary_puts_turns_back() {
+    let store = ConversationStore::default();
+    chat(&store, 1);
+    chat(&store, 2);
+
+    let job = store.take_for_summary(&key()).unwrap();
+    chat(&store, 3);
+    store.finish_summary(job, Err("超时".to_string()));
+
+    let job = store.take_for_summary(&key()).unwrap();
+    let contents: Vec<_> = job.turns.iter().map(|turn| turn.content.as_str()).collect();
+    assert_eq!(contents, vec!["问题1", "回答1", "问题2", "回答2"]);
+}
+
+#[test]
+fn stale_summary_after_reset_is_discarded() {
+    let store = ConversationStore::default();
+    chat(&store, 1);
+    chat(&store, 2);
+    let stale = store.take_for_summary(&key()).unwrap();
+
+    // 摘要期间对话被重置，新对话开始了自己的摘要任务
+    assert_eq!(store.reset("test", None), 1);
+    chat(&store, 3);
+    chat(&store, 4);
+    let current = store.take_for_summary(&key()).unwrap();
+
+    store.finish_summary(stale, Ok("旧摘要".to_string()));
+    let snapshot = store.get(&key()).unwrap();
+    assert_eq!(snapshot.summary, None);
+    assert!(
+        store.take_for_summary(&key()).is_none(),
+        "旧任务不能结束新任务"
+    );
+
+    store.finish_summary(current, Ok("新摘要".to_string()));
+    let snapshot = store.get(&key()).unwrap();
+    assert_eq!(snapshot.summary.as_deref(), Some("新摘要"));
+}
+
+#[tokio::test]
+async fn summarize_ignores_reply_limits() {
+    // 代替聊天补全接口，记录请求体并返回固定的摘要
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+    let route = warp::post()
+        .and(warp::body::json())
+        .map(move |request: Value| {
+            recorded.lock().unwrap().push(request);
+            warp::reply::json(&json!({
+                "id": "chatcmpl-test",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "test-model",
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": " 观众问了两个问题。\n" },
+                    "finish_reason": "stop",
+                }],
+                "usage": { "prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2 },
+            }))
+        });
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut extra = serde_json::Map::new();
+    extra.insert("max_completion_tokens".to_string(), json!(20));
+    let config = OpenAIConfig {
+        api_url: format!("http://{}/v1/chat/completions", addr),
+        api_key: "test".to_string(),
+        model: "test-model".to_string(),
+        sampling: SamplingParams {
+            temperature: Some(0.5),
+            max_tokens: Some(20),
+            stop: Some(vec!["。".to_string()]),
+            extra,
+            ..Default::default()
+        },
+    };
+    let store = ConversationStore::default();
+    chat(&store, 1);
+    chat(&store, 2);
+    let job = store.take_for_summary(&key()).unwrap();
+
+    let summary = summarize(&reqwest::Client::new(), &config, &job, 300)
+        .await
+        .unwrap();
+
+    assert_eq!(summary, "观众问了两个问题。");
+    let requests = requests.lock().unwrap();
+    let request = requests[0].as_object().unwrap();
+    assert_eq!(request["temperature"], json!(0.5));
+    for field in ["max_tokens", "stop", "max_completion_tokens"] {
+        assert!(!request.contains_key(field), "摘要请求不应包含 {}", field);
+    }
+}
@@ -0,0 +1,289 @@
+//! `services::openai` 流式对话的测试
+//!
+//! 用本地的 warp 服务代替聊天补全接口，按给定的切分方式逐块发送 `data:` 事件。
+
+use aivtuber_lib::services::chat_stream::ChatStreamRegistry;
+use aivtuber_lib::services::config::OpenAIConfig;
+use aivtuber_lib::services::openai::{OpenAIMessage, SamplingParams, chat_completion_stream};
+use bytes::Bytes;
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Notify, oneshot};
+use warp::Filter;
+use warp::http::{Response, StatusCode};
+use warp::hyper::Body;
+
+/// 代替聊天补全接口的本地服务，每个请求按顺序发送同样的数据块
+struct StandIn {
+    url: String,
+    /// 收到的请求体
+    requests: Arc<Mutex<Vec<Value>>>,
+    /// 客户端断开连接时触发，只在 `hold_open` 时有效
+    closed: Option<oneshot::Receiver<()>>,
+}
+
+impl StandIn {
+    /// `hold_open` 为真时发送完数据块后不结束响应，只定期发送注释行，直到客户端断开
+    async fn start(status: StatusCode, chunks: Vec<Vec<u8>>, hold_open: bool) -> Self {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let (closed_tx, closed_rx) = oneshot::channel();
+        let closed_tx = Arc::new(Mutex::new(Some(closed_tx)));
+
+        let recorded = requests.clone();
+        let route = warp::post()
+            .and(warp::body::json())
+            .map(move |request: Value| {
+                recorded.lock().unwrap().push(request);
+                let (mut sender, body) = Body::channel();
+                let chunks = chunks.clone();
+                let closed_tx = closed_tx.clone();
+                tokio::spawn(async move {
+                    for chunk in chunks {
+                        // 每块单独发出，保证客户端按给定的切分方式收到
+                        tokio::time::sleep(Duration::from_millis(2)).await;
+                        if sender.send_data(Bytes::from(chunk)).await.is_err() {
+                            return;
+                        }
+                    }
+                    if !hold_open {
+                        return;
+                    }
+                    loop {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        if sender.send_data(Bytes::from(": ping\n\n")).await.is_err() {
+                            break;
+                        }
+                    }
+                    if let Some(closed_tx) = closed_tx.lock().unwrap().take() {
+                        let _ = closed_tx.send(());
+                    }
+                });
+                Response::builder()
+                    .status(status)
+                    .header("Content-Type", "text/event-stream")
+                    .body(body)
+                    .unwrap()
+            });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        Self {
+            url: format!("http://{}/v1/chat/completions", addr),
+            requests,
+            closed: hold_open.then_some(closed_rx),
+        }
+    }
+
+    fn config(&self) -> OpenAIConfig {
+        OpenAIConfig {
+            api_url: self.url.clone(),
+            api_key: "test".to_string(),
+            model: "test-model".to_string(),
+            sampling: SamplingParams::default(),
+        }
+    }
+}
+
+fn user_message(content: &str) -> Vec<OpenAIMessage> {
+    vec![OpenAIMessage {
+        role: "user".to_string(),
+        content: content.to_string(),
+    }]
+}
+
+fn delta_event(content: &str) -> String {
+    let chunk = json!({
+        "object": "chat.completion.chunk",
+        "choices": [{ "index": 0, "delta": { "content": content }, "finish_reason": null }],
+    });
+    format!("data: {}\n\n", chunk)
+}
+
+/// 把字节按固定长度切块
+fn split_every(bytes: &[u8], size: usize) -> Vec<Vec<u8>> {
+    bytes.chunks(size).map(<[u8]>::to_vec).collect()
+}
+
+/// 运行一次流式请求，返回结果和依次收到的增量内容
+async fn stream(stand_in: &StandIn) -> (Result<String, String>, Vec<String>) {
+    let mut deltas = Vec::new();
+    let result = chat_completion_stream(
+        &reqwest::Client::new(),
+        &stand_in.config(),
+        user_message("你好"),
+        |delta| deltas.push(delta.to_string()),
+    )
+    .await;
+    (result, deltas)
+}
+
+#[tokio::test]
+async fn chunks_split_mid_line_and_mid_utf8() {
+    let body = [
+        ": keep-alive\n\n".to_string(),
+        delta_event("晚上好，"),
+        "event: message\r\ndata: ".to_string()
+            + &json!({ "choices": [{ "delta": { "content": "今天聊点什么？" } }] }).to_string()
+            + "\r\n\r\n",
+        delta_event(""),
+        "data: [DONE]\n\n".to_string(),
+    ]
+    .concat();
+    // 每块 5 字节，既会切开行也会切开多字节字符
+    let chunks = split_every(body.as_bytes(), 5);
+    assert!(
+        chunks
+            .iter()
+            .any(|chunk| std::str::from_utf8(chunk).is_err()),
+        "切块应当包含被切开的多字节字符"
+    );
+    let stand_in = StandIn::start(StatusCode::OK, chunks, false).await;
+
+    let (result, deltas) = stream(&stand_in).await;
+
+    assert_eq!(result.unwrap(), "晚上好，今天聊点什么？");
+    assert_eq!(deltas, vec!["晚上好，", "今天聊点什么？"]);
+    let requests = stand_in.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["stream"], json!(true));
+    assert_eq!(requests[0]["model"], json!("test-model"));
+}
+
+#[tokio::test]
+async fn multi_line_data_is_joined() {
+    // 同一事件的 JSON 分在两行 data 中
+    let body = concat!(
+        "data: {\"choices\": [{\"delta\":\n",
+        "data: {\"content\": \"多行\"}}]}\n",
+        "\n",
+        "data: [DONE]\n",
+        "\n",
+    );
+    let stand_in = StandIn::start(StatusCode::OK, split_every(body.as_bytes(), 16), false).await;
+
+    let (result, deltas) = stream(&stand_in).await;
+
+    assert_eq!(result.unwrap(), "多行");
+    assert_eq!(deltas, vec!["多行"]);
+}
+
+#[tokio::test]
+async fn missing_done_keeps_the_content() {
+    // 最后一个事件没有以空行结尾，随后连接直接关闭
+    let body = delta_event("第一句。") + delta_event("第二句。").trim_end();
+    let stand_in = StandIn::start(StatusCode::OK, split_every(body.as_bytes(), 7), false).await;
+
+    let (result, deltas) = stream(&stand_in).await;
+
+    assert_eq!(result.unwrap(), "第一句。第二句。");
+    assert_eq!(deltas, vec!["第一句。", "第二句。"]);
+}
+
+#[tokio::test]
+async fn non_success_status_is_an_error() {
+    let body = br#"{"error":{"message":"rate limited"}}"#.to_vec();
+    let stand_in = StandIn::start(StatusCode::TOO_MANY_REQUESTS, vec![body], false).await;
+
+    let (result, deltas) = stream(&stand_in).await;
+
+    let error = result.unwrap_err();
+    assert!(error.contains("429"), "{}", error);
+    assert!(error.contains("rate limited"), "{}", error);
+    assert!(deltas.is_empty());
+}
+
+#[tokio::test]
+async fn cancel_closes_the_connection() {
+    let body = delta_event("说到一半");
+    let mut stand_in = StandIn::start(StatusCode::OK, vec![body.into_bytes()], true).await;
+    let registry = ChatStreamRegistry::default();
+    let first_delta = Notify::new();
+    let config = stand_in.config();
+    let client = reqwest::Client::new();
+
+    let request = registry.run(
+        "stream-1",
+        chat_completion_stream(&client, &config, user_message("你好"), |_| {
+            first_delta.notify_one()
+        }),
+    );
+    let cancel = async {
+        first_delta.notified().await;
+        assert!(registry.is_running("stream-1"));
+        assert!(registry.cancel("stream-1"));
+    };
+    let (result, ()) = tokio::time::timeout(Duration::from_secs(5), async {
+        tokio::join!(request, cancel)
+    })
+    .await
+    .expect("取消后请求应当立即结束");
+
+    assert!(result.is_none());
+    assert!(!registry.is_running("stream-1"));
+    assert!(!registry.cancel("stream-1"), "已经结束的请求不能再取消");
+    tokio::time::timeout(Duration::from_secs(5), stand_in.closed.take().unwrap())
+        .await
+        .expect("取消后连接应当关闭")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn newer_request_replaces_the_older_one() {
+    let body = delta_event("旧的");
+    let stand_in = StandIn::start(StatusCode::OK, vec![body.into_bytes()], true).await;
+    let registry = ChatStreamRegistry::default();
+    let config = stand_in.config();
+    let client = reqwest::Client::new();
+    let started = Notify::new();
+
+    let older = registry.run(
+        "stream-1",
+        chat_completion_stream(&client, &config, user_message("旧的"), |_| {
+            started.notify_one()
+        }),
+    );
+    let newer = async {
+        started.notified().await;
+        registry.run("stream-1", async { "新的" }).await
+    };
+    let (older, newer) =
+        tokio::time::timeout(Duration::from_secs(5), async { tokio::join!(older, newer) })
+            .await
+            .expect("旧请求应当被取消");
+
+    assert!(older.is_none());
+    assert_eq!(newer, Some("新的"));
+    assert!(!registry.is_running("stream-1"));
+}
+
+#[tokio::test]
+async fn dropped_request_is_unregistered() {
+    let body = delta_event("说到一半");
+    let mut stand_in = StandIn::start(StatusCode::OK, vec![body.into_bytes()], true).await;
+    let registry = ChatStreamRegistry::default();
+    let first_delta = Notify::new();
+    let config = stand_in.config();
+    let client = reqwest::Client::new();
+
+    // 模拟醒目留言被撤回时调用方直接丢弃请求
+    let mut request = Box::pin(registry.run(
+        "stream-1",
+        chat_completion_stream(&client, &config, user_message("你好"), |_| {
+            first_delta.notify_one()
+        }),
+    ));
+    tokio::select! {
+        _ = &mut request => panic!("请求不应当自己结束"),
+        _ = first_delta.notified() => {}
+    }
+    assert!(registry.is_running("stream-1"));
+    drop(request);
+
+    assert!(!registry.is_running("stream-1"));
+    assert!(!registry.cancel("stream-1"));
+    tokio::time::timeout(Duration::from_secs(5), stand_in.closed.take().unwrap())
+        .await
+        .expect("丢弃请求后连接应当关闭")
+        .unwrap();
+}
@@ -0,0 +1,51 @@
+//! `services::persona` 的提示词模板测试
+
+use aivtuber_lib::services::persona::{PersonaConfig, PromptBuilder, PromptContext};
+
+/// 北京时间 2024-01-01 20:00
+const EVENING: i64 = 1_704_110_400;
+
+fn builder(name: &str, template: &str) -> PromptBuilder {
+    PromptBuilder::new(PersonaConfig {
+        name: name.to_string(),
+        streamer_name: "小明".to_string(),
+        system_prompt: template.to_string(),
+        ..Default::default()
+    })
+}
+
+#[test]
+fn variables_are_filled_in() {
+    let context = PromptContext {
+        stream_title: Some("深夜杂谈".to_string()),
+        ..Default::default()
+    };
+    let prompt = builder(
+        "小爱",
+        "{name}在{streamer}的直播间，{time_of_day}，「{title}」，{area}",
+    )
+    .system_prompt_at(&context, EVENING);
+
+    assert_eq!(prompt, "小爱在小明的直播间，晚上，「深夜杂谈」，未知");
+}
+
+#[test]
+fn substituted_values_are_not_expanded_again() {
+    let context = PromptContext {
+        stream_title: Some("{viewer}的{name}".to_string()),
+        viewer_name: Some("观众{area}".to_string()),
+        ..Default::default()
+    };
+    let prompt =
+        builder("{title}酱", "{name}|{title}|{viewer}").system_prompt_at(&context, EVENING);
+
+    assert_eq!(prompt, "{title}酱|{viewer}的{name}|观众{area}");
+}
+
+#[test]
+fn unknown_placeholders_are_kept() {
+    let prompt = builder("小爱", "{name}{{name}}{unknown}{")
+        .system_prompt_at(&PromptContext::default(), EVENING);
+
+    assert_eq!(prompt, "小爱{小爱}{unknown}{");
+}
@@ -0,0 +1,420 @@
+//! `core::proto` 的解包与消息解析测试
+//!
+//! `fixtures/messages` 下是抓取的业务消息载荷，`fixtures/golden` 下是解析后再序列化的期望结果。
+//! 修改载荷结构后可以用 `UPDATE_GOLDEN=1 cargo test --test proto` 重新生成期望结果。
+
+use aivtuber_lib::core::{
+    BilibiliMessage, ParseDiagnostics, Proto, ProtoDecoder, ProtoError, VER_BROTLI, VER_HEARTBEAT,
+    VER_NORMAL, VER_ZLIB,
+};
+use serde_json::Value;
+use std::io::Write;
+use std::path::PathBuf;
+
+fn fixture_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+fn load_fixture(name: &str) -> String {
+    let path = fixture_dir()
+        .join("messages")
+        .join(format!("{}.json", name));
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("读取 {:?} 失败: {}", path, e))
+}
+
+/// 解析载荷，并把重新序列化的结果与期望结果比较
+fn parse_golden(name: &str) -> BilibiliMessage {
+    let message: BilibiliMessage = serde_json::from_str(&load_fixture(name))
+        .unwrap_or_else(|e| panic!("解析 {} 失败: {}", name, e));
+    let actual = serde_json::to_value(&message).unwrap();
+
+    let path = fixture_dir().join("golden").join(format!("{}.json", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut json = serde_json::to_string_pretty(&actual).unwrap();
+        json.push('\n');
+        std::fs::write(&path, json).unwrap();
+    }
+    let expected: Value = serde_json::from_str(
+        &std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("读取 {:?} 失败: {}", path, e)),
+    )
+    .unwrap();
+    assert_eq!(actual, expected, "{} 的解析结果与期望不一致", name);
+    message
+}
+
+fn packet(ver: u16, op: u32, body: &[u8]) -> Vec<u8> {
+    let mut proto = Proto::new();
+    proto.ver = ver;
+    proto.op = op;
+    proto.body = body.to_vec();
+    proto.pack()
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn brotli(data: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    {
+        let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+        writer.write_all(data).unwrap();
+    }
+    compressed
+}
+
+fn bodies(packets: &[Proto]) -> Vec<String> {
+    packets
+        .iter()
+        .map(|packet| packet.get_body_string().unwrap())
+        .collect()
+}
+
+#[test]
+fn danmaku() {
+    let BilibiliMessage::Danmaku { data } = parse_golden("danmaku") else {
+        panic!("应解析为 Danmaku");
+    };
+    assert_eq!(data.msg, "晚上好呀");
+    assert_eq!(data.room_id, 7734200);
+    assert!(data.fans_medal_wearing_status);
+
+    let BilibiliMessage::Danmaku { data } = parse_golden("danmaku_emoji") else {
+        panic!("应解析为 Danmaku");
+    };
+    assert_eq!(data.dm_type, 1);
+    assert!(data.emoji_img_url.is_some_and(|url| url.ends_with(".png")));
+}
+
+#[test]
+fn gift() {
+    let BilibiliMessage::Gift { data } = parse_golden("gift") else {
+        panic!("应解析为 Gift");
+    };
+    assert_eq!(data.gift_name, "小花花");
+    assert_eq!(data.anchor_info.uname, "小凉冰");
+    assert!(data.combo_info.is_none());
+
+    let BilibiliMessage::Gift { data } = parse_golden("gift_combo") else {
+        panic!("应解析为 Gift");
+    };
+    let combo = data.combo_info.expect("连击礼物应带 combo_info");
+    assert_eq!(combo.combo_count, 3);
+    assert_eq!(
+        data.blind_gift.map(|blind| blind.blind_gift_id),
+        Some(32251)
+    );
+}
+
+#[test]
+fn super_chat() {
+    let BilibiliMessage::SuperChat { data } = parse_golden("super_chat") else {
+        panic!("应解析为 SuperChat");
+    };
+    assert_eq!(data.message_id, 1049213);
+    assert_eq!(data.rmb, 30);
+
+    let BilibiliMessage::SuperChatDel { data } = parse_golden("super_chat_del") else {
+        panic!("应解析为 SuperChatDel");
+    };
+    assert_eq!(data.message_ids, vec![1049213]);
+}
+
+#[test]
+fn guard() {
+    let BilibiliMessage::Guard { data } = parse_golden("guard") else {
+        panic!("应解析为 Guard");
+    };
+    assert_eq!(data.user_info.uname, "凉拌海带丝");
+    assert_eq!(data.guard_level, 3);
+    assert_eq!(data.guard_unit, "月");
+}
+
+#[test]
+fn like() {
+    let BilibiliMessage::Like { data } = parse_golden("like") else {
+        panic!("应解析为 Like");
+    };
+    assert_eq!(data.like_count, 5);
+    assert_eq!(data.guard_level, None);
+}
+
+#[test]
+fn live_room_enter() {
+    let BilibiliMessage::LiveRoomEnter { data } = parse_golden("live_room_enter") else {
+        panic!("应解析为 LiveRoomEnter");
+    };
+    assert_eq!(data.uname, "路过的观众");
+}
+
+#[test]
+fn live_start_and_end() {
+    let BilibiliMessage::LiveStart { data } = parse_golden("live_start") else {
+        panic!("应解析为 LiveStart");
+    };
+    assert_eq!(data.title, "深夜杂谈，来聊聊天吧");
+    assert_eq!(data.area_name, "虚拟主播");
+
+    let BilibiliMessage::LiveEnd { data } = parse_golden("live_end") else {
+        panic!("应解析为 LiveEnd");
+    };
+    assert_eq!(data.timestamp, 1716289200);
+}
+
+#[test]
+fn interaction_end() {
+    let BilibiliMessage::InteractionEnd { data } = parse_golden("interaction_end") else {
+        panic!("应解析为 InteractionEnd");
+    };
+    assert_eq!(data.game_id, "e3f1c6a2-8b7d-4c59-a0e4-1d2c3b4a5f60");
+}
+
+#[test]
+fn unknown_cmd_is_forwarded() {
+    let before = ParseDiagnostics::global().snapshot();
+
+    let message = parse_golden("unknown_cmd");
+    let BilibiliMessage::Unknown { cmd, data } = &message else {
+        panic!("应解析为 Unknown");
+    };
+    assert_eq!(cmd, "LIVE_OPEN_PLATFORM_NEW_FEATURE");
+    assert_eq!(data["payload"]["tags"][1], "b");
+    assert_eq!(message.cmd(), "LIVE_OPEN_PLATFORM_NEW_FEATURE");
+    ParseDiagnostics::global().record_message(&message);
+
+    let BilibiliMessage::Unknown { data, .. } = parse_golden("unknown_without_data") else {
+        panic!("应解析为 Unknown");
+    };
+    assert!(data.is_null());
+
+    let after = ParseDiagnostics::global().snapshot();
+    assert!(after.messages_unknown > before.messages_unknown);
+}
+
+#[test]
+fn known_cmd_with_bad_shape_falls_back_to_unknown() {
+    let before = ParseDiagnostics::global().snapshot();
+
+    let message = parse_golden("known_cmd_bad_shape");
+    let BilibiliMessage::Unknown { cmd, data } = &message else {
+        panic!("结构不匹配时应回退为 Unknown");
+    };
+    assert_eq!(cmd, "LIVE_OPEN_PLATFORM_LIKE");
+    assert_eq!(data, "not an object");
+    ParseDiagnostics::global().record_message(&message);
+
+    let after = ParseDiagnostics::global().snapshot();
+    assert!(after.messages_failed > before.messages_failed);
+}
+
+#[test]
+fn lenient_fields_are_coerced() {
+    let before = ParseDiagnostics::global().snapshot();
+
+    let BilibiliMessage::Gift { data } = parse_golden("lenient_gift") else {
+        panic!("类型不符的字段不应导致整条消息解析失败");
+    };
+    assert_eq!(data.room_id, 7734200);
+    assert_eq!(data.gift_num, 2);
+    assert_eq!(data.price, 100);
+    assert!(data.paid);
+    assert!(data.fans_medal_wearing_status);
+    assert_eq!(data.combo_gift, Some(false));
+    assert_eq!(data.combo_info.map(|combo| combo.combo_count), Some(3));
+
+    let after = ParseDiagnostics::global().snapshot();
+    assert!(after.fields_coerced > before.fields_coerced);
+}
+
+#[test]
+fn lenient_fields_fall_back_to_defaults() {
+    let before = ParseDiagnostics::global().snapshot();
+
+    let BilibiliMessage::Danmaku { data } = parse_golden("lenient_danmaku_missing_fields") else {
+        panic!("缺少字段不应导致整条消息解析失败");
+    };
+    assert_eq!(data.msg, "字段被精简过的弹幕");
+    assert_eq!(data.timestamp, 1716282373);
+    assert_eq!(data.fans_medal_level, 0);
+    assert_eq!(data.uface, "");
+
+    let after = ParseDiagnostics::global().snapshot();
+    assert!(after.fields_defaulted > before.fields_defaulted);
+
+    // 值为 null 的字段同样回退为默认值
+    let message = serde_json::from_str(&load_fixture("lenient_gift")).unwrap();
+    let BilibiliMessage::Gift { data } = message else {
+        panic!("应解析为 Gift");
+    };
+    assert_eq!(data.r_price, 0);
+    assert_eq!(data.anchor_info.uname, "");
+}
+
+#[test]
+fn decode_concatenated_frames_of_every_version() {
+    let danmaku = load_fixture("danmaku");
+    let like = load_fixture("like");
+    let nested = [
+        packet(VER_NORMAL, 5, danmaku.as_bytes()),
+        packet(VER_NORMAL, 5, like.as_bytes()),
+    ]
+    .concat();
+
+    let frame = [
+        packet(VER_NORMAL, 5, danmaku.as_bytes()),
+        packet(VER_ZLIB, 5, &zlib(&nested)),
+        packet(VER_BROTLI, 5, &brotli(&nested)),
+        packet(VER_HEARTBEAT, 3, &1234u32.to_be_bytes()),
+    ]
+    .concat();
+
+    let packets = ProtoDecoder::decode_all(&frame).unwrap();
+    assert_eq!(
+        bodies(&packets),
+        vec![
+            danmaku.clone(),
+            danmaku.clone(),
+            like.clone(),
+            danmaku.clone(),
+            like.clone(),
+            "1234".to_string(),
+        ]
+    );
+    assert!(packets[..5].iter().all(|packet| packet.op == 5));
+    assert_eq!(packets[5].op, 3);
+
+    let messages: Vec<BilibiliMessage> = packets[..5]
+        .iter()
+        .map(|packet| serde_json::from_str(&packet.get_body_string().unwrap()).unwrap())
+        .collect();
+    assert!(matches!(messages[2], BilibiliMessage::Like { .. }));
+}
+
+#[test]
+fn decode_frames_split_at_every_offset() {
+    let danmaku = load_fixture("danmaku");
+    let gift = load_fixture("gift");
+    let nested = packet(VER_NORMAL, 5, gift.as_bytes());
+
+    for ver in [VER_NORMAL, VER_ZLIB, VER_BROTLI] {
+        let body = match ver {
+            VER_ZLIB => zlib(&nested),
+            VER_BROTLI => brotli(&nested),
+            _ => gift.as_bytes().to_vec(),
+        };
+        let stream = [
+            packet(VER_NORMAL, 5, danmaku.as_bytes()),
+            packet(ver, 5, &body),
+        ]
+        .concat();
+
+        for split in 1..stream.len() {
+            let mut decoder = ProtoDecoder::new();
+            let mut packets = Vec::new();
+            for chunk in [&stream[..split], &stream[split..]] {
+                decoder.feed(chunk);
+                while let Some(packet) = decoder.next_packet().unwrap() {
+                    packets.push(packet);
+                }
+            }
+            assert_eq!(
+                bodies(&packets),
+                vec![danmaku.clone(), gift.clone()],
+                "ver={} 在第 {} 字节处切分",
+                ver,
+                split
+            );
+            assert_eq!(decoder.buffered_len(), 0);
+        }
+    }
+}
+
+#[test]
+fn decode_byte_by_byte() {
+    let gift = load_fixture("gift_combo");
+    let stream = packet(
+        VER_BROTLI,
+        5,
+        &brotli(&packet(VER_NORMAL, 5, gift.as_bytes())),
+    );
+
+    let mut decoder = ProtoDecoder::new();
+    let mut packets = Vec::new();
+    for byte in &stream {
+        decoder.feed(std::slice::from_ref(byte));
+        packets.extend(decoder.by_ref().map(Result::unwrap));
+    }
+    assert_eq!(bodies(&packets), vec![gift]);
+}
+
+#[test]
+fn decode_all_rejects_truncated_frames() {
+    let frame = packet(VER_NORMAL, 5, load_fixture("like").as_bytes());
+
+    let err = ProtoDecoder::decode_all(&frame[..frame.len() - 1]).unwrap_err();
+    assert!(
+        matches!(err, ProtoError::IncompletePacket { .. }),
+        "{}",
+        err
+    );
+
+    let err = ProtoDecoder::decode_all(&frame[..10]).unwrap_err();
+    assert!(
+        matches!(err, ProtoError::IncompleteHeader { len: 10 }),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn invalid_header_clears_buffer() {
+    let mut frame = packet(VER_NORMAL, 5, b"{}");
+    // 包头长度字段改成非法值
+    frame[5] = 20;
+
+    let mut decoder = ProtoDecoder::new();
+    decoder.feed(&frame);
+    assert!(matches!(
+        decoder.next_packet(),
+        Err(ProtoError::InvalidHeaderLength(20))
+    ));
+    assert_eq!(decoder.buffered_len(), 0);
+
+    // 清空后可以继续解析新的数据
+    decoder.feed(&packet(VER_NORMAL, 5, b"{}"));
+    assert!(decoder.next_packet().unwrap().is_some());
+}
+
+#[test]
+fn corrupted_compressed_body_is_an_error() {
+    let frame = packet(VER_ZLIB, 5, b"definitely not zlib");
+    let err = ProtoDecoder::decode_all(&frame).unwrap_err();
+    assert!(matches!(err, ProtoError::Decompress(_)), "{}", err);
+}
+
+#[test]
+fn nested_compressed_packets_are_rejected() {
+    // 解压后又是压缩包，层层嵌套时每层都在上限以内，总量却会成倍放大
+    let inner = packet(VER_NORMAL, 5, load_fixture("danmaku").as_bytes());
+    let twice = packet(VER_BROTLI, 5, &brotli(&packet(VER_ZLIB, 5, &zlib(&inner))));
+    let err = ProtoDecoder::decode_all(&twice).unwrap_err();
+    assert!(
+        matches!(err, ProtoError::NestedCompression { ver: VER_ZLIB }),
+        "{}",
+        err
+    );
+
+    // 同一帧后面的数据仍然可以继续解析
+    let mut decoder = ProtoDecoder::new();
+    decoder.feed(&[twice, packet(VER_ZLIB, 5, &zlib(&inner))].concat());
+    assert!(matches!(
+        decoder.next_packet(),
+        Err(ProtoError::NestedCompression { .. })
+    ));
+    let packet = decoder.next_packet().unwrap().unwrap();
+    assert_eq!(packet.ver, VER_NORMAL);
+    assert!(decoder.next_packet().unwrap().is_none());
+}
@@ -0,0 +1,102 @@
+//! `services::sentence` 的增量分句测试
+
+use aivtuber_lib::services::sentence::SentenceSplitter;
+
+/// 依次追加 `chunks`，返回每次追加得到的句子和 `finish` 取出的最后一句
+fn split(chunks: &[&str]) -> (Vec<Vec<String>>, Option<String>) {
+    let mut splitter = SentenceSplitter::default();
+    let pushed = chunks.iter().map(|chunk| splitter.push(chunk)).collect();
+    (pushed, splitter.finish())
+}
+
+/// 所有句子连在一起
+fn sentences(chunks: &[&str]) -> Vec<String> {
+    let (pushed, rest) = split(chunks);
+    pushed.into_iter().flatten().chain(rest).collect()
+}
+
+#[test]
+fn full_width_terminators_split() {
+    assert_eq!(
+        sentences(&["晚上好。今天好开心！你吃饭了吗？我先说；然后……好吧"]),
+        vec![
+            "晚上好。",
+            "今天好开心！",
+            "你吃饭了吗？",
+            "我先说；",
+            "然后……",
+            "好吧"
+        ]
+    );
+}
+
+#[test]
+fn repeated_terminators_and_closers_stay_together() {
+    assert_eq!(
+        sentences(&["真的吗？！「当然。」他说。"]),
+        vec!["真的吗？！", "「当然。」", "他说。"]
+    );
+}
+
+#[test]
+fn ascii_terminators_need_a_following_space() {
+    assert_eq!(
+        sentences(&["Hello! How are you? Fine."]),
+        vec!["Hello!", "How are you?", "Fine."]
+    );
+    // 后面直接跟英文时不断开
+    assert_eq!(sentences(&["Hi!there"]), vec!["Hi!there"]);
+}
+
+#[test]
+fn ascii_terminators_before_cjk_split() {
+    // 半角的 ! ? ; 后面直接跟中文也算句末
+    assert_eq!(
+        sentences(&["你好!我是小爱?对;没错"]),
+        vec!["你好!", "我是小爱?", "对;", "没错"]
+    );
+    // 半角的 . 后面跟中文多半是序号，不断开
+    assert_eq!(sentences(&["1.第一点"]), vec!["1.第一点"]);
+}
+
+#[test]
+fn decimals_and_abbreviations_are_not_split() {
+    assert_eq!(
+        sentences(&["价格是3.5元，版本v1.2.3。e.g.这样"]),
+        vec!["价格是3.5元，版本v1.2.3。", "e.g.这样"]
+    );
+}
+
+#[test]
+fn sentences_span_push_boundaries() {
+    // 句末标点后面还没收到字符时不能确定句子是否结束
+    let (pushed, rest) = split(&["你好", "呀。", "”我", "是3.", "5岁!", "真的"]);
+    assert_eq!(
+        pushed,
+        vec![
+            Vec::<String>::new(),
+            vec![],
+            vec!["你好呀。”".to_string()],
+            vec![],
+            vec![],
+            vec!["我是3.5岁!".to_string()],
+        ]
+    );
+    assert_eq!(rest.as_deref(), Some("真的"));
+}
+
+#[test]
+fn finish_flushes_the_rest() {
+    let mut splitter = SentenceSplitter::default();
+    assert!(splitter.push("没有标点的结尾").is_empty());
+    assert_eq!(splitter.finish().as_deref(), Some("没有标点的结尾"));
+    assert_eq!(splitter.finish(), None, "取出后缓冲区为空");
+
+    // 以句末标点结尾时也由 finish 取出
+    assert!(splitter.push("最后一句。").is_empty());
+    assert_eq!(splitter.finish().as_deref(), Some("最后一句。"));
+
+    // 只有标点或空白的片段被丢弃
+    assert_eq!(splitter.push("好。 。\n"), vec!["好。"]);
+    assert_eq!(splitter.finish(), None);
+}
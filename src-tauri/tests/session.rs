@@ -0,0 +1,266 @@
+//! `services::session` 的测试：用 `MockSource` 和录制回放驱动会话运行器
+
+use aivtuber_lib::core::{BilibiliMessage, ConnectionEvent, Proto, VER_NORMAL};
+use aivtuber_lib::services::combo::{ComboConfig, ComboFinished};
+use aivtuber_lib::services::pipeline::{PipelineConfig, PipelineStats};
+use aivtuber_lib::services::recorder::{ReplayConfig, SessionRecorder};
+use aivtuber_lib::services::session::{SessionContext, SessionOutput, run_session};
+use aivtuber_lib::services::source::{LiveEventSource, MockSource, ReplaySource};
+use aivtuber_lib::services::stats::LiveStats;
+use aivtuber_lib::services::superchat::SuperChatRegistry;
+use aivtuber_lib::services::viewer::{ViewerProfile, ViewerRegistry};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const SESSION: &str = "test";
+
+fn fixture(name: &str) -> BilibiliMessage {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/messages")
+        .join(format!("{}.json", name));
+    let json =
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("读取 {:?} 失败: {}", path, e));
+    serde_json::from_str(&json).unwrap_or_else(|e| panic!("解析 {} 失败: {}", name, e))
+}
+
+/// 收集所有输出
+#[derive(Default)]
+struct Collector {
+    connections: Mutex<Vec<ConnectionEvent>>,
+    batches: Mutex<Vec<Vec<BilibiliMessage>>>,
+    viewers: Mutex<Vec<ViewerProfile>>,
+    combos: Mutex<Vec<ComboFinished>>,
+}
+
+impl Collector {
+    fn messages(&self) -> Vec<BilibiliMessage> {
+        self.batches.lock().unwrap().concat()
+    }
+}
+
+impl SessionOutput for Collector {
+    fn connection(&self, event: &ConnectionEvent) {
+        self.connections.lock().unwrap().push(event.clone());
+    }
+
+    fn messages(&self, messages: &[BilibiliMessage]) {
+        self.batches.lock().unwrap().push(messages.to_vec());
+    }
+
+    fn viewer_returned(&self, profile: &ViewerProfile) {
+        self.viewers.lock().unwrap().push(profile.clone());
+    }
+
+    fn combo_finished(&self, summary: &ComboFinished) {
+        self.combos.lock().unwrap().push(summary.clone());
+    }
+}
+
+struct Harness {
+    stats: Arc<LiveStats>,
+    superchats: Arc<SuperChatRegistry>,
+    pipeline_stats: Arc<PipelineStats>,
+    output: Arc<Collector>,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let stats = Arc::new(LiveStats::default());
+        stats.start(SESSION);
+        Self {
+            stats,
+            superchats: Arc::new(SuperChatRegistry::default()),
+            pipeline_stats: Arc::new(PipelineStats::default()),
+            output: Arc::new(Collector::default()),
+        }
+    }
+
+    /// 运行一个已经连接的事件源，等连击聚合任务也结束后返回
+    async fn run(
+        &self,
+        source: &mut impl LiveEventSource,
+        pipeline: PipelineConfig,
+        combo: ComboConfig,
+    ) -> Option<&'static str> {
+        let context = SessionContext {
+            session: SESSION.to_string(),
+            stats: self.stats.clone(),
+            archive: None,
+            viewers: Arc::new(ViewerRegistry::in_memory()),
+            superchats: self.superchats.clone(),
+            pipeline,
+            pipeline_stats: self.pipeline_stats.clone(),
+            combo,
+        };
+        let end_reason = run_session(source, context, self.output.clone()).await;
+        // 连击聚合任务结束时释放它持有的输出端
+        while Arc::strong_count(&self.output) > 1 {
+            tokio::task::yield_now().await;
+        }
+        end_reason
+    }
+}
+
+fn disabled_combo() -> ComboConfig {
+    ComboConfig {
+        enabled: false,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn stats_count_messages_the_pipeline_drops() {
+    let harness = Harness::new();
+    let mut messages = vec![fixture("like"); 20];
+    messages.push(fixture("live_end"));
+    let mut source = MockSource::new(messages, Duration::ZERO);
+    source.connect().await.unwrap();
+    let pipeline = PipelineConfig {
+        capacity: 8,
+        low_priority_limit: 2,
+        batch_interval_ms: 0,
+        ..Default::default()
+    };
+
+    let end_reason = harness.run(&mut source, pipeline, disabled_combo()).await;
+
+    assert_eq!(end_reason, Some("直播已结束"));
+    let emitted = harness.output.messages();
+    let likes = emitted
+        .iter()
+        .filter(|message| matches!(message, BilibiliMessage::Like { .. }))
+        .count();
+    let dropped = harness.pipeline_stats.snapshot().dropped;
+    assert!(dropped > 0, "点赞应当在积压时被丢弃");
+    assert_eq!(likes as u64 + dropped, 20);
+    assert!(matches!(
+        emitted.last(),
+        Some(BilibiliMessage::LiveEnd { .. })
+    ));
+
+    // 统计在丢弃之前完成，20 条点赞全部计入
+    let snapshot = harness.stats.snapshot(SESSION, false).unwrap();
+    assert_eq!(snapshot.likes, 20 * 5);
+    assert_eq!(snapshot.total_messages, 21);
+}
+
+#[tokio::test]
+async fn bookkeeping_sees_gifts_before_coalescing() {
+    let harness = Harness::new();
+    let messages = vec![
+        fixture("live_start"),
+        fixture("gift_combo"),
+        fixture("gift_combo"),
+        fixture("gift_combo"),
+        fixture("super_chat"),
+        fixture("super_chat_del"),
+    ];
+    let mut source = MockSource::new(messages, Duration::ZERO);
+    source.connect().await.unwrap();
+    let pipeline = PipelineConfig {
+        batch_interval_ms: 0,
+        ..Default::default()
+    };
+
+    // 没有结束消息，事件源发完后结束
+    let end_reason = harness
+        .run(&mut source, pipeline, ComboConfig::default())
+        .await;
+    assert_eq!(end_reason, None);
+
+    // 同一批次中的连击礼物被合并为一条
+    let gifts: Vec<i64> = harness
+        .output
+        .messages()
+        .iter()
+        .filter_map(|message| match message {
+            BilibiliMessage::Gift { data } => Some(data.gift_num),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(gifts, vec![3]);
+    assert_eq!(harness.pipeline_stats.snapshot().coalesced, 2);
+
+    // 统计和连击聚合看到的是合并前的三条礼物
+    let snapshot = harness.stats.snapshot(SESSION, false).unwrap();
+    assert_eq!(snapshot.paid_gifts.events, 3);
+    assert_eq!(snapshot.paid_gifts.num, 3);
+    assert_eq!(snapshot.title.as_deref(), Some("深夜杂谈，来聊聊天吧"));
+    let combos = harness.output.combos.lock().unwrap();
+    assert_eq!(combos.len(), 1);
+    assert_eq!(combos[0].events, 3);
+    assert_eq!(combos[0].total_num, 3);
+
+    assert!(harness.superchats.is_retracted(1049213));
+
+    let connections = harness.output.connections.lock().unwrap();
+    assert!(matches!(
+        connections.as_slice(),
+        [
+            ConnectionEvent::Connecting { .. },
+            ConnectionEvent::Authenticated { .. },
+            ConnectionEvent::Closed { .. },
+        ]
+    ));
+}
+
+#[tokio::test]
+async fn closed_mock_source_ends_the_session() {
+    let harness = Harness::new();
+    let mut source = MockSource::new(vec![fixture("danmaku"); 3], Duration::ZERO);
+    source.connect().await.unwrap();
+    source.close().await.unwrap();
+
+    let end_reason = harness
+        .run(&mut source, PipelineConfig::default(), disabled_combo())
+        .await;
+
+    assert_eq!(end_reason, None);
+    assert!(harness.output.messages().is_empty());
+    let connections = harness.output.connections.lock().unwrap();
+    assert!(matches!(
+        connections.last(),
+        Some(ConnectionEvent::Closed { reason, .. }) if reason == "主动断开连接"
+    ));
+}
+
+#[tokio::test]
+async fn replay_source_runs_through_the_session() {
+    let path = std::env::temp_dir().join(format!("aivtuber-replay-{}.bin", std::process::id()));
+    let recorder = SessionRecorder::create(&path).unwrap();
+    for name in ["danmaku", "live_end"] {
+        let mut proto = Proto::new();
+        proto.ver = VER_NORMAL;
+        proto.op = 5;
+        proto.body = serde_json::to_vec(&fixture(name)).unwrap();
+        recorder.record(&proto.pack()).unwrap();
+    }
+    while recorder.frames() < 2 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    drop(recorder);
+
+    let harness = Harness::new();
+    let mut source = ReplaySource::new(ReplayConfig {
+        path: path.clone(),
+        speed: 1000.0,
+    });
+    source.connect().await.unwrap();
+    let end_reason = harness
+        .run(&mut source, PipelineConfig::default(), disabled_combo())
+        .await;
+    source.close().await.unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(end_reason, Some("直播已结束"));
+    let emitted = harness.output.messages();
+    assert!(matches!(
+        emitted.as_slice(),
+        [
+            BilibiliMessage::Danmaku { .. },
+            BilibiliMessage::LiveEnd { .. }
+        ]
+    ));
+    assert_eq!(harness.stats.snapshot(SESSION, false).unwrap().danmaku, 1);
+}
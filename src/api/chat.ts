@@ -1,4 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { ConversationSnapshot, SamplingParams } from '../types/bilibili.types';
 
 // 类型定义
 export interface ChatResponse {
@@ -12,6 +14,27 @@ export interface ChatAndSpeakResponse {
     message: string;
     chat_content?: string;
     audio_data?: number[];
+    audio_chunks?: number; // 流式生成时语音按句推送的段数，此时不返回 audio_data
+}
+
+// 流式对话的增量内容（openai-stream-delta 事件）
+export interface ChatStreamDelta {
+    stream_id: string;
+    delta: string;
+}
+
+// 按句合成的一段语音（tts-audio-chunk 事件），按 sequence 顺序播放
+export interface SpeechChunk {
+    stream_id: string;
+    sequence: number;
+    text: string;
+    audio_data?: number[]; // 该句合成失败时为空
+}
+
+// 流式回复没有生成完就被放弃（openai-stream-cancelled 事件），已推送的语音不再有后续
+export interface ChatStreamCancelled {
+    stream_id: string;
+    reason: string;
 }
 
 export interface TtsResponse {
@@ -31,20 +54,44 @@ export function bytesToBlob(bytes: number[], mimeType: string = 'audio/wav'): Bl
 }
 
 /**
- * OpenAI 对话
+ * OpenAI 对话，接口地址、密钥和模型来自配置文件
+ * session 决定使用哪段对话记忆，sampling 中设置了的项覆盖配置中的采样参数，只对本次调用生效
  */
 export async function chatWithOpenAI(
     message: string,
-    apiKey: string,
-    model: string = 'gpt-4o',
-    temperature: number = 0.7
+    session?: string,
+    sampling?: SamplingParams
 ): Promise<ChatResponse> {
     try {
         return await invoke<ChatResponse>('chat_with_openai', {
             message,
-            apiKey,
-            model,
-            temperature,
+            session,
+            sampling,
+        });
+    } catch (error) {
+        return {
+            success: false,
+            message: error instanceof Error ? error.message : '未知错误',
+        };
+    }
+}
+
+/**
+ * 流式对话，增量内容通过 onChatStreamDelta 接收，返回完整回复
+ * session 和 sampling 的含义与 chatWithOpenAI 相同
+ */
+export async function chatWithOpenAIStream(
+    message: string,
+    streamId: string,
+    session?: string,
+    sampling?: SamplingParams
+): Promise<ChatResponse> {
+    try {
+        return await invoke<ChatResponse>('chat_with_openai_stream', {
+            message,
+            streamId,
+            session,
+            sampling,
         });
     } catch (error) {
         return {
@@ -54,6 +101,55 @@ export async function chatWithOpenAI(
     }
 }
 
+/**
+ * 取消进行中的流式对话，返回是否找到了对应的请求
+ */
+export async function cancelChatStream(streamId: string): Promise<boolean> {
+    return await invoke<boolean>('cancel_chat_stream', { streamId });
+}
+
+/**
+ * 监听流式对话的增量内容，只接收指定 streamId 的内容
+ */
+export async function onChatStreamDelta(
+    streamId: string,
+    handler: (delta: string) => void
+): Promise<UnlistenFn> {
+    return await listen<ChatStreamDelta>('openai-stream-delta', (event) => {
+        if (event.payload.stream_id === streamId) {
+            handler(event.payload.delta);
+        }
+    });
+}
+
+/**
+ * 监听按句合成的语音，只接收指定 streamId 的语音
+ */
+export async function onSpeechChunk(
+    streamId: string,
+    handler: (chunk: SpeechChunk) => void
+): Promise<UnlistenFn> {
+    return await listen<SpeechChunk>('tts-audio-chunk', (event) => {
+        if (event.payload.stream_id === streamId) {
+            handler(event.payload);
+        }
+    });
+}
+
+/**
+ * 监听流式回复被放弃的通知（取消或醒目留言被撤回），只接收指定 streamId 的通知
+ */
+export async function onChatStreamCancelled(
+    streamId: string,
+    handler: (reason: string) => void
+): Promise<UnlistenFn> {
+    return await listen<ChatStreamCancelled>('openai-stream-cancelled', (event) => {
+        if (event.payload.stream_id === streamId) {
+            handler(event.payload.reason);
+        }
+    });
+}
+
 /**
  * 文本转语音
  */
@@ -71,11 +167,24 @@ export async function textToSpeech(text: string): Promise<TtsResponse> {
 /**
  * 对话 + TTS（推荐使用）
  * 后端集成处理，减少通信开销
+ * 回复醒目留言时传入 superChatId，留言被撤回后后端会放弃生成
+ * 传入 streamId 时以流式方式生成回复，语音按句通过 onSpeechChunk 推送，可用 cancelChatStream 取消
+ * context 中的会话和观众 open_id 用于在提示词中加入直播标题和观众档案，sampling 覆盖本次调用的采样参数
  */
-export async function chatAndSpeak(userMessage: string): Promise<ChatAndSpeakResponse> {
+export async function chatAndSpeak(
+    userMessage: string,
+    superChatId?: number,
+    streamId?: string,
+    context: { session?: string; openId?: string; sampling?: SamplingParams } = {}
+): Promise<ChatAndSpeakResponse> {
     try {
         return await invoke<ChatAndSpeakResponse>('chat_and_speak', {
             message: userMessage,
+            superChatId,
+            streamId,
+            session: context.session,
+            openId: context.openId,
+            sampling: context.sampling,
         });
     } catch (error) {
         return {
@@ -85,6 +194,30 @@ export async function chatAndSpeak(userMessage: string): Promise<ChatAndSpeakRes
     }
 }
 
+/**
+ * 查看对话记忆，openId 为空时为整个会话共用的对话
+ */
+export async function getConversation(
+    session?: string,
+    openId?: string
+): Promise<ConversationSnapshot | null> {
+    return await invoke<ConversationSnapshot | null>('get_conversation', { session, openId });
+}
+
+/**
+ * 列出对话记忆，最近更新的在前
+ */
+export async function listConversations(session?: string): Promise<ConversationSnapshot[]> {
+    return await invoke<ConversationSnapshot[]>('list_conversations', { session });
+}
+
+/**
+ * 清空对话记忆，openId 为空时清空整个会话，返回清空的对话数
+ */
+export async function resetConversation(session?: string, openId?: string): Promise<number> {
+    return await invoke<number>('reset_conversation', { session, openId });
+}
+
 /**
  * 播放音频字节数组
  */
@@ -1,6 +1,14 @@
 import { ref, computed } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
-import type { BilibiliConfig } from '../types/bilibili.types'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
+import type {
+  BilibiliConfig,
+  BilibiliError,
+  ConnectionEvent,
+  SessionStatsSnapshot,
+  SessionSummary,
+  SessionTagged
+} from '../types/bilibili.types'
 
 export function useBilibiliConnection() {
   // 连接状态
@@ -26,6 +34,37 @@ export function useBilibiliConnection() {
     connectTime: null as Date | null
   })
 
+  // 后端定时推送的直播统计，以此为准覆盖本地计数
+  let statsUnlisten: UnlistenFn | null = null
+  const startStatsListener = async () => {
+    if (statsUnlisten) return
+    statsUnlisten = await listen('bilibili-stats', (event) => {
+      const stats = event.payload as SessionStatsSnapshot
+      if (stats.session !== 'default') return
+      connectionStats.value.totalMessages = stats.total_messages
+      connectionStats.value.danmuCount = stats.danmaku
+      connectionStats.value.giftCount = stats.paid_gifts.events + stats.free_gifts.events
+    })
+  }
+  const stopStatsListener = () => {
+    statsUnlisten?.()
+    statsUnlisten = null
+  }
+
+  // 直播结束、互动结束或重连失败时后端会自动关闭会话
+  let sessionEndedUnlisten: UnlistenFn | null = null
+  const startSessionEndedListener = async () => {
+    if (sessionEndedUnlisten) return
+    sessionEndedUnlisten = await listen('bilibili-session-ended', (event) => {
+      const summary = event.payload as SessionTagged<SessionSummary>
+      if (summary.session !== 'default') return
+      isConnected.value = false
+      connectionStatus.value = '已断开: ' + summary.reason
+      connectionStats.value.connectTime = null
+      stopStatsListener()
+    })
+  }
+
   // 计算属性
   const isConnectionReady = computed(() => 
     bilibiliConfig.value.id_code && 
@@ -46,10 +85,13 @@ export function useBilibiliConnection() {
       isConnected.value = true
       connectionStatus.value = '已连接'
       connectionStats.value.connectTime = new Date()
+      await startStatsListener()
+      await startSessionEndedListener()
       console.log('连接成功:', result)
     } catch (error) {
       console.error('连接失败:', error)
-      connectionStatus.value = '连接失败: ' + error
+      const err = error as BilibiliError
+      connectionStatus.value = '连接失败: ' + (err.message ?? error)
       isConnected.value = false
     }
   }
@@ -57,6 +99,7 @@ export function useBilibiliConnection() {
   const disconnectBilibili = async () => {
     try {
       await invoke('disconnect_bilibili')
+      stopStatsListener()
       isConnected.value = false
       connectionStatus.value = '未连接'
       connectionStats.value.connectTime = null
@@ -70,9 +113,13 @@ export function useBilibiliConnection() {
 
   const checkConnectionStatus = async () => {
     try {
-      const status = await invoke('get_connection_status')
-      isConnected.value = status as boolean
-      connectionStatus.value = status ? '已连接' : '未连接'
+      const status = await invoke<ConnectionEvent>('get_connection_status')
+      isConnected.value = ['authenticated', 'heartbeat_lost', 'heartbeat_recovered'].includes(status.state)
+      if (status.state === 'heartbeat_lost') {
+        connectionStatus.value = '连接不稳定: ' + status.reason
+      } else {
+        connectionStatus.value = isConnected.value ? '已连接' : '未连接'
+      }
     } catch (error) {
       console.error('检查连接状态失败:', error)
     }
@@ -1,19 +1,30 @@
 import { onUnmounted } from 'vue'
 import { listen, type UnlistenFn } from '@tauri-apps/api/event'
-import type { BilibiliLiveMessage } from '../types/bilibili.types'
+import type { BilibiliLiveMessage, ComboFinished, SessionTagged } from '../types/bilibili.types'
 
 export function useBilibiliEventListener() {
   let messageUnlisten: UnlistenFn | null = null
+  let comboUnlisten: UnlistenFn | null = null
 
   const startListening = async (
-    onMessage: (message: BilibiliLiveMessage) => void
+    onMessage: (message: SessionTagged<BilibiliLiveMessage>) => void,
+    onCombo?: (combo: SessionTagged<ComboFinished>) => void
   ) => {
     try {
-      messageUnlisten = await listen('bilibili-message', (event) => {
-        const message = event.payload as BilibiliLiveMessage
-        console.log('收到bilibili消息:', message)
-        onMessage(message)
+      // 后端按批次发送消息
+      messageUnlisten = await listen('bilibili-messages', (event) => {
+        const { session, messages } = event.payload as { session: string; messages: BilibiliLiveMessage[] }
+        console.log(`收到bilibili消息 ${messages.length} 条:`, session)
+        for (const message of messages) {
+          onMessage({ ...message, session } as SessionTagged<BilibiliLiveMessage>)
+        }
       })
+      // 礼物连击结束后的汇总，用于只答谢一次
+      if (onCombo) {
+        comboUnlisten = await listen('bilibili-combo', (event) => {
+          onCombo(event.payload as SessionTagged<ComboFinished>)
+        })
+      }
       console.log('开始监听bilibili消息')
     } catch (error) {
       console.error('启动消息监听失败:', error)
@@ -26,6 +37,10 @@ export function useBilibiliEventListener() {
       messageUnlisten = null
       console.log('停止监听bilibili消息')
     }
+    if (comboUnlisten) {
+      comboUnlisten()
+      comboUnlisten = null
+    }
   }
 
   // 生命周期钩子
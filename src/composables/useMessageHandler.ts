@@ -14,7 +14,79 @@ import type {
   InteractionEndMessage
 } from '../types/bilibili.types'
 import { LivePlatformCmd } from '../types/bilibili.types'
-import { chatAndSpeak, bytesToArrayBuffer } from '../api/chat'
+import {
+  chatAndSpeak,
+  bytesToArrayBuffer,
+  onSpeechChunk,
+  onChatStreamCancelled,
+  type SpeechChunk
+} from '../api/chat'
+
+// 用于计算语音时长，前一句播完再播下一句
+let durationContext: AudioContext | null = null
+
+const audioDuration = async (audioData: ArrayBuffer): Promise<number> => {
+  try {
+    durationContext ??= new AudioContext()
+    // decodeAudioData 会占用传入的缓冲区，这里解码一份副本
+    const buffer = await durationContext.decodeAudioData(audioData.slice(0))
+    return buffer.duration
+  } catch (error) {
+    console.warn('计算语音时长失败:', error)
+    return 0
+  }
+}
+
+// 按 sequence 顺序排队播放按句推送的语音，合成失败的句子直接跳过
+const createSpeechQueue = (playAudio: (audioData: ArrayBuffer) => void) => {
+  const pending = new Map<number, SpeechChunk>()
+  let nextSequence = 0
+  let cancelled = false
+  let playback = Promise.resolve()
+
+  const play = async (audioData: ArrayBuffer) => {
+    if (cancelled) return
+    const duration = await audioDuration(audioData)
+    if (cancelled) return
+    playAudio(audioData)
+    await new Promise((resolve) => setTimeout(resolve, duration * 1000))
+  }
+
+  return {
+    push(chunk: SpeechChunk) {
+      if (cancelled) return
+      pending.set(chunk.sequence, chunk)
+      // 先到的后续句子留在 pending 中，等前面的句子到齐再播放
+      let next = pending.get(nextSequence)
+      while (next) {
+        pending.delete(nextSequence)
+        nextSequence++
+        if (next.audio_data) {
+          const audioData = bytesToArrayBuffer(next.audio_data)
+          playback = playback.then(() => play(audioData))
+        }
+        next = pending.get(nextSequence)
+      }
+    },
+    // 放弃还没有播放的句子
+    cancel() {
+      cancelled = true
+      pending.clear()
+    },
+    // 已经按顺序收到的句子数
+    get received() {
+      return nextSequence
+    }
+  }
+}
+
+// 等待条件成立，最多等待 timeoutMs 毫秒
+const waitUntil = async (condition: () => boolean, timeoutMs: number) => {
+  const deadline = Date.now() + timeoutMs
+  while (!condition() && Date.now() < deadline) {
+    await new Promise((resolve) => setTimeout(resolve, 50))
+  }
+}
 
 export function useMessageHandler() {
   // 消息状态
@@ -160,21 +232,64 @@ export function useMessageHandler() {
     }
   }
 
+  // 流式生成回复，每合成完一句就按顺序播放；回复被取消或醒目留言被撤回时停止播放剩余的句子
+  const replyWithSpeech = async (
+    message: string,
+    superChatId: number | undefined,
+    openId: string,
+    playAudio: (audioData: ArrayBuffer) => void
+  ) => {
+    const streamId = `reply-${Date.now()}-${Math.random().toString(36).slice(2)}`
+    const queue = createSpeechQueue(playAudio)
+    const unlistenChunk = await onSpeechChunk(streamId, (chunk) => queue.push(chunk))
+    const unlistenCancelled = await onChatStreamCancelled(streamId, (reason) => {
+      console.log('AI回复已放弃:', reason)
+      queue.cancel()
+    })
+    try {
+      const chatResp = await chatAndSpeak(message, superChatId, streamId, { openId })
+      if (!chatResp.success) {
+        queue.cancel()
+        return
+      }
+      // 事件和命令结果不保证先后顺序，等所有语音都收到后再停止监听
+      const total = chatResp.audio_chunks ?? 0
+      await waitUntil(() => queue.received >= total, 5000)
+    } finally {
+      unlistenChunk()
+      unlistenCancelled()
+    }
+  }
+
   // 处理弹幕消息并生成AI回复
   const processDanmuMessage = async (
     danmuData: DanmakuMessage,
     playAudio: (audioData: ArrayBuffer) => void
   ) => {
     try {
-      const chatResp = await chatAndSpeak(danmuData.msg)
-      if (chatResp.audio_data) {
-        playAudio(bytesToArrayBuffer(chatResp.audio_data))
-      }
+      await replyWithSpeech(danmuData.msg, undefined, danmuData.open_id, playAudio)
     } catch (error) {
       console.error('处理弹幕AI回复失败:', error)
     }
   }
 
+  // 处理醒目留言并生成AI回复，留言被撤回后后端放弃生成，尚未播放的语音不再播放
+  const processSuperChatMessage = async (
+    superChatData: SuperChatMessage,
+    playAudio: (audioData: ArrayBuffer) => void
+  ) => {
+    try {
+      await replyWithSpeech(
+        `（${superChatData.rmb}元醒目留言）${superChatData.message}`,
+        superChatData.message_id,
+        superChatData.open_id,
+        playAudio
+      )
+    } catch (error) {
+      console.error('处理醒目留言AI回复失败:', error)
+    }
+  }
+
   // 添加消息的主要处理函数
   const addMessage = async (
     message: BilibiliLiveMessage,
@@ -221,6 +336,11 @@ export function useMessageHandler() {
       }
     }
 
+    // 醒目留言同样交给AI回复
+    if (message.cmd === LivePlatformCmd.SUPER_CHAT && options.playAudio) {
+      await processSuperChatMessage(message.data as SuperChatMessage, options.playAudio)
+    }
+
     // 保留所有类型消息，但限制总数
     if (messages.value.length > 200) {
       messages.value = messages.value.slice(0, 200)
@@ -241,6 +361,7 @@ export function useMessageHandler() {
     // 方法
     formatMessage,
     processDanmuMessage,
+    processSuperChatMessage,
     addMessage,
     clearMessages
   }
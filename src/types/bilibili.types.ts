@@ -14,6 +14,258 @@ export interface BilibiliConfig {
   access_key: string;
   access_secret: string;
   host: string;
+  record_path?: string; // 录制原始WebSocket帧的文件路径
+  replay?: { path: string; speed?: number }; // 从录制文件回放，无需凭据
+  pipeline?: PipelineConfig;
+  combo?: { enabled?: boolean; default_timeout_ms?: number }; // 礼物连击聚合
+  heartbeat?: HeartbeatConfig;
+  persona?: PersonaConfig;
+  memory?: MemoryConfig;
+}
+
+// 对话记忆配置，近期对话超出 token 预算后移入由大模型生成的滚动摘要
+export interface MemoryConfig {
+  enabled?: boolean;
+  per_viewer?: boolean; // 按观众 open_id 分别记忆
+  max_tokens?: number; // 近期对话的 token 预算（估算值）
+  min_messages?: number; // 始终保留的最近消息条数
+  summarize?: boolean; // 关闭时移出窗口的对话直接丢弃
+  summary_max_chars?: number;
+}
+
+// 一段对话记忆（get_conversation / list_conversations）
+export interface ConversationSnapshot {
+  session: string;
+  open_id?: string; // 为空表示整个会话共用的对话
+  summary?: string;
+  turns: { role: 'user' | 'assistant'; content: string; timestamp: number }[];
+  tokens: number;
+  pending_summary: number; // 等待合并进摘要的消息条数
+  updated_at: number;
+}
+
+// 采样参数，可写在 openai 配置中，也可在单次调用时覆盖
+// 未列出的字段（服务商特有的参数）原样放进请求体
+export interface SamplingParams {
+  temperature?: number; // 0 到 2
+  top_p?: number; // 0 到 1
+  max_tokens?: number;
+  presence_penalty?: number; // -2 到 2
+  frequency_penalty?: number; // -2 到 2
+  stop?: string[]; // 最多 4 个
+  seed?: number;
+  [key: string]: unknown;
+}
+
+// 人设配置，system_prompt 支持 {name} {streamer} {title} {area} {time_of_day} {viewer} 变量
+export interface PersonaConfig {
+  name?: string; // 角色名
+  streamer_name?: string; // 主播名
+  system_prompt?: string;
+  speaking_style?: string;
+  banned_topics?: string[];
+  utc_offset_hours?: number; // 计算时段用的时区，默认 8
+}
+
+// 心跳配置，连续未回复或失败达到上限后重连
+export interface HeartbeatConfig {
+  ws_interval_ms?: number;
+  ws_max_missed?: number; // WebSocket 心跳连续未回复的上限
+  app_interval_ms?: number;
+  app_max_failures?: number; // 应用心跳连续失败的上限
+  app_retry_delay_ms?: number; // 应用心跳失败后的重试间隔
+}
+
+// 心跳健康状态与延迟（get_heartbeat_stats），延迟单位为毫秒
+export interface HeartbeatStats {
+  health: 'healthy' | 'degraded' | 'dead';
+  ws_missed: number;
+  ws_replies: number;
+  ws_last_latency_ms?: number;
+  ws_avg_latency_ms?: number;
+  app_failures: number;
+  app_successes: number;
+  app_last_latency_ms?: number;
+  app_avg_latency_ms?: number;
+}
+
+// 一次礼物连击结束后的汇总（bilibili-combo 事件）
+export interface ComboFinished {
+  combo_key: string; // combo_id，没有时为 open_id:gift_id
+  room_id: number;
+  uid: number;
+  open_id: string;
+  uname: string;
+  uface: string;
+  gift_id: number;
+  gift_name: string;
+  total_num: number;
+  total_value: number; // 单位与 price 相同（1/1000 元）
+  paid: boolean;
+  events: number;
+  first_timestamp: number;
+  last_timestamp: number;
+}
+
+// 消息管道配置，积压时先丢弃点赞和进房消息，礼物、SC、大航海不会丢弃
+export interface PipelineConfig {
+  capacity?: number;
+  low_priority_limit?: number; // 积压达到该数量后丢弃点赞和进房消息
+  batch_interval_ms?: number; // 打包发送间隔，0 表示立即发送
+  max_batch_size?: number;
+  coalesce_combo?: boolean; // 合并同一批次中的连击礼物
+}
+
+// 消息管道计数
+export interface PipelineStats {
+  sent: number;
+  dropped: number;
+  coalesced: number;
+  batches: number;
+}
+
+// 本地模拟开放平台配置（start_mock_server），省略时使用默认凭据和示例脚本
+export interface MockConfig {
+  id_code: string;
+  app_id: number;
+  access_key: string;
+  access_secret: string;
+  script?: { delay_ms?: number; message: Record<string, unknown>; compressed?: boolean }[];
+  close_after_ms?: number; // 服务端主动断开WebSocket的毫秒数，用于测试重连
+}
+
+// 连接生命周期事件（bilibili-connection 事件及 get_connection_status 返回值）
+export type ConnectionEvent =
+  | { state: 'connecting'; timestamp: number }
+  | { state: 'authenticated'; timestamp: number }
+  | { state: 'auth_failed'; reason: string; timestamp: number }
+  | { state: 'heartbeat_lost'; reason: string; timestamp: number } // 连接降级但仍在使用
+  | { state: 'heartbeat_recovered'; timestamp: number }
+  | { state: 'reconnecting'; attempt: number; delay_ms: number; reason: string; timestamp: number }
+  | { state: 'closed'; reason: string; timestamp: number }
+  | { state: 'interaction_end'; game_id: string; timestamp: number };
+
+// 多直播间时事件附带的会话名称，未指定时为 'default'
+export type SessionTagged<T> = T & { session: string };
+
+// list_bilibili_sessions 返回的会话状态
+export interface SessionStatus {
+  session: string;
+  room_id?: number; // 主播直播间号，开启游戏会话后才有
+  status: ConnectionEvent;
+  pipeline: PipelineStats;
+  heartbeat: HeartbeatStats;
+}
+
+// 会话结束汇总（bilibili-session-ended 事件）
+export interface SessionSummary {
+  reason: string; // 直播已结束、互动玩法已结束、重连失败原因或主动断开连接
+  room_id?: number;
+  stats?: SessionStatsSnapshot;
+  record_path?: string;
+}
+
+// 醒目留言登记状态（list_super_chats / get_super_chat_status）
+export type SuperChatStatus = 'active' | 'expired' | 'retracted';
+
+export interface SuperChatEntry {
+  session: string;
+  status: SuperChatStatus;
+  data: SuperChatMessage;
+}
+
+// 直播统计（bilibili-stats 事件、get_live_stats），礼物价值单位为 1/1000 元
+export interface GiftTotals {
+  events: number;
+  num: number;
+  price: number;
+  r_price: number;
+}
+
+export interface MinuteStats {
+  minute: number; // 该分钟开始的毫秒时间戳
+  danmaku: number;
+  paid_gift_value: number;
+  free_gift_value: number;
+  super_chat_rmb: number;
+  guards: number;
+  likes: number;
+  enters: number;
+}
+
+export interface SessionStatsSnapshot {
+  session: string;
+  started_at: number;
+  last_message_at?: number;
+  ended_at?: number;
+  title?: string; // 最近一次开播消息中的直播标题
+  area_name?: string;
+  total_messages: number;
+  paid_gifts: GiftTotals;
+  free_gifts: GiftTotals;
+  super_chats: number;
+  super_chat_rmb: number;
+  guards: Record<string, { events: number; num: number; price: number }>; // 键为 guard_level
+  danmaku: number;
+  unique_danmaku_senders: number;
+  likes: number;
+  enters: number;
+  minutes: MinuteStats[]; // 定时推送的事件中为空
+}
+
+// 消息归档查询条件（query_archive），各条件为“且”的关系
+export interface ArchiveQuery {
+  session?: string;
+  room_id?: number;
+  open_id?: string; // 按观众查询
+  cmds?: string[]; // 按消息类型查询，如 LIVE_OPEN_PLATFORM_SEND_GIFT
+  since?: number; // 接收时间范围（毫秒），包含起点不包含终点
+  until?: number;
+  text?: string; // 弹幕全文检索
+  before_id?: number; // 向前翻页
+  limit?: number; // 默认 100，最多 1000
+}
+
+export interface ArchivedEvent {
+  id: number;
+  session: string;
+  room_id?: number;
+  cmd: string;
+  open_id?: string;
+  uname?: string;
+  received_at: number;
+  message: BilibiliLiveMessage;
+}
+
+// 观众档案，时间为毫秒时间戳，礼物价值单位为 1/1000 元
+export interface ViewerProfile {
+  open_id: string;
+  uname: string;
+  uface: string;
+  name_history: string[]; // 曾用名，最近的在后
+  first_seen: number;
+  last_seen: number;
+  visits: number; // 间隔超过 30 分钟算一次新的来访
+  visit_started: number;
+  previous_seen?: number;
+  message_count: number;
+  gift_value: number;
+  super_chat_count: number;
+  super_chat_rmb: number;
+  guard_level: number;
+  guard_purchased_at?: number;
+  fans_medal_name: string;
+  fans_medal_level: number;
+}
+
+export type ViewerOrder = 'recent' | 'gift_value' | 'messages';
+
+// 后端返回的Bilibili错误
+export interface BilibiliError {
+  kind: 'transport' | 'protocol' | 'auth' | 'api' | 'config';
+  code?: number; // 开放平台错误码
+  message: string;
+  retryable: boolean; // 稍后重试是否可能成功
 }
 
 // 主播信息
@@ -216,7 +468,14 @@ export type BilibiliLiveMessage =
   | LivePlatformMessage<RoomEnterMessage>
   | LivePlatformMessage<LiveStartMessage>
   | LivePlatformMessage<LiveEndMessage>
-  | LivePlatformMessage<InteractionEndMessage>;
+  | LivePlatformMessage<InteractionEndMessage>
+  | UnknownLiveMessage;
+
+// 后端未收录的消息类型，原样转发 cmd 和 data
+export interface UnknownLiveMessage {
+  cmd: string;
+  data: unknown;
+}
 
 // 格式化后的消息类型
 export interface FormattedMessage {